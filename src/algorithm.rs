@@ -0,0 +1,311 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::ops::Range;
+
+/// Lines that occur more often than this are never used as histogram anchors
+const MAX_HISTOGRAM_COUNT: usize = 64;
+
+/// How to line up the two inputs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Algorithm {
+    /// Shortest edit script, the classic diff algorithm
+    #[default]
+    Myers,
+    /// Anchor on lines that occur exactly once on each side, which keeps moved blocks of code intact
+    Patience,
+    /// Anchor on the rarest lines common to both sides, like git's histogram diff
+    Histogram,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Edit {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// The edits turning 'left' into 'right'. Within each change, deletions come before insertions
+pub(crate) fn diff<T: Hash + Eq>(left: &[T], right: &[T], algorithm: Algorithm) -> Vec<Edit> {
+    let mut edits = Vec::with_capacity(left.len().max(right.len()));
+    diff_range(
+        left,
+        right,
+        0..left.len(),
+        0..right.len(),
+        algorithm,
+        &mut edits,
+    );
+    canonicalize(edits)
+}
+
+/// Like [`diff`], for items that can only be compared for equality. Always uses Myers
+pub(crate) fn diff_eq<T: PartialEq>(left: &[T], right: &[T]) -> Vec<Edit> {
+    let (mut l, mut r) = (0..left.len(), 0..right.len());
+    let mut edits = Vec::with_capacity(left.len().max(right.len()));
+    while !l.is_empty() && !r.is_empty() && left[l.start] == right[r.start] {
+        edits.push(Edit::Equal(l.start, r.start));
+        l.start += 1;
+        r.start += 1;
+    }
+    if l.is_empty() {
+        edits.extend(r.map(Edit::Insert));
+    } else if r.is_empty() {
+        edits.extend(l.map(Edit::Delete));
+    } else {
+        myers(left, right, l, r, &mut edits);
+    }
+    canonicalize(edits)
+}
+
+fn diff_range<T: Hash + Eq>(
+    left: &[T],
+    right: &[T],
+    mut l: Range<usize>,
+    mut r: Range<usize>,
+    algorithm: Algorithm,
+    edits: &mut Vec<Edit>,
+) {
+    while !l.is_empty() && !r.is_empty() && left[l.start] == right[r.start] {
+        edits.push(Edit::Equal(l.start, r.start));
+        l.start += 1;
+        r.start += 1;
+    }
+    let mut suffix = Vec::new();
+    while !l.is_empty() && !r.is_empty() && left[l.end - 1] == right[r.end - 1] {
+        l.end -= 1;
+        r.end -= 1;
+        suffix.push(Edit::Equal(l.end, r.end));
+    }
+
+    if l.is_empty() {
+        edits.extend(r.map(Edit::Insert));
+    } else if r.is_empty() {
+        edits.extend(l.map(Edit::Delete));
+    } else {
+        match algorithm {
+            Algorithm::Myers => myers(left, right, l, r, edits),
+            Algorithm::Patience => patience(left, right, l, r, edits),
+            Algorithm::Histogram => histogram(left, right, l, r, edits),
+        }
+    }
+    edits.extend(suffix.into_iter().rev());
+}
+
+/// Myers' O(ND) algorithm, keeping every round's furthest reaching paths to backtrack through
+fn myers<T: PartialEq>(
+    left: &[T],
+    right: &[T],
+    l: Range<usize>,
+    r: Range<usize>,
+    edits: &mut Vec<Edit>,
+) {
+    let n = l.len() as isize;
+    let m = r.len() as isize;
+    let offset = n + m;
+    let index = |k: isize| (k + offset) as usize;
+    // Whether the path to diagonal 'k' in round 'd' comes down from k + 1 (an insertion)
+    let from_above =
+        |v: &[isize], k: isize, d: isize| k == -d || (k != d && v[index(k - 1)] < v[index(k + 1)]);
+
+    let mut v = vec![0isize; 2 * offset as usize + 2];
+    let mut trace = Vec::new();
+    'rounds: for d in 0..=offset {
+        trace.push(v.clone());
+        for k in (-d..=d).step_by(2) {
+            let mut x = if from_above(&v, k, d) {
+                v[index(k + 1)]
+            } else {
+                v[index(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && left[l.start + x as usize] == right[r.start + y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[index(k)] = x;
+            if x >= n && y >= m {
+                break 'rounds;
+            }
+        }
+    }
+
+    let mut script = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let prev_k = if from_above(v, k, d) { k + 1 } else { k - 1 };
+        let prev_x = v[index(prev_k)];
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            script.push(Edit::Equal(l.start + x as usize, r.start + y as usize));
+        }
+        if d > 0 {
+            if x == prev_x {
+                y -= 1;
+                script.push(Edit::Insert(r.start + y as usize));
+            } else {
+                x -= 1;
+                script.push(Edit::Delete(l.start + x as usize));
+            }
+        }
+    }
+    edits.extend(script.into_iter().rev());
+}
+
+/// Diff the gaps between 'anchors', matched pairs of indices increasing on both sides
+fn diff_between<T: Hash + Eq>(
+    left: &[T],
+    right: &[T],
+    l: Range<usize>,
+    r: Range<usize>,
+    anchors: &[(Range<usize>, Range<usize>)],
+    algorithm: Algorithm,
+    edits: &mut Vec<Edit>,
+) {
+    let (mut i, mut j) = (l.start, r.start);
+    for (left_anchor, right_anchor) in anchors {
+        diff_range(
+            left,
+            right,
+            i..left_anchor.start,
+            j..right_anchor.start,
+            algorithm,
+            edits,
+        );
+        edits.extend(
+            left_anchor
+                .clone()
+                .zip(right_anchor.clone())
+                .map(|(a, b)| Edit::Equal(a, b)),
+        );
+        (i, j) = (left_anchor.end, right_anchor.end);
+    }
+    diff_range(left, right, i..l.end, j..r.end, algorithm, edits);
+}
+
+fn patience<T: Hash + Eq>(
+    left: &[T],
+    right: &[T],
+    l: Range<usize>,
+    r: Range<usize>,
+    edits: &mut Vec<Edit>,
+) {
+    // Occurrences on each side and where the line was last seen
+    let mut counts: HashMap<&T, (usize, usize, usize, usize)> = HashMap::new();
+    for i in l.clone() {
+        let entry = counts.entry(&left[i]).or_default();
+        entry.0 += 1;
+        entry.2 = i;
+    }
+    for j in r.clone() {
+        if let Some(entry) = counts.get_mut(&right[j]) {
+            entry.1 += 1;
+            entry.3 = j;
+        }
+    }
+
+    let mut unique: Vec<(usize, usize)> = counts
+        .values()
+        .filter(|(in_left, in_right, _, _)| *in_left == 1 && *in_right == 1)
+        .map(|&(_, _, i, j)| (i, j))
+        .collect();
+    if unique.is_empty() {
+        return myers(left, right, l, r, edits);
+    }
+    unique.sort_unstable();
+
+    let anchors: Vec<_> = longest_increasing(&unique)
+        .into_iter()
+        .map(|(i, j)| (i..i + 1, j..j + 1))
+        .collect();
+    diff_between(left, right, l, r, &anchors, Algorithm::Patience, edits);
+}
+
+/// The longest run of pairs (sorted by left index) whose right indices also increase
+fn longest_increasing(pairs: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    // Patience sorting: the top of each pile, plus a link from each pair to its predecessor
+    let mut piles: Vec<usize> = Vec::new();
+    let mut previous = vec![None; pairs.len()];
+    for (n, &(_, j)) in pairs.iter().enumerate() {
+        let pile = piles.partition_point(|&top| pairs[top].1 < j);
+        previous[n] = pile.checked_sub(1).map(|p| piles[p]);
+        if pile == piles.len() {
+            piles.push(n);
+        } else {
+            piles[pile] = n;
+        }
+    }
+
+    let mut result = Vec::with_capacity(piles.len());
+    let mut current = piles.last().copied();
+    while let Some(n) = current {
+        result.push(pairs[n]);
+        current = previous[n];
+    }
+    result.reverse();
+    result
+}
+
+fn histogram<T: Hash + Eq>(
+    left: &[T],
+    right: &[T],
+    l: Range<usize>,
+    r: Range<usize>,
+    edits: &mut Vec<Edit>,
+) {
+    // How often each line occurs on the left, and its first position
+    let mut histogram: HashMap<&T, (usize, usize)> = HashMap::new();
+    for i in l.clone() {
+        histogram.entry(&left[i]).or_insert((0, i)).0 += 1;
+    }
+
+    let mut best: Option<(usize, usize, usize)> = None;
+    for j in r.clone() {
+        if let Some(&(count, i)) = histogram.get(&right[j])
+            && count <= MAX_HISTOGRAM_COUNT
+            && best.is_none_or(|(best_count, _, _)| count < best_count)
+        {
+            best = Some((count, i, j));
+        }
+    }
+    let Some((_, i, j)) = best else {
+        return myers(left, right, l, r, edits);
+    };
+
+    // Grow the anchor into the whole matching region around it
+    let (mut start_i, mut start_j) = (i, j);
+    while start_i > l.start && start_j > r.start && left[start_i - 1] == right[start_j - 1] {
+        start_i -= 1;
+        start_j -= 1;
+    }
+    let (mut end_i, mut end_j) = (i + 1, j + 1);
+    while end_i < l.end && end_j < r.end && left[end_i] == right[end_j] {
+        end_i += 1;
+        end_j += 1;
+    }
+
+    let anchor = [(start_i..end_i, start_j..end_j)];
+    diff_between(left, right, l, r, &anchor, Algorithm::Histogram, edits);
+}
+
+/// Order each run of changes as all of its deletions followed by all of its insertions
+fn canonicalize(edits: Vec<Edit>) -> Vec<Edit> {
+    let mut out = Vec::with_capacity(edits.len());
+    let mut inserts = Vec::new();
+    for edit in edits {
+        match edit {
+            Edit::Insert(_) => inserts.push(edit),
+            Edit::Delete(_) => out.push(edit),
+            Edit::Equal(..) => {
+                out.append(&mut inserts);
+                out.push(edit);
+            }
+        }
+    }
+    out.append(&mut inserts);
+    out
+}
@@ -0,0 +1,105 @@
+use anyhow::{Context, Result, bail};
+use different::{FilePatch, Patch};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+/// Where a file patch applies under 'target'.
+/// Git style 'a/' and 'b/' prefixes are dropped, and paths that would leave 'target' are rejected
+fn resolve(target: &Path, file: &FilePatch) -> Result<PathBuf> {
+    // Reversed patches have the prefixes swapped
+    let prefixed = |path: &str| path.starts_with("a/") || path.starts_with("b/");
+    let git_style = (file.creates() || prefixed(&file.old_path))
+        && (file.deletes() || prefixed(&file.new_path));
+    let path = if file.creates() {
+        &file.new_path
+    } else {
+        &file.old_path
+    };
+    let path = match path.split_once('/') {
+        Some((_, rest)) if git_style => rest,
+        _ => path,
+    };
+    let escapes = Path::new(path).components().any(|component| {
+        matches!(
+            component,
+            Component::ParentDir | Component::RootDir | Component::Prefix(_)
+        )
+    });
+    if escapes {
+        bail!(
+            "Refusing to patch {path}, it is outside of {}",
+            target.display()
+        );
+    }
+    Ok(target.join(path))
+}
+
+/// Apply the unified diff in 'patch' to 'target', a file or a directory the patch's paths are
+/// relative to. Nothing is written unless every file patch applies
+pub fn apply(patch: &Path, target: &Path, dry_run: bool, reverse: bool) -> Result<()> {
+    let text = if patch == Path::new("-") {
+        let mut text = String::new();
+        io::stdin()
+            .read_to_string(&mut text)
+            .context("Unable to read stdin")?;
+        text
+    } else {
+        fs::read_to_string(patch).with_context(|| format!("Unable to read {}", patch.display()))?
+    };
+
+    let mut patch = Patch::parse(&text)?;
+    if reverse {
+        patch = patch.reverse();
+    }
+
+    let targets: Vec<PathBuf> = if target.is_dir() {
+        patch
+            .files
+            .iter()
+            .map(|file| resolve(target, file))
+            .collect::<Result<_>>()?
+    } else if patch.files.len() == 1 {
+        vec![target.to_path_buf()]
+    } else {
+        bail!(
+            "Patch changes {} files, {} has to be a directory",
+            patch.files.len(),
+            target.display()
+        );
+    };
+
+    let mut results = Vec::new();
+    for (file, path) in patch.files.iter().zip(targets) {
+        let contents = if file.creates() {
+            String::new()
+        } else {
+            fs::read_to_string(&path)
+                .with_context(|| format!("Unable to read {}", path.display()))?
+        };
+        let patched = file
+            .apply(&contents)
+            .with_context(|| format!("Unable to patch {}", path.display()))?;
+        results.push((file, path, patched));
+    }
+
+    for (file, path, patched) in results {
+        if dry_run {
+            writeln!(io::stdout(), "checking file {}", path.display())?;
+            continue;
+        }
+
+        writeln!(io::stdout(), "patching file {}", path.display())?;
+        if file.deletes() {
+            fs::remove_file(&path)
+                .with_context(|| format!("Unable to remove {}", path.display()))?;
+        } else {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, patched)
+                .with_context(|| format!("Unable to write {}", path.display()))?;
+        }
+    }
+    Ok(())
+}
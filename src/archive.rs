@@ -0,0 +1,302 @@
+use crate::recursive::WalkOptions;
+use anyhow::{Context, Result, bail};
+use flate2::read::GzDecoder;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Files larger than this are only compared by hash, and reported without a diff if they differ
+const MAX_DIFF_SIZE: u64 = 16 * 1024 * 1024;
+
+/// What an archive entry is
+#[derive(Debug, PartialEq, Eq)]
+enum Kind {
+    /// A file's size and the SHA-256 of its contents, which aren't kept
+    File {
+        size: u64,
+        hash: [u8; 32],
+    },
+    Directory,
+    Link(String),
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::File { .. } => write!(f, "file"),
+            Self::Directory => write!(f, "directory"),
+            Self::Link(target) => write!(f, "link to {target}"),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    kind: Kind,
+    /// Permission bits, if the archive has them
+    mode: Option<u32>,
+}
+
+/// The entries of an archive, and the contents of the files that were asked for
+#[derive(Debug, Default)]
+struct Archive {
+    entries: BTreeMap<PathBuf, Entry>,
+    contents: BTreeMap<PathBuf, Vec<u8>>,
+}
+
+impl Archive {
+    /// Hash a file entry as it is read, keeping its contents only if they are 'wanted'
+    fn add_file(
+        &mut self,
+        path: PathBuf,
+        mode: Option<u32>,
+        mut reader: impl Read,
+        wanted: &BTreeSet<PathBuf>,
+    ) -> io::Result<()> {
+        let mut kept = wanted.contains(&path).then(Vec::new);
+        let mut hasher = Sha256::new();
+        let mut size = 0;
+        let mut buf = [0; 64 * 1024];
+        loop {
+            let n = match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            hasher.update(&buf[..n]);
+            if let Some(kept) = &mut kept {
+                kept.extend_from_slice(&buf[..n]);
+            }
+            size += n as u64;
+        }
+        if let Some(kept) = kept {
+            self.contents.insert(path.clone(), kept);
+        }
+        let kind = Kind::File {
+            size,
+            hash: hasher.finalize().into(),
+        };
+        self.entries.insert(path, Entry { kind, mode });
+        Ok(())
+    }
+}
+
+/// Whether 'path' is named like a .tar, .tar.gz, .tgz or .zip archive
+pub fn is_archive(path: &Path) -> bool {
+    let name = path.to_string_lossy().to_lowercase();
+    [".tar", ".tar.gz", ".tgz", ".zip"]
+        .iter()
+        .any(|extension| name.ends_with(extension))
+}
+
+/// Entry paths without a leading './' or trailing '/', so archives made either way compare equal
+fn entry_path(path: &str) -> PathBuf {
+    let path = path.trim_end_matches('/');
+    PathBuf::from(path.strip_prefix("./").unwrap_or(path))
+}
+
+fn read_tar(reader: impl Read, wanted: &BTreeSet<PathBuf>, archive: &mut Archive) -> Result<()> {
+    let mut tar = tar::Archive::new(reader);
+    for entry in tar.entries()? {
+        let entry = entry?;
+        let path = entry_path(&entry.path()?.to_string_lossy());
+        if path.as_os_str().is_empty() || path == Path::new(".") {
+            continue;
+        }
+        let header = entry.header();
+        let mode = header.mode().ok().map(|mode| mode & 0o7777);
+        let kind = match header.entry_type() {
+            tar::EntryType::Regular | tar::EntryType::Continuous => {
+                archive.add_file(path, mode, entry, wanted)?;
+                continue;
+            }
+            tar::EntryType::Directory => Kind::Directory,
+            tar::EntryType::Symlink | tar::EntryType::Link => {
+                let target = entry.link_name()?.unwrap_or_default();
+                Kind::Link(target.to_string_lossy().into_owned())
+            }
+            // Devices, fifos and extension headers have no contents to compare
+            _ => continue,
+        };
+        archive.entries.insert(path, Entry { kind, mode });
+    }
+    Ok(())
+}
+
+fn read_zip(file: File, wanted: &BTreeSet<PathBuf>, archive: &mut Archive) -> Result<()> {
+    let mut zip = zip::ZipArchive::new(BufReader::new(file))?;
+    for i in 0..zip.len() {
+        let mut file = zip.by_index(i)?;
+        let path = entry_path(file.name());
+        let mode = file.unix_mode().map(|mode| mode & 0o7777);
+        let kind = if file.is_dir() {
+            Kind::Directory
+        } else if file.is_symlink() {
+            // Symlinks are stored as files holding their target
+            let mut target = Vec::new();
+            file.read_to_end(&mut target)?;
+            Kind::Link(String::from_utf8_lossy(&target).into_owned())
+        } else {
+            archive.add_file(path, mode, file, wanted)?;
+            continue;
+        };
+        archive.entries.insert(path, Entry { kind, mode });
+    }
+    Ok(())
+}
+
+/// Read every entry of the archive at 'path', one at a time,
+/// keeping the contents of the 'wanted' files only
+fn read_archive(path: &Path, wanted: &BTreeSet<PathBuf>) -> Result<Archive> {
+    let file = File::open(path).with_context(|| format!("Unable to read {}", path.display()))?;
+    let name = path.to_string_lossy().to_lowercase();
+    let mut archive = Archive::default();
+    let read = if name.ends_with(".zip") {
+        read_zip(file, wanted, &mut archive)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        read_tar(GzDecoder::new(BufReader::new(file)), wanted, &mut archive)
+    } else {
+        read_tar(BufReader::new(file), wanted, &mut archive)
+    };
+    read.with_context(|| format!("Unable to read archive {}", path.display()))?;
+    Ok(archive)
+}
+
+/// Compare the entries of two archives, which can be of different kinds.
+/// Entries only in one archive and changes to their kind or permissions are reported.
+/// Files are compared by hash first, so the archives are read twice but only the contents
+/// of changed files are held, and only those up to [`MAX_DIFF_SIZE`] are handed to 'diff_file'
+/// with their path in the archive and contents.
+/// Returns true if the archives hold the same entries
+pub fn diff_archives(
+    left: &Path,
+    right: &Path,
+    options: &WalkOptions,
+    mut diff_file: impl FnMut(&Path, Vec<u8>, Vec<u8>) -> Result<bool>,
+) -> Result<bool> {
+    if !is_archive(left) || !is_archive(right) {
+        bail!("Only archives can be compared with archives");
+    }
+    let mut left_entries = read_archive(left, &BTreeSet::new())?.entries;
+    let mut right_entries = read_archive(right, &BTreeSet::new())?.entries;
+    let paths: BTreeSet<PathBuf> = left_entries
+        .keys()
+        .chain(right_entries.keys())
+        .filter(|path| !options.excluded(path))
+        .filter(|path| {
+            let depth = path.components().count();
+            options.max_depth.is_none_or(|max| depth <= max)
+        })
+        .cloned()
+        .collect();
+
+    // Read the changed files that are small enough to diff again, this time keeping them
+    let changed: BTreeSet<PathBuf> = paths
+        .iter()
+        .filter(|&path| {
+            match (
+                left_entries.get(path).map(|e| &e.kind),
+                right_entries.get(path).map(|e| &e.kind),
+            ) {
+                (
+                    Some(
+                        left @ Kind::File {
+                            size: left_size, ..
+                        },
+                    ),
+                    Some(
+                        right @ Kind::File {
+                            size: right_size, ..
+                        },
+                    ),
+                ) => left != right && left_size.max(right_size) <= &MAX_DIFF_SIZE,
+                _ => false,
+            }
+        })
+        .cloned()
+        .collect();
+    let (mut left_contents, mut right_contents) = if changed.is_empty() {
+        Default::default()
+    } else {
+        (
+            read_archive(left, &changed)?.contents,
+            read_archive(right, &changed)?.contents,
+        )
+    };
+
+    let mut same = true;
+    for path in paths {
+        let (left_entry, right_entry) =
+            match (left_entries.remove(&path), right_entries.remove(&path)) {
+                (Some(left_entry), Some(right_entry)) => (left_entry, right_entry),
+                (Some(_), None) => {
+                    writeln!(
+                        io::stdout(),
+                        "Only in {}: {}",
+                        left.display(),
+                        path.display()
+                    )?;
+                    same = false;
+                    continue;
+                }
+                _ => {
+                    writeln!(
+                        io::stdout(),
+                        "Only in {}: {}",
+                        right.display(),
+                        path.display()
+                    )?;
+                    same = false;
+                    continue;
+                }
+            };
+
+        if let (Some(left_mode), Some(right_mode)) = (left_entry.mode, right_entry.mode)
+            && left_mode != right_mode
+        {
+            writeln!(
+                io::stdout(),
+                "Mode of {} changed from {left_mode:o} to {right_mode:o}",
+                path.display()
+            )?;
+            same = false;
+        }
+        match (left_entry.kind, right_entry.kind) {
+            (left_kind @ Kind::File { .. }, right_kind @ Kind::File { .. }) => {
+                if left_kind == right_kind {
+                    continue;
+                }
+                match (left_contents.remove(&path), right_contents.remove(&path)) {
+                    (Some(left_file), Some(right_file)) => {
+                        same &= diff_file(&path, left_file, right_file)?;
+                    }
+                    _ => {
+                        writeln!(
+                            io::stdout(),
+                            "Files {}:{} and {}:{} differ",
+                            left.display(),
+                            path.display(),
+                            right.display(),
+                            path.display()
+                        )?;
+                        same = false;
+                    }
+                }
+            }
+            (left_kind, right_kind) if left_kind != right_kind => {
+                writeln!(
+                    io::stdout(),
+                    "{} changed from {left_kind} to {right_kind}",
+                    path.display()
+                )?;
+                same = false;
+            }
+            _ => {}
+        }
+    }
+    Ok(same)
+}
@@ -0,0 +1,79 @@
+use crate::{DiffSettings, line_diff};
+use std::fmt;
+
+/// Panic like `assert_eq!` does, with a line diff of 'left' and 'right' instead of both values
+#[doc(hidden)]
+#[track_caller]
+pub fn assert_failed(left: &str, right: &str, message: Option<fmt::Arguments<'_>>) -> ! {
+    let settings = DiffSettings::new();
+    let diff = line_diff(left, right, &settings);
+    let message = match message {
+        Some(message) => format!(": {message}"),
+        None => String::new(),
+    };
+    // Strings can differ in ways the line diff doesn't show, like a missing last newline
+    let shown = diff.to_string();
+    if shown.is_empty() {
+        panic!("assertion `left == right` failed{message}\n left: {left:?}\nright: {right:?}");
+    }
+    panic!("assertion `left == right` failed{message}\n{shown}");
+}
+
+/// Like `assert_eq!` for text, but panics with a colored, line-numbered diff of the two values.
+/// Both have to implement `AsRef<str>`, such as `String` and `&str`.
+/// A message with format arguments can follow, as with `assert_eq!`
+#[macro_export]
+macro_rules! assert_diff_eq {
+    ($left:expr, $right:expr $(,)?) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                let (left, right): (&str, &str) = (left.as_ref(), right.as_ref());
+                if left != right {
+                    $crate::assert::assert_failed(left, right, None);
+                }
+            }
+        }
+    };
+    ($left:expr, $right:expr, $($message:tt)+) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                let (left, right): (&str, &str) = (left.as_ref(), right.as_ref());
+                if left != right {
+                    $crate::assert::assert_failed(left, right, Some(format_args!($($message)+)));
+                }
+            }
+        }
+    };
+}
+
+/// Like [`assert_diff_eq!`] for any values that are `PartialEq` and `Debug`,
+/// diffing their pretty printed (`{:#?}`) forms
+#[macro_export]
+macro_rules! assert_debug_diff_eq {
+    ($left:expr, $right:expr $(,)?) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                if !(*left == *right) {
+                    $crate::assert::assert_failed(
+                        &format!("{left:#?}"),
+                        &format!("{right:#?}"),
+                        None,
+                    );
+                }
+            }
+        }
+    };
+    ($left:expr, $right:expr, $($message:tt)+) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                if !(*left == *right) {
+                    $crate::assert::assert_failed(
+                        &format!("{left:#?}"),
+                        &format!("{right:#?}"),
+                        Some(format_args!($($message)+)),
+                    );
+                }
+            }
+        }
+    };
+}
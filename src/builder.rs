@@ -0,0 +1,297 @@
+use crate::inline::CustomTokenizer;
+use crate::normalize::Normalizer;
+use crate::{
+    Algorithm, ColorMode, ControlChars, DiffSettings, Result, Substitution, Theme, Tokenizer,
+    UnicodeForm, WordDiff,
+};
+use colored::Color;
+use regex::Regex;
+use std::borrow::Cow;
+use std::sync::Arc;
+
+/// Chainable construction of [`DiffSettings`] for library users
+#[derive(Debug, Clone, Default)]
+pub struct DiffSettingsBuilder {
+    settings: DiffSettings,
+}
+
+impl From<DiffSettings> for DiffSettingsBuilder {
+    /// Continue building from existing settings, e.g. ones parsed from the command line
+    fn from(settings: DiffSettings) -> Self {
+        Self { settings }
+    }
+}
+
+impl DiffSettingsBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn names(mut self, left: String, right: String) -> Self {
+        self.settings.left_name = Some(left);
+        self.settings.right_name = Some(right);
+        self
+    }
+
+    pub fn left_name(mut self, name: impl Into<String>) -> Self {
+        self.settings.left_name = Some(name.into());
+        self
+    }
+
+    pub fn right_name(mut self, name: impl Into<String>) -> Self {
+        self.settings.right_name = Some(name.into());
+        self
+    }
+
+    /// Character the left header is drawn with, '-' by default
+    pub fn left_marker(mut self, marker: char) -> Self {
+        self.settings.left_marker = marker;
+        self
+    }
+
+    /// Character the right header is drawn with, '+' by default
+    pub fn right_marker(mut self, marker: char) -> Self {
+        self.settings.right_marker = marker;
+        self
+    }
+
+    /// How many markers start each header
+    pub fn marker_count(mut self, n: usize) -> Self {
+        self.settings.marker_count = n;
+        self
+    }
+
+    /// Spaces in front of every line of the diff
+    pub fn indent_spaces(mut self, n: usize) -> Self {
+        self.settings.indent_spaces = n;
+        self
+    }
+
+    pub fn show_line_numbers(mut self, enabled: bool) -> Self {
+        self.settings.no_line_numbers = !enabled;
+        self
+    }
+
+    /// Whether to start with the two lines naming the inputs
+    pub fn show_header(mut self, enabled: bool) -> Self {
+        self.settings.no_header = !enabled;
+        self
+    }
+
+    /// Minimal output for logs, see [`DiffSettings::plain`]
+    pub fn plain(self) -> Self {
+        self.indent_spaces(0).show_line_numbers(false).color(false)
+    }
+
+    pub fn max_line_number(mut self, n: usize) -> Self {
+        self.settings.max_line_number = Some(n);
+        self
+    }
+
+    /// Only show this many unchanged lines around each change
+    pub fn context_lines(mut self, n: usize) -> Self {
+        self.settings.context_lines = Some(n);
+        self
+    }
+
+    /// Only show the removed and added lines, without unchanged lines around them
+    pub fn only_changes(mut self, enabled: bool) -> Self {
+        self.settings.only_changes = enabled;
+        self
+    }
+
+    /// Rewrite lines with a sed-like substitution before comparing them
+    pub fn preprocess(mut self, substitution: Substitution) -> Self {
+        self.settings.preprocess.push(substitution);
+        self
+    }
+
+    /// Rewrite lines with 'normalizer' before comparing them, after any substitutions.
+    /// Lines are shown as they are
+    pub fn normalizer(
+        mut self,
+        normalizer: impl Fn(&str) -> Cow<'_, str> + Send + Sync + 'static,
+    ) -> Self {
+        self.settings
+            .normalizers
+            .push(Normalizer(Arc::new(normalizer)));
+        self
+    }
+
+    /// Normalize both inputs to 'form' before comparing, or not at all with None
+    pub fn normalize_unicode(mut self, form: impl Into<Option<UnicodeForm>>) -> Self {
+        self.settings.normalize_unicode = form.into();
+        self
+    }
+
+    pub fn ignore_all_space(mut self, enabled: bool) -> Self {
+        self.settings.ignore_all_space = enabled;
+        self
+    }
+
+    pub fn ignore_space_change(mut self, enabled: bool) -> Self {
+        self.settings.ignore_space_change = enabled;
+        self
+    }
+
+    pub fn ignore_blank_lines(mut self, enabled: bool) -> Self {
+        self.settings.ignore_blank_lines = enabled;
+        self
+    }
+
+    /// Don't count added or removed lines matching 'regex' as differences
+    pub fn ignore_matching_lines(mut self, regex: Regex) -> Self {
+        self.settings.ignore_matching_lines.push(regex);
+        self
+    }
+
+    pub fn ignore_case(mut self, enabled: bool) -> Self {
+        self.settings.ignore_case = enabled;
+        self
+    }
+
+    pub fn strip_trailing_cr(mut self, enabled: bool) -> Self {
+        self.settings.strip_trailing_cr = enabled;
+        self
+    }
+
+    pub fn show_line_endings(mut self, enabled: bool) -> Self {
+        self.settings.show_line_endings = enabled;
+        self
+    }
+
+    pub fn show_whitespace(mut self, enabled: bool) -> Self {
+        self.settings.show_whitespace = enabled;
+        self
+    }
+
+    pub fn control_chars(mut self, mode: ControlChars) -> Self {
+        self.settings.control_chars = mode;
+        self
+    }
+
+    pub fn ignore_ansi(mut self, enabled: bool) -> Self {
+        self.settings.ignore_ansi = enabled;
+        self
+    }
+
+    /// Compare the array at 'path' in structured diffs as a multiset. '[*]' in the path matches any index
+    pub fn unordered_array(mut self, path: impl Into<String>) -> Self {
+        self.settings.unordered_arrays.push(path.into());
+        self
+    }
+
+    /// Match up the rows of tables by their value in this column
+    pub fn key_column(mut self, column: impl Into<String>) -> Self {
+        self.settings.key_column = Some(column.into());
+        self
+    }
+
+    pub fn algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.settings.algorithm = algorithm;
+        self
+    }
+
+    pub fn word_diff(mut self, mode: WordDiff) -> Self {
+        self.settings.word_diff = Some(mode);
+        self
+    }
+
+    /// Highlight changes within lines by the tokens 'tokenizer' splits them into,
+    /// instead of a built-in [`WordDiff`] mode
+    pub fn tokenizer(mut self, tokenizer: impl Tokenizer + Send + Sync + 'static) -> Self {
+        self.settings.tokenizer = Some(CustomTokenizer(Arc::new(tokenizer)));
+        self
+    }
+
+    /// Syntax highlight the lines in the language of the input names
+    #[cfg(feature = "highlight")]
+    pub fn highlight(mut self, enabled: bool) -> Self {
+        self.settings.highlight = enabled;
+        self
+    }
+
+    /// Syntax highlight the lines as 'language', like 'rust' or 'py'
+    #[cfg(feature = "highlight")]
+    pub fn syntax(mut self, language: impl Into<String>) -> Self {
+        self.settings.syntax = Some(language.into());
+        self
+    }
+
+    /// Make the line numbers OSC 8 hyperlinks to the lines in the input files
+    pub fn hyperlinks(mut self, enabled: bool) -> Self {
+        self.settings.hyperlinks = enabled;
+        self
+    }
+
+    /// Link line numbers to 'template', in which '{path}' and '{line}' are replaced
+    pub fn hyperlink_format(mut self, template: impl Into<String>) -> Self {
+        self.settings.hyperlink_format = Some(template.into());
+        self
+    }
+
+    pub fn side_by_side(mut self, enabled: bool) -> Self {
+        self.settings.side_by_side = enabled;
+        self
+    }
+
+    pub fn column_width(mut self, width: usize) -> Self {
+        self.settings.column_width = Some(width);
+        self
+    }
+
+    /// Force color on or off, regardless of what the terminal supports
+    pub fn color(self, enabled: bool) -> Self {
+        self.color_mode(if enabled {
+            ColorMode::Always
+        } else {
+            ColorMode::Never
+        })
+    }
+
+    pub fn theme(mut self, theme: Theme) -> Self {
+        self.settings.theme = theme;
+        self
+    }
+
+    /// Color of the lines only in the left input, instead of the theme's
+    pub fn left_color(mut self, color: Color) -> Self {
+        self.settings.left_color = Some(color);
+        self
+    }
+
+    /// Color of the lines only in the right input, instead of the theme's
+    pub fn right_color(mut self, color: Color) -> Self {
+        self.settings.right_color = Some(color);
+        self
+    }
+
+    /// Background of the lines only in the left input, instead of the theme's
+    pub fn left_background(mut self, color: Color) -> Self {
+        self.settings.left_background = Some(color);
+        self
+    }
+
+    /// Background of the lines only in the right input, instead of the theme's
+    pub fn right_background(mut self, color: Color) -> Self {
+        self.settings.right_background = Some(color);
+        self
+    }
+
+    pub fn color_mode(mut self, mode: ColorMode) -> Self {
+        self.settings.color_mode = mode;
+        self
+    }
+
+    /// The settings, or why they can't be used together. This is the checked way to build them
+    pub fn try_build(self) -> Result<DiffSettings> {
+        self.settings.validate()?;
+        Ok(self.settings)
+    }
+
+    /// The settings, without checking that they can be used together.
+    /// Use [`DiffSettingsBuilder::try_build`] for settings that come from users
+    pub fn build(self) -> DiffSettings {
+        self.settings
+    }
+}
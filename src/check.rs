@@ -0,0 +1,225 @@
+use crate::watch;
+use anyhow::{Context, Result, bail};
+use different::runner::{CheckStatus, RunOptions, Summary, load_checks, run_suite};
+use different::{ReportFormat, update_snapshots};
+use minijinja::{Environment, path_loader};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Run the checks in 'checks_file' against 'base', which defaults to the checks file's directory.
+/// 'variables' are added to, and override, the ones in the file.
+/// 'options' says how the checks run. The results are also written to each of 'reports',
+/// where a path of '-' replaces the usual output on stdout.
+/// With 'update', expected values that didn't match are replaced in the checks file.
+/// With 'watch', the checks run again whenever the checks file or base directory changes,
+/// printing only the checks whose status changed.
+/// Returns true if every check passed or was skipped
+pub fn check(
+    checks_file: &Path,
+    base: Option<&Path>,
+    variables: &[(String, String)],
+    reports: &[(ReportFormat, PathBuf)],
+    mut options: RunOptions,
+    update: bool,
+    watch: bool,
+) -> Result<bool> {
+    let base = base.unwrap_or(checks_dir(checks_file));
+    let to_stdout = reports.iter().any(|(_, path)| path == Path::new("-"));
+    options.quiet = to_stdout;
+
+    if !watch {
+        let summary = run_checks(checks_file, base, variables, reports, &options, update)?;
+        if !to_stdout {
+            writeln!(io::stdout(), "{summary}")?;
+        }
+        if summary.errored() > 0 {
+            bail!("{} checks could not be run", summary.errored());
+        }
+        return Ok(summary.failed() == 0);
+    }
+
+    let mut previous: Option<Summary> = None;
+    watch::watch(&[checks_file, base], || {
+        // After the first run, only what changed is printed
+        options.quiet = to_stdout || previous.is_some();
+        let summary = run_checks(checks_file, base, variables, reports, &options, update)?;
+        if !to_stdout {
+            if let Some(previous) = &previous {
+                let mut stdout = io::stdout();
+                writeln!(stdout, "---")?;
+                write_changes(&mut stdout, previous, &summary)?;
+            }
+            writeln!(io::stdout(), "{summary}")?;
+        }
+        previous = Some(summary);
+        Ok(())
+    })?;
+    unreachable!("watching only stops with an error");
+}
+
+/// The directory of 'checks_file'
+fn checks_dir(checks_file: &Path) -> &Path {
+    // A bare file name has an empty parent, which commands can't run in
+    checks_file
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."))
+}
+
+/// Load and run the checks once, then write the reports and update snapshots
+fn run_checks(
+    checks_file: &Path,
+    base: &Path,
+    variables: &[(String, String)],
+    reports: &[(ReportFormat, PathBuf)],
+    options: &RunOptions,
+    update: bool,
+) -> Result<Summary> {
+    let mut file = load_checks(checks_file)?;
+    file.variables.extend(variables.iter().cloned());
+
+    let dir = checks_dir(checks_file);
+    let templates = match &file.templates {
+        Some(templates) => dir.join(templates),
+        None => dir.to_path_buf(),
+    };
+    let mut jinja_env = Environment::new();
+    jinja_env.set_loader(path_loader(templates));
+
+    let summary = run_suite(&file, base, &jinja_env, options)?;
+
+    let suite = checks_file
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+    for (format, path) in reports {
+        let report = match format {
+            ReportFormat::Junit => summary.to_junit(&suite),
+            ReportFormat::Json => summary.to_json()?,
+        };
+        if path == Path::new("-") {
+            write!(io::stdout(), "{report}")?;
+        } else {
+            fs::write(path, report)
+                .with_context(|| format!("Unable to write report {}", path.display()))?;
+        }
+    }
+    if update {
+        let updated = update_snapshots(checks_file, &summary)?;
+        for result in summary.results.iter().filter(|r| !r.snapshots.is_empty()) {
+            if updated.contains(&result.name) {
+                eprintln!("Updated snapshot of '{}'", result.name);
+            } else {
+                eprintln!(
+                    "Unable to update snapshot of '{}', it isn't defined in {}",
+                    result.name,
+                    checks_file.display()
+                );
+            }
+        }
+    }
+    Ok(summary)
+}
+
+fn status_name(outcome: &different::Result<CheckStatus>) -> &'static str {
+    match outcome {
+        Ok(CheckStatus::Success) => "ok",
+        Ok(CheckStatus::Fail { .. }) => "FAILED",
+        Ok(CheckStatus::Skip { .. }) => "skipped",
+        Err(_) => "ERROR",
+    }
+}
+
+/// Write the checks that are new or whose status changed since 'previous' to 'out'
+fn write_changes(out: &mut impl Write, previous: &Summary, current: &Summary) -> io::Result<()> {
+    let before: HashMap<&str, &'static str> = previous
+        .results
+        .iter()
+        .map(|result| (result.name.as_str(), status_name(&result.outcome)))
+        .collect();
+    let mut changed = false;
+    for result in &current.results {
+        let name = &result.name;
+        let status = status_name(&result.outcome);
+        let reason = match &result.outcome {
+            Ok(CheckStatus::Fail { reason }) | Ok(CheckStatus::Skip { reason }) => {
+                format!(" - {reason}")
+            }
+            Err(e) => format!(" - {}", e.full_message()),
+            Ok(CheckStatus::Success) => String::new(),
+        };
+        match before.get(name.as_str()) {
+            Some(&was) if was == status => continue,
+            Some(was) => writeln!(out, "{name}: {status} (was {was}){reason}")?,
+            None => writeln!(out, "{name}: {status} (new){reason}")?,
+        }
+        changed = true;
+    }
+    if !changed {
+        writeln!(out, "No checks changed")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use different::runner::CheckResult;
+    use std::time::Duration;
+
+    fn summary(outcomes: Vec<(&str, CheckStatus)>) -> Summary {
+        let results = outcomes
+            .into_iter()
+            .map(|(name, status)| CheckResult {
+                name: name.to_string(),
+                kind: Some("command"),
+                outcome: Ok(status),
+                duration: Duration::ZERO,
+                snapshots: Vec::new(),
+            })
+            .collect();
+        Summary { results }
+    }
+
+    fn changes(previous: &Summary, current: &Summary) -> String {
+        let mut out = Vec::new();
+        write_changes(&mut out, previous, current).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn new_and_changed_checks() {
+        let previous = summary(vec![
+            ("build", CheckStatus::Success),
+            ("lint", CheckStatus::Success),
+        ]);
+        let current = summary(vec![
+            ("build", CheckStatus::Success),
+            (
+                "lint",
+                CheckStatus::Fail {
+                    reason: "exit code 1".to_string(),
+                },
+            ),
+            (
+                "docs",
+                CheckStatus::Skip {
+                    reason: "no docs".to_string(),
+                },
+            ),
+        ]);
+        assert_eq!(
+            changes(&previous, &current),
+            "lint: FAILED (was ok) - exit code 1\ndocs: skipped (new) - no docs\n"
+        );
+    }
+
+    #[test]
+    fn no_checks_changed() {
+        let previous = summary(vec![("build", CheckStatus::Success)]);
+        let current = summary(vec![("build", CheckStatus::Success)]);
+        assert_eq!(changes(&previous, &current), "No checks changed\n");
+    }
+}
@@ -0,0 +1,187 @@
+use anyhow::{Context, Result, bail};
+use clap::parser::ValueSource;
+use clap::{Arg, Command};
+use log::debug;
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::PathBuf;
+
+/// Prefix of the environment variables that set options, like DIFFERENT_THEME=github
+const ENV_PREFIX: &str = "DIFFERENT_";
+
+/// Environment variable naming the config file to use instead of the usual one
+const CONFIG_ENV: &str = "DIFFERENT_CONFIG";
+
+/// A default for one option
+enum Setting {
+    Flag(bool),
+    Values(Vec<String>),
+}
+
+/// The config file: $DIFFERENT_CONFIG, or else different/config.toml in $XDG_CONFIG_HOME or ~/.config
+fn config_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os(CONFIG_ENV) {
+        return Some(PathBuf::from(path));
+    }
+    // Relative paths in XDG_CONFIG_HOME are invalid and should be ignored
+    let dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(dir.join("different").join("config.toml"))
+}
+
+/// The option with the long name 'name', which may be written with '_' instead of '-'
+fn find_arg<'c>(command: &'c Command, name: &str) -> Option<&'c Arg> {
+    let name = name.replace('_', "-");
+    command
+        .get_arguments()
+        .find(|arg| arg.get_long() == Some(name.as_str()))
+}
+
+fn takes_values(arg: &Arg) -> bool {
+    arg.get_action().takes_values()
+}
+
+/// Read the options set in the config file, keyed by their long name
+fn read_config(command: &Command, settings: &mut BTreeMap<String, Setting>) -> Result<()> {
+    let Some(path) = config_path() else {
+        return Ok(());
+    };
+    // Only a config file that was asked for has to exist
+    if !path.exists() && env::var_os(CONFIG_ENV).is_none() {
+        debug!("No config file at {}", path.display());
+        return Ok(());
+    }
+    let text = fs::read_to_string(&path)
+        .with_context(|| format!("Unable to read config file {}", path.display()))?;
+    let table: toml::Table =
+        toml::from_str(&text).with_context(|| format!("Invalid config file {}", path.display()))?;
+
+    for (key, value) in table {
+        let Some(arg) = find_arg(command, &key) else {
+            bail!("Unknown option '{key}' in config file {}", path.display());
+        };
+        let setting = match value {
+            toml::Value::Boolean(enabled) if !takes_values(arg) => Setting::Flag(enabled),
+            _ if !takes_values(arg) => {
+                bail!(
+                    "'{key}' in config file {} must be true or false",
+                    path.display()
+                )
+            }
+            toml::Value::Array(values) => Setting::Values(
+                values
+                    .into_iter()
+                    .map(|value| config_value(&key, value))
+                    .collect::<Result<_>>()?,
+            ),
+            value => Setting::Values(vec![config_value(&key, value)?]),
+        };
+        settings.insert(arg.get_long().unwrap_or_default().to_string(), setting);
+    }
+    Ok(())
+}
+
+fn config_value(key: &str, value: toml::Value) -> Result<String> {
+    match value {
+        toml::Value::String(s) => Ok(s),
+        toml::Value::Integer(n) => Ok(n.to_string()),
+        toml::Value::Float(n) => Ok(n.to_string()),
+        toml::Value::Boolean(b) => Ok(b.to_string()),
+        _ => bail!("'{key}' in config file must be a string, number or boolean"),
+    }
+}
+
+/// Read the options set by DIFFERENT_* environment variables, over the config file's
+fn read_env(command: &Command, settings: &mut BTreeMap<String, Setting>) -> Result<()> {
+    for (name, value) in env::vars() {
+        let Some(option) = name.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+        if name == CONFIG_ENV {
+            continue;
+        }
+        let Some(arg) = find_arg(command, &option.to_lowercase()) else {
+            debug!("Ignoring {name}, which isn't an option");
+            continue;
+        };
+        let setting = if takes_values(arg) {
+            Setting::Values(vec![value])
+        } else {
+            match value.to_lowercase().as_str() {
+                "" | "0" | "false" | "no" | "off" => Setting::Flag(false),
+                "1" | "true" | "yes" | "on" => Setting::Flag(true),
+                _ => bail!("{name} must be true or false, not '{value}'"),
+            }
+        };
+        settings.insert(arg.get_long().unwrap_or_default().to_string(), setting);
+    }
+    Ok(())
+}
+
+/// Add the options from the config file and DIFFERENT_* environment variables in front of 'args',
+/// unless they are given on the command line, or conflict with an option that is.
+/// Subcommands don't take defaults
+pub fn with_defaults(mut command: Command, args: Vec<OsString>) -> Result<Vec<OsString>> {
+    command.build();
+    // Let the real parse report bad arguments
+    let Ok(matches) = command.clone().try_get_matches_from(&args) else {
+        return Ok(args);
+    };
+    if matches.subcommand().is_some() {
+        return Ok(args);
+    }
+
+    let mut settings = BTreeMap::new();
+    read_config(&command, &mut settings)?;
+    read_env(&command, &mut settings)?;
+
+    let given: Vec<&Arg> = matches
+        .ids()
+        .filter(|id| matches.value_source(id.as_str()) == Some(ValueSource::CommandLine))
+        .filter_map(|id| command.get_arguments().find(|arg| arg.get_id() == id))
+        .collect();
+    let conflicts = |a: &Arg, b: &Arg| {
+        a.get_id() == b.get_id()
+            || command
+                .get_arg_conflicts_with(a)
+                .iter()
+                .any(|c| c.get_id() == b.get_id())
+            || command
+                .get_arg_conflicts_with(b)
+                .iter()
+                .any(|c| c.get_id() == a.get_id())
+    };
+
+    let mut defaults = Vec::new();
+    for (long, setting) in settings {
+        let Some(arg) = find_arg(&command, &long) else {
+            continue;
+        };
+        if given.iter().any(|given| conflicts(arg, given)) {
+            debug!("--{long} from the config is overridden by the command line");
+            continue;
+        }
+        match setting {
+            Setting::Flag(true) => defaults.push(OsString::from(format!("--{long}"))),
+            Setting::Flag(false) => {}
+            Setting::Values(values) => defaults.extend(
+                values
+                    .into_iter()
+                    .map(|value| OsString::from(format!("--{long}={value}"))),
+            ),
+        }
+    }
+    debug!("Defaults from the config: {defaults:?}");
+
+    let mut args = args.into_iter();
+    Ok(args
+        .next()
+        .into_iter()
+        .chain(defaults)
+        .chain(args)
+        .collect())
+}
@@ -0,0 +1,69 @@
+use crate::{DifferentError, Result};
+use encoding_rs::{Encoding, UTF_8, UTF_16BE, UTF_16LE, WINDOWS_1252};
+
+/// How many bytes to look at when guessing
+const SAMPLE_SIZE: usize = 4096;
+
+/// Look up an encoding by one of its WHATWG labels, like 'latin1', 'utf-16le' or 'shift_jis'
+pub fn parse_encoding(label: &str) -> Result<&'static Encoding> {
+    match Encoding::for_label(label.trim().as_bytes()) {
+        Some(encoding) => Ok(encoding),
+        None => Err(DifferentError::UnknownEncoding(label.to_string())),
+    }
+}
+
+/// Whether 'byte' is an ASCII control character that text doesn't contain,
+/// unlike tabs, line breaks, form feeds, backspaces and escapes
+fn is_binary_control(byte: u8) -> bool {
+    (byte < 0x20 && !b"\t\n\r\x08\x0c\x1b".contains(&byte)) || byte == 0x7f
+}
+
+/// Guess the encoding of 'bytes': from a byte order mark, else UTF-8 if they are valid UTF-8,
+/// UTF-16 if most of every other byte is NUL, as in ASCII text, or else Windows-1252,
+/// a superset of Latin-1, if there are no NULs and next to no other control characters.
+/// Returns None if they look binary
+pub fn detect_encoding(bytes: &[u8]) -> Option<&'static Encoding> {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return Some(encoding);
+    }
+    let sample = &bytes[..bytes.len().min(SAMPLE_SIZE)];
+    if !bytes.contains(&0) {
+        if std::str::from_utf8(bytes).is_ok() {
+            return Some(UTF_8);
+        }
+        // Any text encoding would do for binary data without NULs, so look for its controls
+        let controls = sample.iter().filter(|&&b| is_binary_control(b)).count();
+        return (controls * 100 <= sample.len()).then_some(WINDOWS_1252);
+    }
+
+    let units = sample.len() / 2;
+    if !bytes.len().is_multiple_of(2) || units == 0 {
+        return None;
+    }
+    let nuls = |offset: usize| {
+        sample
+            .chunks_exact(2)
+            .filter(|unit| unit[offset] == 0)
+            .count()
+    };
+    let (even, odd) = (nuls(0), nuls(1));
+    if odd > units / 2 && even <= units / 10 {
+        Some(UTF_16LE)
+    } else if even > units / 2 && odd <= units / 10 {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+/// Decode 'bytes' as 'encoding', or else the detected one, dropping any byte order mark
+/// for that encoding. Bytes that aren't valid in the encoding become U+FFFD.
+/// Returns None if no encoding was given and they look binary
+pub fn decode(bytes: &[u8], encoding: Option<&'static Encoding>) -> Option<String> {
+    let text = match encoding {
+        // A given encoding wins over a byte order mark for another one
+        Some(encoding) => encoding.decode_with_bom_removal(bytes).0,
+        None => detect_encoding(bytes)?.decode(bytes).0,
+    };
+    Some(text.into_owned())
+}
@@ -0,0 +1,157 @@
+#[cfg(any(feature = "structured", feature = "table"))]
+use std::fmt::Display;
+use std::io;
+use std::num::ParseIntError;
+use std::time::TryFromFloatSecsError;
+
+/// Everything that can go wrong in the library, so callers can tell failures apart.
+/// Messages match what the `diff` binary prints
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum DifferentError {
+    /// A color that isn't a known name, a '#rrggbb' code, an 'r,g,b' triple or a palette number
+    #[error("{0}")]
+    InvalidColor(String),
+    /// A style, theme or base theme that can't be used
+    #[error("{0}")]
+    InvalidTheme(String),
+    /// A '--preprocess' substitution that isn't like 's/REGEX/REPLACEMENT/FLAGS'
+    #[error("{0}")]
+    InvalidSubstitution(String),
+    /// An encoding label that isn't a WHATWG one
+    #[error("Unknown encoding '{0}'")]
+    UnknownEncoding(String),
+    /// A language syntax highlighting doesn't know
+    #[error("Unknown language '{0}'")]
+    UnknownLanguage(String),
+    /// Settings that can't be used together
+    #[error("{0}")]
+    ConflictingSettings(String),
+    /// A diff or patch that doesn't fit the input it is applied or composed with
+    #[error("{0}")]
+    Mismatch(String),
+    /// A patch, table or checks file that can be read but doesn't make sense
+    #[error("{0}")]
+    Invalid(String),
+    /// A check that can't be run, as opposed to one that fails
+    #[error("{0}")]
+    Check(String),
+    /// What was being done when 'error' happened. The message is only the context,
+    /// 'error' is its source, and [`DifferentError::inner`] gets to what went wrong
+    #[error("{context}")]
+    Context {
+        context: String,
+        #[source]
+        error: Box<DifferentError>,
+    },
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    ParseInt(#[from] ParseIntError),
+    #[error(transparent)]
+    Duration(#[from] TryFromFloatSecsError),
+    #[error(transparent)]
+    Regex(#[from] regex::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    // Errors of optional dependencies are boxed, so their versions aren't part of the API
+    /// YAML that can't be parsed or written
+    #[error(transparent)]
+    Yaml(BoxError),
+    /// TOML that can't be parsed
+    #[error(transparent)]
+    Toml(BoxError),
+    /// A TOML checks file that can't be edited to update snapshots
+    #[error(transparent)]
+    TomlEdit(BoxError),
+    /// A CSV or TSV table that can't be read
+    #[error(transparent)]
+    Csv(BoxError),
+    /// A template that can't be rendered
+    #[error(transparent)]
+    Template(BoxError),
+    /// An HTTP request that couldn't be made or answered
+    #[error(transparent)]
+    Http(BoxError),
+    /// A glob pattern that can't be parsed
+    #[error(transparent)]
+    GlobPattern(BoxError),
+    /// A path matched by a glob pattern that can't be read
+    #[error(transparent)]
+    Glob(BoxError),
+}
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+impl DifferentError {
+    /// The error beneath any context, to match on what went wrong
+    pub fn inner(&self) -> &Self {
+        match self {
+            Self::Context { error, .. } => error.inner(),
+            _ => self,
+        }
+    }
+
+    /// The message followed by those of the errors that caused it, separated by ': '
+    /// like anyhow's `{:#}`
+    pub fn full_message(&self) -> String {
+        let mut message = self.to_string();
+        let mut source = std::error::Error::source(self);
+        while let Some(error) = source {
+            message.push_str(": ");
+            message.push_str(&error.to_string());
+            source = error.source();
+        }
+        message
+    }
+}
+
+/// Box errors of the optional dependencies into their variant, if the feature using them is enabled
+macro_rules! boxed_from {
+    ($($feature:literal: $error:ty => $variant:ident),* $(,)?) => {
+        $(
+            #[cfg(feature = $feature)]
+            impl From<$error> for DifferentError {
+                fn from(error: $error) -> Self {
+                    Self::$variant(Box::new(error))
+                }
+            }
+        )*
+    };
+}
+
+boxed_from! {
+    "runner": toml_edit::TomlError => TomlEdit,
+    "runner": minijinja::Error => Template,
+    "runner": glob::PatternError => GlobPattern,
+    "runner": glob::GlobError => Glob,
+    "runner": ureq::Error => Http,
+    "structured": serde_yaml::Error => Yaml,
+    "structured": toml::de::Error => Toml,
+    "table": csv::Error => Csv,
+}
+
+pub type Result<T, E = DifferentError> = std::result::Result<T, E>;
+
+/// Add what was being done to an error, like anyhow's trait of the same name
+#[cfg(any(feature = "structured", feature = "table"))]
+pub(crate) trait Context<T> {
+    #[cfg_attr(not(feature = "structured"), allow(dead_code))]
+    fn context(self, context: impl Display) -> Result<T>;
+
+    fn with_context<C: Display>(self, context: impl FnOnce() -> C) -> Result<T>;
+}
+
+#[cfg(any(feature = "structured", feature = "table"))]
+impl<T, E: Into<DifferentError>> Context<T> for std::result::Result<T, E> {
+    fn context(self, context: impl Display) -> Result<T> {
+        self.with_context(|| context)
+    }
+
+    fn with_context<C: Display>(self, context: impl FnOnce() -> C) -> Result<T> {
+        self.map_err(|error| DifferentError::Context {
+            context: context().to_string(),
+            error: Box::new(error.into()),
+        })
+    }
+}
@@ -0,0 +1,59 @@
+use std::fmt::Display;
+
+const BYTES_PER_ROW: usize = 16;
+
+/// Whether 'bytes' should be treated as binary rather than text:
+/// it contains a NUL byte or isn't valid UTF-8
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes.contains(&0) || std::str::from_utf8(bytes).is_err()
+}
+
+/// One row of a hexdump. Rows compare equal if their bytes do, wherever they are in the input
+#[derive(Debug, Clone)]
+pub struct HexRow {
+    pub offset: usize,
+    pub bytes: Vec<u8>,
+}
+
+impl PartialEq for HexRow {
+    fn eq(&self, other: &Self) -> bool {
+        self.bytes == other.bytes
+    }
+}
+
+impl Display for HexRow {
+    /// `hexdump -C` style: offset, bytes in hex, then the printable ones as text
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let hex: Vec<String> = self.bytes.iter().map(|b| format!("{b:02x}")).collect();
+        let ascii: String = self
+            .bytes
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        let width = BYTES_PER_ROW * 3 - 1;
+        write!(
+            f,
+            "{:08x}  {:<width$}  |{ascii}|",
+            self.offset,
+            hex.join(" ")
+        )
+    }
+}
+
+/// Split 'bytes' into hexdump rows, ready to be diffed with [`crate::slice_diff`]
+pub fn hex_rows(bytes: &[u8]) -> Vec<HexRow> {
+    bytes
+        .chunks(BYTES_PER_ROW)
+        .enumerate()
+        .map(|(i, chunk)| HexRow {
+            offset: i * BYTES_PER_ROW,
+            bytes: chunk.to_vec(),
+        })
+        .collect()
+}
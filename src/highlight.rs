@@ -0,0 +1,156 @@
+use crate::{DiffSettings, Side, Style};
+#[cfg(feature = "cli")]
+use crate::{DifferentError, Result};
+use colored::Color;
+use std::fmt::Display;
+use std::path::Path;
+use std::sync::OnceLock;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+
+/// Theme the syntax colors come from, picked to read well on a dark terminal
+const SYNTAX_THEME: &str = "base16-ocean.dark";
+
+/// A dark shade of a side's color, to set its lines apart by their background
+/// when the syntax colors take the place of the foreground colors
+fn dark_background(color: Color) -> Option<Color> {
+    let (r, g, b) = match color {
+        Color::Red | Color::BrightRed => (63, 0, 1),
+        Color::Green | Color::BrightGreen => (0, 40, 0),
+        Color::Yellow | Color::BrightYellow => (50, 50, 0),
+        Color::Blue | Color::BrightBlue => (0, 0, 70),
+        Color::Magenta | Color::BrightMagenta => (60, 0, 60),
+        Color::Cyan | Color::BrightCyan => (0, 50, 50),
+        Color::TrueColor { r, g, b } => (r / 4, g / 4, b / 4),
+        _ => return None,
+    };
+    Some(Color::TrueColor { r, g, b })
+}
+
+fn syntax_set() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn syntax_theme() -> &'static Theme {
+    static THEME: OnceLock<Theme> = OnceLock::new();
+    THEME.get_or_init(|| {
+        let mut themes = ThemeSet::load_defaults().themes;
+        themes.remove(SYNTAX_THEME).unwrap_or_default()
+    })
+}
+
+/// The syntax named like 'rust', or used for files with an extension like 'rs'
+fn find_syntax(language: &str) -> Option<&'static SyntaxReference> {
+    syntax_set().find_syntax_by_token(language)
+}
+
+/// The syntax of the file at 'path', by its name, like 'Makefile', or else its extension
+fn syntax_for_path(path: &str) -> Option<&'static SyntaxReference> {
+    let path = Path::new(path);
+    let syntaxes = syntax_set();
+    let name = path.file_name()?.to_str()?;
+    syntaxes.find_syntax_by_extension(name).or_else(|| {
+        let extension = path.extension()?.to_str()?;
+        syntaxes.find_syntax_by_extension(extension)
+    })
+}
+
+/// Check that there is a syntax for 'language', for the command line
+#[cfg(feature = "cli")]
+pub(crate) fn parse_syntax(language: &str) -> Result<String> {
+    if find_syntax(language).is_none() {
+        return Err(DifferentError::UnknownLanguage(language.to_string()));
+    }
+    Ok(language.to_string())
+}
+
+/// Syntax highlighting state for both inputs, fed every line in order,
+/// since what a line means can depend on the ones before it
+pub(crate) struct Highlighter {
+    left: HighlightLines<'static>,
+    right: HighlightLines<'static>,
+    left_background: Option<Color>,
+    right_background: Option<Color>,
+}
+
+impl Highlighter {
+    /// A highlighter for the language in 'settings' or of the inputs' names,
+    /// if highlighting is on and the language is known
+    pub(crate) fn new(settings: &DiffSettings) -> Option<Self> {
+        if !settings.color_enabled() || (!settings.highlight && settings.syntax.is_none()) {
+            return None;
+        }
+        let syntax = match &settings.syntax {
+            Some(language) => find_syntax(language),
+            None => [&settings.left_name, &settings.right_name]
+                .into_iter()
+                .flatten()
+                .find_map(|name| syntax_for_path(name)),
+        }?;
+        let theme = settings.theme();
+        Some(Self {
+            left: HighlightLines::new(syntax, syntax_theme()),
+            right: HighlightLines::new(syntax, syntax_theme()),
+            left_background: theme.left.bg.or(theme.left.fg.and_then(dark_background)),
+            right_background: theme.right.bg.or(theme.right.fg.and_then(dark_background)),
+        })
+    }
+
+    /// Highlight the next line of the diff, which is fed to the side or sides it belongs to.
+    /// Unchanged lines are shown as they are on the left
+    pub(crate) fn highlight_line<T: Display>(
+        &mut self,
+        line: &diff::Result<T>,
+        settings: &DiffSettings,
+    ) -> Vec<(Style, String)> {
+        match line {
+            diff::Result::Left(l) => {
+                let l = settings.display_line(l.to_string(), true);
+                self.highlight(Side::Left, &l, true)
+            }
+            diff::Result::Right(r) => {
+                let r = settings.display_line(r.to_string(), true);
+                self.highlight(Side::Right, &r, true)
+            }
+            diff::Result::Both(l, r) => {
+                let r = settings.display_line(r.to_string(), false);
+                self.highlight(Side::Right, &r, false);
+                let l = settings.display_line(l.to_string(), false);
+                self.highlight(Side::Left, &l, false)
+            }
+        }
+    }
+
+    /// The next line of one side, split into pieces with their style.
+    /// Pieces of changed lines get the side's background
+    fn highlight(&mut self, side: Side, line: &str, changed: bool) -> Vec<(Style, String)> {
+        let (highlighter, background) = match side {
+            Side::Left => (&mut self.left, self.left_background),
+            Side::Right => (&mut self.right, self.right_background),
+        };
+        // The default syntaxes expect lines to end with a newline
+        let line = format!("{line}\n");
+        let Ok(ranges) = highlighter.highlight_line(&line, syntax_set()) else {
+            return vec![(Style::default(), line.trim_end_matches('\n').to_string())];
+        };
+        ranges
+            .into_iter()
+            .map(|(style, text)| {
+                let color = style.foreground;
+                let style = Style {
+                    fg: Some(Color::TrueColor {
+                        r: color.r,
+                        g: color.g,
+                        b: color.b,
+                    }),
+                    bg: background.filter(|_| changed),
+                    ..Style::default()
+                };
+                (style, text.trim_end_matches('\n').to_string())
+            })
+            .filter(|(_, text)| !text.is_empty())
+            .collect()
+    }
+}
@@ -0,0 +1,194 @@
+use crate::{Diff, DiffLine, LineKind, visible_lines};
+use std::fmt::{Display, Write};
+
+const STYLE: &str = "\
+body { font-family: sans-serif; margin: 1em; }
+table.diff { border-collapse: collapse; font-family: monospace; width: 100%; }
+table.diff th { text-align: left; padding: 0.3em; background: #eee; }
+table.diff td { padding: 0 0.4em; white-space: pre-wrap; vertical-align: top; }
+td.num { color: #888; text-align: right; width: 1%; user-select: none; }
+td.marker { width: 1%; user-select: none; }
+.removed { background: #fdd; }
+.added { background: #dfd; }
+tr.skip td { color: #888; background: #f6f6f6; }
+";
+
+pub(crate) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn num(n: Option<usize>) -> String {
+    n.map(|n| n.to_string()).unwrap_or_default()
+}
+
+fn class(kind: LineKind) -> &'static str {
+    match kind {
+        LineKind::Removed => "removed",
+        LineKind::Added => "added",
+        LineKind::Unchanged => "unchanged",
+    }
+}
+
+/// One row per line with both line numbers and a marker, like the text output
+fn write_inline<T: Display>(
+    html: &mut String,
+    lines: &[Option<DiffLine<'_, T>>],
+    left: &str,
+    right: &str,
+) -> std::fmt::Result {
+    writeln!(
+        html,
+        "<thead><tr><th colspan=\"4\">--- {left}<br>+++ {right}</th></tr></thead>"
+    )?;
+    writeln!(html, "<tbody>")?;
+    for line in lines {
+        let Some(line) = line else {
+            writeln!(html, "<tr class=\"skip\"><td colspan=\"4\">…</td></tr>")?;
+            continue;
+        };
+        let marker = match line.kind {
+            LineKind::Removed => "-",
+            LineKind::Added => "+",
+            LineKind::Unchanged => "",
+        };
+        writeln!(
+            html,
+            "<tr class=\"{}\"><td class=\"num\">{}</td><td class=\"num\">{}</td><td class=\"marker\">{marker}</td><td>{}</td></tr>",
+            class(line.kind),
+            num(line.left_lineno),
+            num(line.right_lineno),
+            escape(&line.content.to_string()),
+        )?;
+    }
+    writeln!(html, "</tbody>")
+}
+
+fn write_pair<T: Display>(
+    html: &mut String,
+    left: Option<&DiffLine<'_, T>>,
+    right: Option<&DiffLine<'_, T>>,
+) -> std::fmt::Result {
+    let cells = |line: Option<&DiffLine<'_, T>>, lineno: fn(&DiffLine<'_, T>) -> Option<usize>| {
+        match line {
+            Some(line) => format!(
+                "<td class=\"num\">{}</td><td class=\"{}\">{}</td>",
+                num(lineno(line)),
+                class(line.kind),
+                escape(&line.content.to_string())
+            ),
+            None => "<td class=\"num\"></td><td></td>".to_string(),
+        }
+    };
+    writeln!(
+        html,
+        "<tr>{}{}</tr>",
+        cells(left, |line| line.left_lineno),
+        cells(right, |line| line.right_lineno)
+    )
+}
+
+/// Two columns, pairing up removed and added lines within each change
+fn write_side_by_side<T: Display>(
+    html: &mut String,
+    lines: &[Option<DiffLine<'_, T>>],
+    left: &str,
+    right: &str,
+) -> std::fmt::Result {
+    writeln!(
+        html,
+        "<thead><tr><th colspan=\"2\">--- {left}</th><th colspan=\"2\">+++ {right}</th></tr></thead>"
+    )?;
+    writeln!(html, "<tbody>")?;
+
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+    let flush = |html: &mut String,
+                 removed: &mut Vec<&DiffLine<'_, T>>,
+                 added: &mut Vec<&DiffLine<'_, T>>| {
+        for i in 0..removed.len().max(added.len()) {
+            write_pair(html, removed.get(i).copied(), added.get(i).copied())?;
+        }
+        removed.clear();
+        added.clear();
+        Ok(())
+    };
+
+    for line in lines {
+        match line {
+            Some(line) if line.kind == LineKind::Removed => removed.push(line),
+            Some(line) if line.kind == LineKind::Added => added.push(line),
+            Some(line) => {
+                flush(html, &mut removed, &mut added)?;
+                write_pair(html, Some(line), Some(line))?;
+            }
+            None => {
+                flush(html, &mut removed, &mut added)?;
+                writeln!(html, "<tr class=\"skip\"><td colspan=\"4\">…</td></tr>")?;
+            }
+        }
+    }
+    flush(html, &mut removed, &mut added)?;
+    writeln!(html, "</tbody>")
+}
+
+impl<T: Display> Diff<'_, T> {
+    /// The diff as a self-contained HTML page with inline CSS.
+    /// Uses a two column table when side-by-side is enabled
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+        self.write_html(&mut html)
+            .expect("writing to a String can't fail");
+        html
+    }
+
+    fn write_html(&self, html: &mut String) -> std::fmt::Result {
+        writeln!(html, "<!DOCTYPE html>")?;
+        writeln!(html, "<html>")?;
+        writeln!(html, "<head>")?;
+        writeln!(html, "<meta charset=\"utf-8\">")?;
+        writeln!(html, "<title>diff</title>")?;
+        writeln!(html, "<style>\n{STYLE}</style>")?;
+        writeln!(html, "</head>")?;
+        writeln!(html, "<body>")?;
+
+        match self {
+            Self::Same => writeln!(html, "<p>The inputs are the same</p>")?,
+            Self::Diff { settings, diff } => {
+                // Lines hidden by the context setting collapse into a single None
+                let visible = visible_lines(diff, settings.context());
+                let mut lines = Vec::new();
+                for (line, visible) in self.lines().zip(visible) {
+                    if visible {
+                        lines.push(Some(line));
+                    } else if lines.last().is_none_or(Option::is_some) {
+                        lines.push(None);
+                    }
+                }
+
+                let left = escape(settings.left_name.as_deref().unwrap_or("left"));
+                let right = escape(settings.right_name.as_deref().unwrap_or("right"));
+                writeln!(html, "<table class=\"diff\">")?;
+                if settings.side_by_side {
+                    write_side_by_side(html, &lines, &left, &right)?;
+                } else {
+                    write_inline(html, &lines, &left, &right)?;
+                }
+                writeln!(html, "</table>")?;
+            }
+        }
+
+        writeln!(html, "</body>")?;
+        writeln!(html, "</html>")
+    }
+}
@@ -0,0 +1,69 @@
+use crate::{DiffSettings, Side};
+use std::fs;
+
+/// Where line numbers link to unless set: the file and line, which terminals can open in an editor
+const DEFAULT_FORMAT: &str = "file://{path}#L{line}";
+
+/// Percent-encode 'path' for a URL, leaving its separators alone
+fn encode_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/:".contains(&byte) {
+            encoded.push(char::from(byte));
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
+}
+
+/// The absolute path of the input called 'name', if it is a file that can be linked to
+fn absolute_path(name: Option<&String>) -> Option<String> {
+    let path = fs::canonicalize(name?).ok()?;
+    path.is_file().then(|| path.to_string_lossy().into_owned())
+}
+
+/// OSC 8 hyperlinks from line numbers to the lines in the input files
+pub(crate) struct Links<'a> {
+    format: &'a str,
+    left: Option<String>,
+    right: Option<String>,
+}
+
+impl<'a> Links<'a> {
+    /// Links are only made when asked for and the output is colored, as both need a terminal
+    pub(crate) fn new(settings: &'a DiffSettings) -> Self {
+        let format = settings.hyperlink_format.as_deref();
+        if !(settings.hyperlinks || format.is_some()) || !settings.color_enabled() {
+            return Self {
+                format: DEFAULT_FORMAT,
+                left: None,
+                right: None,
+            };
+        }
+        Self {
+            format: format.unwrap_or(DEFAULT_FORMAT),
+            left: absolute_path(settings.left_name.as_ref()),
+            right: absolute_path(settings.right_name.as_ref()),
+        }
+    }
+
+    /// Make 'number', the text of line 'line' of one side, a link to that line.
+    /// Padding in front of it stays outside the link
+    pub(crate) fn wrap(&self, side: Side, line: Option<usize>, number: String) -> String {
+        let path = match side {
+            Side::Left => &self.left,
+            Side::Right => &self.right,
+        };
+        let (Some(path), Some(line)) = (path, line) else {
+            return number;
+        };
+        let url = self
+            .format
+            .replace("{path}", &encode_path(path))
+            .replace("{line}", &line.to_string());
+        let digits = number.trim_start();
+        let padding = &number[..number.len() - digits.len()];
+        format!("{padding}\x1B]8;;{url}\x1B\\{digits}\x1B]8;;\x1B\\")
+    }
+}
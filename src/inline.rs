@@ -0,0 +1,185 @@
+use crate::DiffSettings;
+use std::fmt::{self, Display};
+use std::sync::Arc;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Splits lines into the tokens that changes within them are highlighted by.
+/// The tokens of a line have to join back up into the whole line
+pub trait Tokenizer {
+    fn tokenize<'a>(&self, line: &'a str) -> Vec<&'a str>;
+}
+
+/// Granularity of the highlighting within changed lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum WordDiff {
+    /// Highlight changed words
+    Word,
+    /// Highlight changed characters
+    Char,
+    /// Highlight changed identifiers, numbers and operators like '->' or '=='
+    Code,
+    /// Highlight changed words by Unicode rules, keeping words like "don't" whole
+    /// and splitting Chinese and Japanese text into characters
+    Prose,
+    /// Highlight changed characters as they are seen, keeping accents and emoji whole
+    Grapheme,
+}
+
+/// A piece of a changed line, flagged if it differs from the paired line on the other side
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub text: String,
+    pub changed: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TokenKind {
+    Word,
+    Space,
+    Operator,
+    Other,
+}
+
+fn token_kind(c: char, mode: WordDiff) -> TokenKind {
+    if c.is_alphanumeric() || c == '_' {
+        TokenKind::Word
+    } else if c.is_whitespace() {
+        TokenKind::Space
+    } else if mode == WordDiff::Code && "+-*/%=<>!&|^~:?".contains(c) {
+        TokenKind::Operator
+    } else {
+        TokenKind::Other
+    }
+}
+
+/// Split a line into runs of word characters, runs of whitespace, and single punctuation characters.
+/// Code mode also keeps runs of operator characters and decimals like '1.5' together
+fn split_runs(line: &str, mode: WordDiff) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut prev: Option<TokenKind> = None;
+    let mut chars = line.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let mut kind = token_kind(c, mode);
+        // A point between digits belongs to the number
+        if mode == WordDiff::Code
+            && c == '.'
+            && line[start..i].ends_with(|c: char| c.is_ascii_digit())
+            && chars.peek().is_some_and(|(_, next)| next.is_ascii_digit())
+        {
+            kind = TokenKind::Word;
+        }
+        let split = match mode {
+            WordDiff::Char => true,
+            _ => prev != Some(kind) || kind == TokenKind::Other,
+        };
+        if split && i > start {
+            tokens.push(&line[start..i]);
+            start = i;
+        }
+        prev = Some(kind);
+    }
+    if start < line.len() {
+        tokens.push(&line[start..]);
+    }
+    tokens
+}
+
+impl Tokenizer for WordDiff {
+    fn tokenize<'a>(&self, line: &'a str) -> Vec<&'a str> {
+        match self {
+            Self::Word | Self::Char | Self::Code => split_runs(line, *self),
+            Self::Prose => line.split_word_bounds().collect(),
+            Self::Grapheme => line.graphemes(true).collect(),
+        }
+    }
+}
+
+/// A tokenizer from library users, shared between copies of the settings
+#[derive(Clone)]
+pub(crate) struct CustomTokenizer(pub(crate) Arc<dyn Tokenizer + Send + Sync>);
+
+impl fmt::Debug for CustomTokenizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CustomTokenizer")
+    }
+}
+
+fn push_segment(segments: &mut Vec<Segment>, text: &str, changed: bool) {
+    match segments.last_mut() {
+        Some(last) if last.changed == changed => last.text.push_str(text),
+        _ => segments.push(Segment {
+            text: text.to_string(),
+            changed,
+        }),
+    }
+}
+
+/// Diff a removed line against the added line that replaced it
+pub fn inline_diff(left: &str, right: &str, mode: WordDiff) -> (Vec<Segment>, Vec<Segment>) {
+    inline_diff_with(left, right, &mode)
+}
+
+/// Diff a removed line against the added line that replaced it, split up by 'tokenizer'
+pub fn inline_diff_with(
+    left: &str,
+    right: &str,
+    tokenizer: &(impl Tokenizer + ?Sized),
+) -> (Vec<Segment>, Vec<Segment>) {
+    let left_tokens = tokenizer.tokenize(left);
+    let right_tokens = tokenizer.tokenize(right);
+
+    let mut left_segments = Vec::new();
+    let mut right_segments = Vec::new();
+    for token in diff::slice(&left_tokens, &right_tokens) {
+        match token {
+            diff::Result::Left(l) => push_segment(&mut left_segments, l, true),
+            diff::Result::Both(l, r) => {
+                push_segment(&mut left_segments, l, false);
+                push_segment(&mut right_segments, r, false);
+            }
+            diff::Result::Right(r) => push_segment(&mut right_segments, r, true),
+        }
+    }
+    (left_segments, right_segments)
+}
+
+/// For every line of the diff, the inline segments to render it with, if any.
+/// Within each changed region the n-th removed line is paired with the n-th added line;
+/// lines without a partner are rendered as a whole
+pub(crate) fn line_segments<T: Display>(
+    diff: &[diff::Result<T>],
+    tokenizer: &dyn Tokenizer,
+    settings: &DiffSettings,
+) -> Vec<Option<Vec<Segment>>> {
+    let mut segments = vec![None; diff.len()];
+    let mut lefts = Vec::new();
+    let mut rights = Vec::new();
+
+    let mut pair_up = |lefts: &mut Vec<usize>, rights: &mut Vec<usize>| {
+        for (&i, &j) in lefts.iter().zip(rights.iter()) {
+            let (diff::Result::Left(l), diff::Result::Right(r)) = (&diff[i], &diff[j]) else {
+                continue;
+            };
+            let left = settings.display_line(l.to_string(), true);
+            let right = settings.display_line(r.to_string(), true);
+            let (left, right) = inline_diff_with(&left, &right, tokenizer);
+            segments[i] = Some(left);
+            segments[j] = Some(right);
+        }
+        lefts.clear();
+        rights.clear();
+    };
+
+    for (i, line) in diff.iter().enumerate() {
+        match line {
+            diff::Result::Left(_) => lefts.push(i),
+            diff::Result::Right(_) => rights.push(i),
+            diff::Result::Both(_, _) => pair_up(&mut lefts, &mut rights),
+        }
+    }
+    pair_up(&mut lefts, &mut rights);
+
+    segments
+}
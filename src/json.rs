@@ -0,0 +1,254 @@
+use crate::DiffSettings;
+use crate::paint::Paint;
+use crate::value::{index_path, key_path};
+use serde_json::{Value, json};
+use std::fmt::Display;
+
+/// A single change between two JSON documents, addressed by a JSON Pointer (RFC 6901)
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonOp {
+    Add {
+        path: String,
+        value: Value,
+    },
+    Remove {
+        path: String,
+        value: Value,
+    },
+    Replace {
+        path: String,
+        old: Value,
+        new: Value,
+    },
+}
+
+impl JsonOp {
+    pub fn path(&self) -> &str {
+        match self {
+            Self::Add { path, .. } => path,
+            Self::Remove { path, .. } => path,
+            Self::Replace { path, .. } => path,
+        }
+    }
+
+    /// The operation as an RFC 6902 JSON Patch operation
+    pub fn to_patch_op(&self) -> Value {
+        match self {
+            Self::Add { path, value } => json!({"op": "add", "path": path, "value": value}),
+            Self::Remove { path, .. } => json!({"op": "remove", "path": path}),
+            Self::Replace { path, new, .. } => json!({"op": "replace", "path": path, "value": new}),
+        }
+    }
+}
+
+/// An RFC 6902 JSON Patch document applying 'ops' in order
+pub(crate) fn json_patch<'o>(
+    ops: impl IntoIterator<Item = &'o JsonOp>,
+) -> serde_json::Result<String> {
+    let patch: Vec<Value> = ops.into_iter().map(JsonOp::to_patch_op).collect();
+    serde_json::to_string_pretty(&patch)
+}
+
+#[derive(Debug)]
+pub enum JsonDiff<'a> {
+    Same,
+    Diff {
+        settings: &'a DiffSettings,
+        ops: Vec<JsonOp>,
+    },
+}
+
+impl JsonDiff<'_> {
+    pub fn ops(&self) -> &[JsonOp] {
+        match self {
+            Self::Same => &[],
+            Self::Diff { ops, .. } => ops,
+        }
+    }
+
+    /// The diff as an RFC 6902 JSON Patch, an empty one if the documents are the same
+    pub fn to_json_patch(&self) -> serde_json::Result<String> {
+        json_patch(self.ops())
+    }
+}
+
+impl Display for JsonDiff<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Same => write!(f, "")?,
+            Self::Diff { settings, ops } => {
+                let theme = settings.theme();
+                let indent = " ".repeat(settings.indent_spaces);
+                let paint = settings.color_enabled();
+
+                settings.write_headers(f)?;
+
+                for op in ops {
+                    match op {
+                        JsonOp::Add { path, value } => {
+                            let line = format!("{indent}+ {path}: {value}");
+                            writeln!(f, "{}", theme.right.apply(line).paint(paint))?;
+                        }
+                        JsonOp::Remove { path, value } => {
+                            let line = format!("{indent}- {path}: {value}");
+                            writeln!(f, "{}", theme.left.apply(line).paint(paint))?;
+                        }
+                        JsonOp::Replace { path, old, new } => {
+                            let old = format!("{indent}- {path}: {old}");
+                            let new = format!("{indent}+ {path}: {new}");
+                            writeln!(f, "{}", theme.left.apply(old).paint(paint))?;
+                            writeln!(f, "{}", theme.right.apply(new).paint(paint))?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Escape a single reference token as described in RFC 6901
+pub(crate) fn escape_token(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+/// Whether the array at 'path' is compared as a multiset, by one of the 'unordered' patterns.
+/// Patterns are readable paths in which `[*]` matches any index
+fn path_matches(pattern: &str, path: &str) -> bool {
+    let Some((prefix, rest)) = pattern.split_once("[*]") else {
+        return pattern == path;
+    };
+    let Some(index) = path.strip_prefix(prefix).and_then(|p| p.strip_prefix('[')) else {
+        return false;
+    };
+    let digits = index.chars().take_while(char::is_ascii_digit).count();
+    digits > 0
+        && index[digits..]
+            .strip_prefix(']')
+            .is_some_and(|after| path_matches(rest, after))
+}
+
+/// Compare two arrays as multisets: items equal to one on the other side match up whatever
+/// their position, and the rest are removed or added
+fn diff_unordered(
+    pointer: &str,
+    path: &str,
+    left: &[Value],
+    right: &[Value],
+    unordered: &[String],
+    ops: &mut Vec<JsonOp>,
+) {
+    let same = |i: usize, l: &Value, r: &Value| {
+        let mut ops = Vec::new();
+        let path = index_path(path, i);
+        diff_values(&format!("{pointer}/{i}"), &path, l, r, unordered, &mut ops);
+        ops.is_empty()
+    };
+    let mut matched = vec![false; right.len()];
+    let mut removed = Vec::new();
+    for (i, l) in left.iter().enumerate() {
+        match (0..right.len()).find(|&j| !matched[j] && same(i, l, &right[j])) {
+            Some(j) => matched[j] = true,
+            None => removed.push(i),
+        }
+    }
+    // Removals are listed from the back so each pointer stays valid when applied in order
+    for &i in removed.iter().rev() {
+        ops.push(JsonOp::Remove {
+            path: format!("{pointer}/{i}"),
+            value: left[i].clone(),
+        });
+    }
+    let kept = left.len() - removed.len();
+    let added = right.iter().zip(matched).filter(|(_, matched)| !matched);
+    for (i, (value, _)) in (kept..).zip(added) {
+        ops.push(JsonOp::Add {
+            path: format!("{pointer}/{i}"),
+            value: value.clone(),
+        });
+    }
+}
+
+/// Diff 'left' and 'right' at 'pointer', which is at the readable 'path'
+pub(crate) fn diff_values(
+    pointer: &str,
+    path: &str,
+    left: &Value,
+    right: &Value,
+    unordered: &[String],
+    ops: &mut Vec<JsonOp>,
+) {
+    // Objects are compared key by key, so the order of their keys never matters
+    match (left, right) {
+        (Value::Object(left), Value::Object(right)) => {
+            for (key, left_value) in left {
+                let child = format!("{pointer}/{}", escape_token(key));
+                match right.get(key) {
+                    Some(right_value) => {
+                        let path = key_path(path, key);
+                        diff_values(&child, &path, left_value, right_value, unordered, ops);
+                    }
+                    None => ops.push(JsonOp::Remove {
+                        path: child,
+                        value: left_value.clone(),
+                    }),
+                }
+            }
+            for (key, right_value) in right {
+                if !left.contains_key(key) {
+                    ops.push(JsonOp::Add {
+                        path: format!("{pointer}/{}", escape_token(key)),
+                        value: right_value.clone(),
+                    });
+                }
+            }
+        }
+        (Value::Array(left), Value::Array(right))
+            if unordered.iter().any(|pattern| path_matches(pattern, path)) =>
+        {
+            diff_unordered(pointer, path, left, right, unordered, ops);
+        }
+        (Value::Array(left), Value::Array(right)) => {
+            let common = std::cmp::min(left.len(), right.len());
+            for i in 0..common {
+                let child = format!("{pointer}/{i}");
+                let path = index_path(path, i);
+                diff_values(&child, &path, &left[i], &right[i], unordered, ops);
+            }
+            // Removals are listed from the back so each pointer stays valid when applied in order
+            for i in (common..left.len()).rev() {
+                ops.push(JsonOp::Remove {
+                    path: format!("{pointer}/{i}"),
+                    value: left[i].clone(),
+                });
+            }
+            for (i, value) in right.iter().enumerate().skip(common) {
+                ops.push(JsonOp::Add {
+                    path: format!("{pointer}/{i}"),
+                    value: value.clone(),
+                });
+            }
+        }
+        (left, right) => {
+            if left != right {
+                ops.push(JsonOp::Replace {
+                    path: pointer.to_string(),
+                    old: left.clone(),
+                    new: right.clone(),
+                });
+            }
+        }
+    }
+}
+
+/// Compare two JSON value trees, producing a list of pointer-addressed operations
+/// that turn 'left' into 'right'
+pub fn json_diff<'a>(left: &Value, right: &Value, settings: &'a DiffSettings) -> JsonDiff<'a> {
+    let mut ops = Vec::new();
+    diff_values("", ".", left, right, &settings.unordered_arrays, &mut ops);
+    if ops.is_empty() {
+        JsonDiff::Same
+    } else {
+        JsonDiff::Diff { settings, ops }
+    }
+}
@@ -1,20 +1,209 @@
-use colored::{Color, Colorize};
+use colored::Color;
+use regex::Regex;
+use std::borrow::Cow;
 use std::fmt::Display;
+use std::io;
 
 const DEFAULT_LEFT_MARKER: char = '-';
 const DEFAULT_RIGHT_MARKER: char = '+';
 const DEFAULT_MARKER_COUNT: usize = 4;
 const DEFAULT_INDENT_SPACES: usize = 2;
-const DEFAULT_LEFT_COLOR: Color = Color::Green;
-const DEFAULT_RIGHT_COLOR: Color = Color::Red;
-use anyhow::Result;
 
-fn parse_color(_s: &str) -> Result<Color> {
-    todo!();
+mod algorithm;
+#[cfg(feature = "assert")]
+#[doc(hidden)]
+pub mod assert;
+mod builder;
+mod encoding;
+mod error;
+mod hex;
+#[cfg(feature = "highlight")]
+mod highlight;
+mod html;
+mod hyperlink;
+mod inline;
+mod json;
+mod lines;
+mod markdown;
+#[cfg(feature = "runner")]
+mod matrix;
+mod normalize;
+mod ops;
+mod output;
+mod paint;
+mod patch;
+#[cfg(feature = "runner")]
+mod report;
+#[cfg(feature = "runner")]
+pub mod runner;
+mod side_by_side;
+mod similarity;
+#[cfg(feature = "runner")]
+mod snapshot;
+mod stats;
+mod stream;
+#[cfg(feature = "structured")]
+mod structured;
+#[cfg(feature = "table")]
+mod table;
+mod theme;
+#[cfg(feature = "runner")]
+pub mod types;
+mod unified;
+mod value;
+mod width;
+pub use algorithm::Algorithm;
+pub use builder::DiffSettingsBuilder;
+pub use encoding::{decode, detect_encoding, parse_encoding};
+pub use encoding_rs::Encoding;
+pub use error::{DifferentError, Result};
+pub use hex::{HexRow, hex_rows, is_binary};
+use inline::CustomTokenizer;
+pub use inline::{Segment, Tokenizer, WordDiff, inline_diff, inline_diff_with};
+pub use json::{JsonDiff, JsonOp, json_diff};
+pub use lines::{DiffHunk, DiffLine, LineKind};
+use normalize::Normalizer;
+pub use normalize::{ControlChars, Substitution, UnicodeForm};
+pub use ops::{Hunk, Op, OpTag, group_ops, opcodes};
+pub use output::OutputFormat;
+pub use paint::ColorMode;
+use paint::Paint;
+pub use patch::{FilePatch, Patch, PatchHunk, PatchLine};
+#[cfg(feature = "runner")]
+pub use report::ReportFormat;
+pub use similarity::{char_edit_distance, char_similarity, edit_distance, similarity};
+#[cfg(feature = "runner")]
+pub use snapshot::update_snapshots;
+pub use stats::DiffStats;
+pub use stream::{LineDiffIter, line_diff_iter};
+#[cfg(feature = "structured")]
+pub use structured::{StructuredChange, StructuredDiff, StructuredFormat, structured_diff};
+#[cfg(feature = "table")]
+pub use table::{CellChange, TableChange, TableDiff, table_diff};
+pub use theme::{Style, THEMES, Theme};
+pub use unified::unified_diff;
+pub use value::{ValueChange, value_diff};
+
+const COLOR_NAMES: &[&str] = &[
+    "black",
+    "red",
+    "green",
+    "yellow",
+    "blue",
+    "magenta",
+    "purple",
+    "cyan",
+    "white",
+    "bright-black",
+    "bright-red",
+    "bright-green",
+    "bright-yellow",
+    "bright-blue",
+    "bright-magenta",
+    "bright-cyan",
+    "bright-white",
+];
+
+fn parse_hex_color(hex: &str) -> Option<Color> {
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some(Color::TrueColor {
+        r: channel(0)?,
+        g: channel(2)?,
+        b: channel(4)?,
+    })
 }
 
-#[derive(Debug)]
-enum Side {
+fn parse_rgb_color(s: &str) -> Option<Color> {
+    let channels: Vec<u8> = s
+        .split(',')
+        .map(|c| c.trim().parse().ok())
+        .collect::<Option<_>>()?;
+    let [r, g, b] = channels[..] else {
+        return None;
+    };
+    Some(Color::TrueColor { r, g, b })
+}
+
+/// A color from the 256 color palette: the 16 named colors, a 6x6x6 color cube, then 24 grays
+fn palette_color(n: u8) -> Color {
+    const NAMED: [Color; 16] = [
+        Color::Black,
+        Color::Red,
+        Color::Green,
+        Color::Yellow,
+        Color::Blue,
+        Color::Magenta,
+        Color::Cyan,
+        Color::White,
+        Color::BrightBlack,
+        Color::BrightRed,
+        Color::BrightGreen,
+        Color::BrightYellow,
+        Color::BrightBlue,
+        Color::BrightMagenta,
+        Color::BrightCyan,
+        Color::BrightWhite,
+    ];
+    match n {
+        0..=15 => NAMED[usize::from(n)],
+        16..=231 => {
+            let n = n - 16;
+            let level = |v: u8| if v == 0 { 0 } else { 55 + 40 * v };
+            Color::TrueColor {
+                r: level(n / 36),
+                g: level(n / 6 % 6),
+                b: level(n % 6),
+            }
+        }
+        232..=255 => {
+            let v = 8 + 10 * (n - 232);
+            Color::TrueColor { r: v, g: v, b: v }
+        }
+    }
+}
+
+/// Parse a named color ('red', 'bright-blue'), a '#rrggbb' hex code, an 'r,g,b' triple,
+/// or a number from the 256 color palette
+pub fn parse_color(s: &str) -> Result<Color> {
+    let s = s.trim();
+    if s.starts_with(|c: char| c.is_ascii_digit()) && !s.contains(',') {
+        return s.parse().map(palette_color).map_err(|_| {
+            DifferentError::InvalidColor(format!("Invalid palette color '{s}', expected 0 to 255"))
+        });
+    }
+    if let Some(hex) = s.strip_prefix('#') {
+        return parse_hex_color(hex).ok_or_else(|| {
+            DifferentError::InvalidColor(format!("Invalid hex color '{s}', expected '#rrggbb'"))
+        });
+    }
+    if s.contains(',') {
+        return parse_rgb_color(s).ok_or_else(|| {
+            DifferentError::InvalidColor(format!(
+                "Invalid RGB color '{s}', expected three values from 0 to 255 like '255,0,0'"
+            ))
+        });
+    }
+
+    // colored spells the bright variants with a space, accept the CLI friendly forms too
+    let name = s.to_lowercase().replace(['-', '_'], " ");
+    let name = match name.strip_prefix("bright") {
+        Some(rest) if !rest.starts_with(' ') => format!("bright {rest}"),
+        _ => name,
+    };
+    name.parse().map_err(|_| {
+        DifferentError::InvalidColor(format!(
+            "Unknown color '{s}'. Valid values are {}, '#rrggbb', 'r,g,b', or 0 to 255",
+            COLOR_NAMES.join(", ")
+        ))
+    })
+}
+
+/// One of the two inputs of a diff
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
     Left,
     Right,
 }
@@ -80,67 +269,263 @@ pub enum Diff<'a, T> {
     },
 }
 
+impl<T> Diff<'_, T> {
+    /// Range-level view of the diff, suitable for [`group_ops`]
+    pub fn ops(&self) -> Vec<Op> {
+        match self {
+            Self::Same => Vec::new(),
+            Self::Diff { diff, .. } => ops::ops_from_results(diff),
+        }
+    }
+
+    /// Swap additions and deletions, so the diff describes going from right to left.
+    /// Within each changed region removals are still listed before additions
+    pub fn invert(self) -> Self {
+        let Self::Diff { settings, diff } = self else {
+            return self;
+        };
+
+        let mut inverted = Vec::with_capacity(diff.len());
+        let mut additions = Vec::new();
+        for line in diff {
+            match line {
+                diff::Result::Left(l) => additions.push(diff::Result::Right(l)),
+                diff::Result::Right(r) => inverted.push(diff::Result::Left(r)),
+                diff::Result::Both(l, r) => {
+                    inverted.append(&mut additions);
+                    inverted.push(diff::Result::Both(r, l));
+                }
+            }
+        }
+        inverted.append(&mut additions);
+
+        Self::Diff {
+            settings,
+            diff: inverted,
+        }
+    }
+}
+
+impl<T> Diff<'_, T>
+where
+    T: AsRef<str>,
+{
+    /// Rebuild the right-hand content by applying this diff to 'left'.
+    /// Fails if 'left' does not match the left side the diff was computed from
+    pub fn apply(&self, left: &str) -> Result<String> {
+        let Self::Diff { diff, .. } = self else {
+            return Ok(left.to_string());
+        };
+
+        let mut left_lines = split_lines(left).into_iter().enumerate();
+        let mut out = Vec::new();
+        for line in diff {
+            let expected = match line {
+                diff::Result::Left(l) => l,
+                diff::Result::Both(l, _) => l,
+                diff::Result::Right(r) => {
+                    out.push(r.as_ref());
+                    continue;
+                }
+            };
+            let expected = expected.as_ref();
+            match left_lines.next() {
+                Some((_, actual)) if actual == expected => {}
+                Some((i, actual)) => {
+                    return Err(DifferentError::Mismatch(format!(
+                        "Line {} does not match the diff: expected '{expected}', found '{actual}'",
+                        i + 1
+                    )));
+                }
+                None => {
+                    return Err(DifferentError::Mismatch(format!(
+                        "Input ended early: expected '{expected}'"
+                    )));
+                }
+            }
+            if let diff::Result::Both(_, r) = line {
+                out.push(r.as_ref());
+            }
+        }
+        if let Some((i, _)) = left_lines.next() {
+            return Err(DifferentError::Mismatch(format!(
+                "Input has extra content starting at line {}",
+                i + 1
+            )));
+        }
+
+        Ok(out.join("\n"))
+    }
+
+    /// Rebuild one of the inputs from the diff alone, so only one version and the diffs
+    /// to the others need to be kept. Returns None for [`Diff::Same`], which keeps no lines
+    pub fn restore(&self, side: Side) -> Option<String> {
+        let Self::Diff { diff, .. } = self else {
+            return None;
+        };
+        let lines: Vec<&str> = diff
+            .iter()
+            .filter_map(|line| match (line, side) {
+                (diff::Result::Left(l) | diff::Result::Both(l, _), Side::Left) => Some(l.as_ref()),
+                (diff::Result::Right(r) | diff::Result::Both(_, r), Side::Right) => {
+                    Some(r.as_ref())
+                }
+                _ => None,
+            })
+            .collect();
+        Some(lines.join("\n"))
+    }
+
+    /// Merge two sequential diffs (a to b, then b to c) into a single diff from a to c.
+    /// Fails if the right side of 'first' is not the left side of 'second'
+    pub fn compose(first: Self, second: Self) -> Result<Self>
+    where
+        T: Clone + PartialEq,
+    {
+        let (settings, first) = match first {
+            Self::Same => return Ok(second),
+            Self::Diff { settings, diff } => (settings, diff),
+        };
+        let second = match second {
+            Self::Same => {
+                return Ok(Self::Diff {
+                    settings,
+                    diff: first,
+                });
+            }
+            Self::Diff { diff, .. } => diff,
+        };
+
+        let mut a = Vec::new();
+        let mut b = Vec::new();
+        for line in first {
+            match line {
+                diff::Result::Left(l) => a.push(l),
+                diff::Result::Both(l, r) => {
+                    a.push(l);
+                    b.push(r);
+                }
+                diff::Result::Right(r) => b.push(r),
+            }
+        }
+
+        let mut b = b.into_iter().enumerate();
+        let mut c = Vec::new();
+        for line in second {
+            let expected = match line {
+                diff::Result::Left(l) => l,
+                diff::Result::Both(l, r) => {
+                    c.push(r);
+                    l
+                }
+                diff::Result::Right(r) => {
+                    c.push(r);
+                    continue;
+                }
+            };
+            match b.next() {
+                Some((_, actual)) if actual.as_ref() == expected.as_ref() => {}
+                Some((i, actual)) => {
+                    return Err(DifferentError::Mismatch(format!(
+                        "Diffs disagree on intermediate line {}: '{}' vs '{}'",
+                        i + 1,
+                        actual.as_ref(),
+                        expected.as_ref()
+                    )));
+                }
+                None => {
+                    return Err(DifferentError::Mismatch(
+                        "Second diff is longer than the first".to_string(),
+                    ));
+                }
+            }
+        }
+        if b.next().is_some() {
+            return Err(DifferentError::Mismatch(
+                "First diff is longer than the second".to_string(),
+            ));
+        }
+
+        let diff: Vec<_> = diff::slice(&a, &c)
+            .into_iter()
+            .map(|line| match line {
+                diff::Result::Left(l) => diff::Result::Left(l.clone()),
+                diff::Result::Both(l, r) => diff::Result::Both(l.clone(), r.clone()),
+                diff::Result::Right(r) => diff::Result::Right(r.clone()),
+            })
+            .collect();
+        if diff
+            .iter()
+            .all(|line| matches!(line, diff::Result::Both(_, _)))
+        {
+            return Ok(Self::Same);
+        }
+        Ok(Self::Diff { settings, diff })
+    }
+}
+
 impl<T> Display for Diff<'_, T>
 where
-    T: Display,
+    T: Display + PartialEq,
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Same => write!(f, "")?,
+            Self::Diff { settings, diff } if f.alternate() => write_plain(f, settings, diff)?,
+            Self::Diff { settings, diff } if settings.side_by_side => {
+                settings.write_headers(f)?;
+                side_by_side::write_side_by_side(f, settings, diff)?;
+            }
             Self::Diff { settings, diff } => {
-                let max_num_width = settings.max_line_number.map(|x| x.ilog10() as usize + 1);
+                let max_num_width = settings
+                    .max_line_number
+                    .map(|x| x.max(1).ilog10() as usize + 1);
 
-                let left_color = if let Some(color) = settings.left_color {
-                    color
-                } else {
-                    DEFAULT_LEFT_COLOR
-                };
+                let theme = settings.theme();
+                let indent = " ".repeat(settings.indent_spaces);
+                let paint = settings.color_enabled();
 
-                let right_color = if let Some(color) = settings.right_color {
-                    color
-                } else {
-                    DEFAULT_RIGHT_COLOR
-                };
+                settings.write_headers(f)?;
 
-                let indent = " ".repeat(settings.indent_spaces);
+                let visible = visible_lines(diff, settings.context());
+                let mut skipping = false;
 
-                // TODO: force color and no color should be mutually exclusive
-                if settings.force_color {
-                    colored::control::set_override(true);
-                }
-                if settings.no_color {
-                    colored::control::set_override(false);
-                }
+                let segments = match settings.tokenizer() {
+                    Some(tokenizer) => inline::line_segments(diff, tokenizer, settings),
+                    None => vec![None; diff.len()],
+                };
 
-                let left_header = header(
-                    Side::Left,
-                    settings.left_name.as_ref(),
-                    settings.left_marker,
-                    settings.marker_count,
-                )
-                .color(left_color);
-                let right_header = header(
-                    Side::Right,
-                    settings.right_name.as_ref(),
-                    settings.right_marker,
-                    settings.marker_count,
-                )
-                .color(right_color);
-                writeln!(f, "{left_header}")?;
-                writeln!(f, "{right_header}")?;
+                #[cfg(feature = "highlight")]
+                let mut highlighter = highlight::Highlighter::new(settings);
+                let links = hyperlink::Links::new(settings);
 
                 let mut line_num_a = 0;
                 let mut line_num_b = 0;
-                for line in diff {
+                for ((line, visible), segments) in diff.iter().zip(visible).zip(segments) {
+                    // Hidden lines go through the highlighter too, as they can change its state
+                    #[cfg(feature = "highlight")]
+                    let highlighted = highlighter
+                        .as_mut()
+                        .map(|highlighter| highlighter.highlight_line(line, settings));
+                    #[cfg(not(feature = "highlight"))]
+                    let highlighted: Option<Vec<(Style, String)>> = None;
+
                     let (sep, content, line_num_a_display, line_num_b_display, color) = match line {
                         diff::Result::Left(l) => {
                             line_num_a += 1;
                             ('-', l, Some(line_num_a), None, ColorSide::Left)
                         }
-                        diff::Result::Both(l, _) => {
+                        diff::Result::Both(l, r) => {
                             line_num_a += 1;
                             line_num_b += 1;
-                            ('|', l, Some(line_num_a), Some(line_num_b), ColorSide::Both)
+                            // Lines that only compare equal after normalization get a distinct separator.
+                            // Without line numbers to set apart, equal lines get none
+                            let sep = match (l == r, settings.show_line_numbers()) {
+                                (false, _) => '~',
+                                (true, true) => '|',
+                                (true, false) => ' ',
+                            };
+                            (sep, l, Some(line_num_a), Some(line_num_b), ColorSide::Both)
                         }
                         diff::Result::Right(r) => {
                             line_num_b += 1;
@@ -148,18 +533,81 @@ where
                         }
                     };
 
-                    let line_num_a_display = display_str(line_num_a_display, max_num_width);
-                    let line_num_b_display = display_str(line_num_b_display, max_num_width);
+                    if !visible {
+                        if !skipping && !settings.only_changes {
+                            let dots = theme.unchanged.apply(format!("{indent}..."));
+                            writeln!(f, "{}", dots.paint(paint))?;
+                            skipping = true;
+                        }
+                        continue;
+                    }
+                    skipping = false;
+
+                    let numbers = if settings.show_line_numbers() {
+                        let line_num_a_display = links.wrap(
+                            Side::Left,
+                            line_num_a_display,
+                            display_str(line_num_a_display, max_num_width),
+                        );
+                        let line_num_b_display = links.wrap(
+                            Side::Right,
+                            line_num_b_display,
+                            display_str(line_num_b_display, max_num_width),
+                        );
+                        format!("{indent}{line_num_a_display}{indent}{line_num_b_display} ")
+                    } else {
+                        indent.clone()
+                    };
 
-                    let line = format!(
-                        "{indent}{line_num_a_display}{indent}{line_num_b_display} {sep} {content}"
-                    );
-                    let line = match color {
-                        ColorSide::Left => line.color(left_color),
-                        ColorSide::Right => line.color(right_color),
-                        ColorSide::Both => line.dimmed(),
+                    let style = match color {
+                        ColorSide::Left => theme.left,
+                        ColorSide::Right => theme.right,
+                        ColorSide::Both => theme.unchanged,
                     };
-                    writeln!(f, "{line}")?;
+
+                    if theme.line_number.is_some() || theme.marker.is_some() {
+                        let numbers = theme.line_number.unwrap_or(style).apply(numbers);
+                        let marker = theme.marker.unwrap_or(style).apply(sep.to_string());
+                        write!(f, "{}{} ", numbers.paint(paint), marker.paint(paint))?;
+                    } else if segments.is_some() || highlighted.is_some() {
+                        let prefix = format!("{numbers}{sep} ");
+                        write!(f, "{}", style.apply(prefix).paint(paint))?;
+                    } else {
+                        let changed = !matches!(color, ColorSide::Both);
+                        let content = settings.display_line(content.to_string(), changed);
+                        let line = format!("{numbers}{sep} {content}");
+                        writeln!(f, "{}", style.apply(line).paint(paint))?;
+                        continue;
+                    }
+
+                    match (segments, highlighted) {
+                        (Some(segments), _) => {
+                            let changed = match color {
+                                ColorSide::Left => theme.left_changed,
+                                _ => theme.right_changed,
+                            };
+                            let changed = changed.unwrap_or(Style {
+                                bold: true,
+                                underline: true,
+                                ..style
+                            });
+                            for segment in segments {
+                                let style = if segment.changed { changed } else { style };
+                                write!(f, "{}", style.apply(segment.text).paint(paint))?;
+                            }
+                        }
+                        (None, Some(pieces)) => {
+                            for (style, text) in pieces {
+                                write!(f, "{}", style.apply(text).paint(paint))?;
+                            }
+                        }
+                        (None, None) => {
+                            let changed = !matches!(color, ColorSide::Both);
+                            let content = settings.display_line(content.to_string(), changed);
+                            write!(f, "{}", style.apply(content).paint(paint))?;
+                        }
+                    }
+                    writeln!(f)?;
                 }
             }
         }
@@ -167,28 +615,173 @@ where
     }
 }
 
+impl<T> Diff<'_, T>
+where
+    T: Display + PartialEq,
+{
+    /// Write the diff to 'w' as it is formatted, the same as its `Display` output,
+    /// without building it up in a String first
+    pub fn write_to(&self, w: &mut impl io::Write) -> io::Result<()> {
+        write!(w, "{self}")
+    }
+}
+
+/// Uncolored output with only a marker in front of each line, used for `{:#}`.
+/// Never touches the global color override
+fn write_plain<T: Display>(
+    f: &mut std::fmt::Formatter<'_>,
+    settings: &DiffSettings,
+    diff: &[diff::Result<T>],
+) -> std::fmt::Result {
+    if settings.show_header() {
+        let left_header = header(
+            Side::Left,
+            settings.left_name.as_ref(),
+            settings.left_marker,
+            settings.marker_count,
+        );
+        let right_header = header(
+            Side::Right,
+            settings.right_name.as_ref(),
+            settings.right_marker,
+            settings.marker_count,
+        );
+        writeln!(f, "{left_header}")?;
+        writeln!(f, "{right_header}")?;
+    }
+
+    let visible = visible_lines(diff, settings.context());
+    let mut skipping = false;
+    for (line, visible) in diff.iter().zip(visible) {
+        if !visible {
+            if !skipping && !settings.only_changes {
+                writeln!(f, "...")?;
+                skipping = true;
+            }
+            continue;
+        }
+        skipping = false;
+
+        match line {
+            diff::Result::Left(l) => writeln!(f, "- {l}")?,
+            diff::Result::Both(l, _) => writeln!(f, "  {l}")?,
+            diff::Result::Right(r) => writeln!(f, "+ {r}")?,
+        }
+    }
+    Ok(())
+}
+
+/// Work out which lines to print: changed lines plus at most 'context' unchanged lines
+/// either side of them. With no context limit, every line is shown
+fn visible_lines<T>(diff: &[diff::Result<T>], context: Option<usize>) -> Vec<bool> {
+    let Some(context) = context else {
+        return vec![true; diff.len()];
+    };
+
+    let is_change = |line: &diff::Result<T>| !matches!(line, diff::Result::Both(_, _));
+    let mut visible = vec![false; diff.len()];
+
+    let mut last_change = None;
+    for (i, line) in diff.iter().enumerate() {
+        if is_change(line) {
+            last_change = Some(i);
+        }
+        visible[i] = last_change.is_some_and(|c| i - c <= context);
+    }
+
+    let mut next_change = None;
+    for (i, line) in diff.iter().enumerate().rev() {
+        if is_change(line) {
+            next_change = Some(i);
+        }
+        visible[i] |= next_change.is_some_and(|c| c - i <= context);
+    }
+
+    visible
+}
+
+/// Split 'text' into lines, so that joining the result with newlines gives back the original text.
+/// Unlike [`str::lines`], carriage returns stay at the end of their lines
+fn split_lines(text: &str) -> Vec<&str> {
+    if text.is_empty() {
+        return Vec::new();
+    }
+    text.split('\n').collect()
+}
+
+/// Diff any two slices, e.g. tokens or records, rendering each item with its Display impl.
+/// Items only need to be comparable, so the algorithm setting is ignored in favor of Myers
+pub fn slice_diff<'a, T: PartialEq>(
+    left: &'a [T],
+    right: &'a [T],
+    settings: &'a DiffSettings,
+) -> Diff<'a, &'a T> {
+    let diff: Vec<_> = algorithm::diff_eq(left, right)
+        .into_iter()
+        .map(|edit| match edit {
+            algorithm::Edit::Delete(i) => diff::Result::Left(&left[i]),
+            algorithm::Edit::Equal(i, j) => diff::Result::Both(&left[i], &right[j]),
+            algorithm::Edit::Insert(j) => diff::Result::Right(&right[j]),
+        })
+        .collect();
+    if diff
+        .iter()
+        .all(|item| matches!(item, diff::Result::Both(..)))
+    {
+        Diff::Same
+    } else {
+        Diff::Diff { settings, diff }
+    }
+}
+
+/// Whether every line of 'left' compares equal to the line at the same position in 'right'.
+/// Stops at the first difference without diffing anything
+fn same_lines(left: &str, right: &str, settings: &DiffSettings) -> bool {
+    if left.ends_with('\n') != right.ends_with('\n') {
+        return false;
+    }
+    let mut left_lines = split_lines(left).into_iter();
+    let mut right_lines = split_lines(right).into_iter();
+    loop {
+        match (left_lines.next(), right_lines.next()) {
+            (None, None) => return true,
+            (Some(l), Some(r)) if settings.normalize(l) == settings.normalize(r) => {}
+            _ => return false,
+        }
+    }
+}
+
 /// Compare 'expected' to 'actual', where 'actual' is (well probably) a modified version of 'expected'
 pub fn line_diff<'a>(
     left: &'a str,
     right: &'a str,
     settings: &'a DiffSettings,
 ) -> Diff<'a, &'a str> {
-    let diff = diff::lines(left, right);
+    // Most comparisons are of identical inputs, which don't need the diff algorithm at all
+    if left == right || same_lines(left, right, settings) {
+        return Diff::Same;
+    }
+
+    let diff = normalize::diff_by_key(
+        left,
+        right,
+        |line| settings.normalize(line),
+        settings.algorithm,
+    );
     let mut same = true;
 
     for line in &diff {
         match line {
-            diff::Result::Left(_) => {
+            diff::Result::Left(l) | diff::Result::Right(l) => {
+                if settings.ignores(l) {
+                    continue;
+                }
                 same = false;
                 break;
             }
             diff::Result::Both(_, _) => {
                 continue;
             }
-            diff::Result::Right(_) => {
-                same = false;
-                break;
-            }
         }
     }
     if same {
@@ -198,43 +791,186 @@ pub fn line_diff<'a>(
     }
 }
 
-// TODO: settings for no line numbers, plain style, no header, etc
-// TODO: tests
-
-#[derive(Debug, Clone, clap::Parser)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "cli", derive(clap::Parser))]
 pub struct DiffSettings {
-    #[clap(long)]
+    #[cfg_attr(feature = "cli", clap(long))]
     left_name: Option<String>,
 
-    #[clap(long)]
+    #[cfg_attr(feature = "cli", clap(long))]
     right_name: Option<String>,
 
-    #[clap(long, default_value_t = DEFAULT_LEFT_MARKER)]
+    #[cfg_attr(feature = "cli", clap(long, default_value_t = DEFAULT_LEFT_MARKER))]
     left_marker: char,
 
-    #[clap(long, default_value_t = DEFAULT_RIGHT_MARKER)]
+    #[cfg_attr(feature = "cli", clap(long, default_value_t = DEFAULT_RIGHT_MARKER))]
     right_marker: char,
 
-    #[clap(long, default_value_t = DEFAULT_MARKER_COUNT)]
+    #[cfg_attr(feature = "cli", clap(long, default_value_t = DEFAULT_MARKER_COUNT))]
     marker_count: usize,
 
-    #[clap(long, default_value_t = DEFAULT_INDENT_SPACES)]
+    #[cfg_attr(feature = "cli", clap(long, default_value_t = DEFAULT_INDENT_SPACES))]
     indent_spaces: usize,
 
-    #[clap(short, long)]
-    force_color: bool,
+    /// Leave out the line numbers, leaving just a marker in front of each line
+    #[cfg_attr(feature = "cli", clap(long))]
+    no_line_numbers: bool,
 
-    #[clap(long, value_parser = parse_color)]
+    /// Leave out the two header lines naming the inputs
+    #[cfg_attr(feature = "cli", clap(long))]
+    no_header: bool,
+
+    /// Built-in theme, or the path of a theme file, to draw the diff with
+    #[cfg_attr(
+        feature = "cli",
+        clap(long, value_name = "NAME|PATH", default_value = "classic")
+    )]
+    theme: Theme,
+
+    #[cfg_attr(feature = "cli", clap(long, value_parser = parse_color))]
     left_color: Option<Color>,
 
-    #[clap(long, value_parser = parse_color)]
+    #[cfg_attr(feature = "cli", clap(long, value_parser = parse_color))]
     right_color: Option<Color>,
 
-    #[clap(long)]
-    no_color: bool,
+    /// Background of the lines only in the left input
+    #[cfg_attr(feature = "cli", clap(long, value_name = "COLOR", value_parser = parse_color))]
+    left_background: Option<Color>,
+
+    /// Background of the lines only in the right input
+    #[cfg_attr(feature = "cli", clap(long, value_name = "COLOR", value_parser = parse_color))]
+    right_background: Option<Color>,
+
+    /// When to color the output. 'auto' colors a terminal unless NO_COLOR is set, or anywhere
+    /// if CLICOLOR_FORCE is
+    #[cfg_attr(
+        feature = "cli",
+        clap(long = "color", value_enum, value_name = "WHEN", default_value_t)
+    )]
+    color_mode: ColorMode,
 
-    #[clap(skip)]
+    #[cfg_attr(feature = "cli", clap(skip))]
     max_line_number: Option<usize>,
+
+    /// Only show N unchanged lines around each change, collapsing the rest to '...'
+    #[cfg_attr(feature = "cli", clap(short = 'C', long = "context", value_name = "N"))]
+    context_lines: Option<usize>,
+
+    /// Only show the removed and added lines, without any unchanged lines or '...' between them
+    #[cfg_attr(feature = "cli", clap(long, conflicts_with = "context_lines"))]
+    only_changes: bool,
+
+    /// Rewrite lines with a sed-like substitution before comparing them, e.g. to replace UUIDs
+    /// with 's/[0-9a-f]{8}(-[0-9a-f]{4}){3}-[0-9a-f]{12}/UUID/g'. Lines are shown unchanged.
+    /// Can be given more than once, and the substitutions run in order
+    #[cfg_attr(feature = "cli", clap(long, value_name = "s/REGEX/REPLACEMENT/FLAGS"))]
+    preprocess: Vec<Substitution>,
+
+    #[cfg_attr(feature = "cli", clap(skip))]
+    normalizers: Vec<Normalizer>,
+
+    /// Normalize both inputs to this Unicode form before comparing
+    #[cfg_attr(feature = "cli", clap(long, value_enum))]
+    normalize_unicode: Option<UnicodeForm>,
+
+    /// Ignore all whitespace when comparing lines
+    #[cfg_attr(feature = "cli", clap(short = 'w', long))]
+    ignore_all_space: bool,
+
+    /// Ignore changes in the amount of whitespace when comparing lines
+    #[cfg_attr(feature = "cli", clap(short = 'b', long))]
+    ignore_space_change: bool,
+
+    /// Ignore changes that only add or remove blank lines
+    #[cfg_attr(feature = "cli", clap(short = 'B', long))]
+    ignore_blank_lines: bool,
+
+    /// Ignore changes that only add or remove lines matching REGEX, like timestamps or build IDs.
+    /// Can be given more than once
+    #[cfg_attr(feature = "cli", clap(short = 'I', long, value_name = "REGEX"))]
+    ignore_matching_lines: Vec<Regex>,
+
+    /// Ignore case differences when comparing lines
+    #[cfg_attr(feature = "cli", clap(short = 'i', long))]
+    ignore_case: bool,
+
+    /// Ignore a carriage return at the end of lines, so CRLF and LF line endings compare equal
+    #[cfg_attr(feature = "cli", clap(long))]
+    strip_trailing_cr: bool,
+
+    /// Show carriage returns at the end of lines as '␍' instead of leaving them out
+    #[cfg_attr(feature = "cli", clap(long))]
+    show_line_endings: bool,
+
+    /// Show tabs as '→', trailing spaces as '·' and non-breaking spaces as '⍽' in changed lines
+    #[cfg_attr(feature = "cli", clap(long))]
+    show_whitespace: bool,
+
+    /// How to draw control characters and ANSI escape sequences in the inputs
+    #[cfg_attr(
+        feature = "cli",
+        clap(long, value_enum, value_name = "HOW", default_value_t)
+    )]
+    control_chars: ControlChars,
+
+    /// Ignore ANSI escape sequences, like colors, when comparing lines
+    #[cfg_attr(feature = "cli", clap(long))]
+    ignore_ansi: bool,
+
+    /// Compare the array at PATH in structured diffs as a multiset, ignoring the order of its items.
+    /// PATH is written as in the diff output, like 'spec.containers[*].ports', where '[*]' matches
+    /// any index and '.' is the whole document. Can be given more than once
+    #[cfg_attr(feature = "cli", clap(long = "unordered-array", value_name = "PATH"))]
+    unordered_arrays: Vec<String>,
+
+    /// Match up the rows of CSV and TSV inputs by their value in COLUMN, the first column by default
+    #[cfg_attr(feature = "cli", clap(long, value_name = "COLUMN"))]
+    key_column: Option<String>,
+
+    /// How to line up the two inputs
+    #[cfg_attr(feature = "cli", clap(long, value_enum, default_value_t))]
+    algorithm: Algorithm,
+
+    /// Highlight the changed words or characters within modified lines
+    #[cfg_attr(feature = "cli", clap(long, value_enum))]
+    word_diff: Option<WordDiff>,
+
+    #[cfg_attr(feature = "cli", clap(skip))]
+    tokenizer: Option<CustomTokenizer>,
+
+    /// Syntax highlight the lines in the language of the inputs' file names,
+    /// with the removed and added lines set apart by their background
+    #[cfg(feature = "highlight")]
+    #[cfg_attr(feature = "cli", clap(long))]
+    highlight: bool,
+
+    /// Syntax highlight the lines as LANGUAGE, like 'rust' or 'py', whatever the file names.
+    /// Implies --highlight
+    #[cfg(feature = "highlight")]
+    #[cfg_attr(
+        feature = "cli",
+        clap(long, value_name = "LANGUAGE", value_parser = highlight::parse_syntax)
+    )]
+    syntax: Option<String>,
+
+    /// Make the line numbers links to the lines in the input files, for terminals that support
+    /// OSC 8 hyperlinks. Only done when the output is colored
+    #[cfg_attr(feature = "cli", clap(long))]
+    hyperlinks: bool,
+
+    /// Link line numbers to TEMPLATE, in which '{path}' is the absolute path of the file and
+    /// '{line}' the line number, like 'vscode://file{path}:{line}'. Implies --hyperlinks.
+    /// Defaults to 'file://{path}#L{line}'
+    #[cfg_attr(feature = "cli", clap(long, value_name = "TEMPLATE"))]
+    hyperlink_format: Option<String>,
+
+    /// Show the two inputs next to each other in columns
+    #[cfg_attr(feature = "cli", clap(short = 'y', long))]
+    side_by_side: bool,
+
+    /// Width of each column in side-by-side mode. Defaults to fitting the terminal
+    #[cfg_attr(feature = "cli", clap(long, requires = "side_by_side"))]
+    column_width: Option<usize>,
 }
 
 impl DiffSettings {
@@ -242,16 +978,258 @@ impl DiffSettings {
         Self::default()
     }
 
-    // TODO full builder stuff
-    pub fn names(mut self, left: String, right: String) -> Self {
-        self.left_name = Some(left);
-        self.right_name = Some(right);
-        self
+    /// Start building settings from the defaults
+    pub fn builder() -> DiffSettingsBuilder {
+        DiffSettingsBuilder::default()
+    }
+
+    #[deprecated(note = "use DiffSettingsBuilder::names")]
+    pub fn names(self, left: String, right: String) -> Self {
+        DiffSettingsBuilder::from(self).names(left, right).build()
+    }
+
+    #[deprecated(note = "use DiffSettingsBuilder::max_line_number")]
+    pub fn max_line_number(self, n: usize) -> Self {
+        DiffSettingsBuilder::from(self).max_line_number(n).build()
     }
 
-    pub fn max_line_number(mut self, n: usize) -> Self {
-        self.max_line_number = Some(n);
-        self
+    /// Minimal output for logs: lines prefixed with '-', '+' or ' ', without indentation,
+    /// line numbers or colors
+    pub fn plain() -> Self {
+        Self::builder().plain().build()
+    }
+
+    /// Whether the inputs are shown next to each other in columns
+    pub fn is_side_by_side(&self) -> bool {
+        self.side_by_side
+    }
+
+    /// When the output is colored
+    pub fn color_mode(&self) -> ColorMode {
+        self.color_mode
+    }
+
+    pub fn left_name(&self) -> Option<&str> {
+        self.left_name.as_deref()
+    }
+
+    pub fn right_name(&self) -> Option<&str> {
+        self.right_name.as_deref()
+    }
+
+    pub fn left_marker(&self) -> char {
+        self.left_marker
+    }
+
+    pub fn right_marker(&self) -> char {
+        self.right_marker
+    }
+
+    pub fn marker_count(&self) -> usize {
+        self.marker_count
+    }
+
+    pub fn indent_spaces(&self) -> usize {
+        self.indent_spaces
+    }
+
+    pub fn show_line_numbers(&self) -> bool {
+        !self.no_line_numbers
+    }
+
+    pub fn show_header(&self) -> bool {
+        !self.no_header
+    }
+
+    /// How many unchanged lines are shown around each change, or None for all of them
+    pub fn context_lines(&self) -> Option<usize> {
+        self.context_lines
+    }
+
+    /// The Unicode form both inputs are normalized to before comparing, if any
+    pub fn normalize_unicode(&self) -> Option<UnicodeForm> {
+        self.normalize_unicode
+    }
+
+    /// Whether all whitespace is ignored when comparing lines, like `diff -w`
+    pub fn ignore_all_space(&self) -> bool {
+        self.ignore_all_space
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    pub fn word_diff(&self) -> Option<WordDiff> {
+        self.word_diff
+    }
+
+    pub fn only_changes(&self) -> bool {
+        self.only_changes
+    }
+
+    /// Width of each side-by-side column, or None to fit the terminal
+    pub fn column_width(&self) -> Option<usize> {
+        self.column_width
+    }
+
+    /// Check that the settings can be used together
+    pub fn validate(&self) -> Result<()> {
+        if self.left_marker == self.right_marker {
+            return Err(DifferentError::ConflictingSettings(format!(
+                "The left and right markers are both '{}', so the headers can't be told apart",
+                self.left_marker
+            )));
+        }
+        if self.only_changes && self.context_lines.is_some() {
+            return Err(DifferentError::ConflictingSettings(
+                "Only showing changes leaves no room for context lines".to_string(),
+            ));
+        }
+        if self.column_width.is_some() && !self.side_by_side {
+            return Err(DifferentError::ConflictingSettings(
+                "A column width only applies to side-by-side diffs".to_string(),
+            ));
+        }
+        if self.column_width == Some(0) {
+            return Err(DifferentError::ConflictingSettings(
+                "Side-by-side columns have to be at least 1 wide".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl DiffSettings {
+    /// The theme, with the left and right colors replaced if they were set on their own
+    pub fn theme(&self) -> Theme {
+        let mut theme = self.theme.clone();
+        if let Some(color) = self.left_color {
+            theme.left.fg = Some(color);
+        }
+        if let Some(color) = self.right_color {
+            theme.right.fg = Some(color);
+        }
+        if let Some(color) = self.left_background {
+            theme.left.bg = Some(color);
+        }
+        if let Some(color) = self.right_background {
+            theme.right.bg = Some(color);
+        }
+        theme
+    }
+
+    /// How many unchanged lines to show around each change, None for all of them
+    pub(crate) fn context(&self) -> Option<usize> {
+        if self.only_changes {
+            Some(0)
+        } else {
+            self.context_lines
+        }
+    }
+
+    /// The form of 'line' used for comparison. Displayed lines are never normalized
+    pub(crate) fn normalize<'a>(&self, line: &'a str) -> Cow<'a, str> {
+        let line = match line.strip_suffix('\r') {
+            Some(stripped) if self.strip_trailing_cr => stripped,
+            _ => line,
+        };
+        let mut line = Cow::Borrowed(line);
+        if self.ignore_ansi {
+            line = normalize::strip_ansi(line);
+        }
+        for substitution in &self.preprocess {
+            line = substitution.apply(line);
+        }
+        for normalizer in &self.normalizers {
+            line = normalizer.apply(line);
+        }
+        if let Some(form) = self.normalize_unicode {
+            line = form.apply(line);
+        }
+        if self.ignore_all_space {
+            line = normalize::strip_whitespace(line);
+        } else if self.ignore_space_change {
+            line = normalize::squeeze_whitespace(line);
+        }
+        if self.ignore_case {
+            line = normalize::fold_case(line);
+        }
+        line
+    }
+
+    /// How to split changed lines to highlight what changed within them, if at all
+    pub fn tokenizer(&self) -> Option<&dyn Tokenizer> {
+        match (&self.tokenizer, &self.word_diff) {
+            (Some(CustomTokenizer(tokenizer)), _) => Some(tokenizer.as_ref()),
+            (None, Some(mode)) => Some(mode),
+            (None, None) => None,
+        }
+    }
+
+    /// Whether adding or removing 'line' doesn't count as a difference: blank lines with -B,
+    /// and lines matching one of the -I patterns
+    fn ignores(&self, line: &str) -> bool {
+        (self.ignore_blank_lines && line.trim().is_empty())
+            || self
+                .ignore_matching_lines
+                .iter()
+                .any(|regex| regex.is_match(line))
+    }
+
+    /// How 'line' is drawn: a carriage return at its end is left out,
+    /// or shown as '␍' with `show_line_endings`, and other control characters are made harmless.
+    /// With `show_whitespace`, whitespace in 'changed' lines is made visible too
+    pub fn display_line(&self, mut line: String, changed: bool) -> String {
+        let cr = line.ends_with('\r');
+        if cr {
+            line.pop();
+        }
+        let mut line = self.control_chars.apply(line);
+        if cr && self.show_line_endings {
+            line.push('\u{240d}');
+        }
+        if self.show_whitespace && changed {
+            normalize::visible_whitespace(&line)
+        } else {
+            line
+        }
+    }
+
+    /// Whether to emit escape codes
+    pub(crate) fn color_enabled(&self) -> bool {
+        self.color_mode.enabled()
+    }
+
+    pub(crate) fn write_headers(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.no_header {
+            return Ok(());
+        }
+        let theme = self.theme();
+        let left_header = header(
+            Side::Left,
+            self.left_name.as_ref(),
+            self.left_marker,
+            self.marker_count,
+        );
+        let left_header = theme
+            .header
+            .unwrap_or(theme.left)
+            .apply(left_header)
+            .paint(self.color_enabled());
+        let right_header = header(
+            Side::Right,
+            self.right_name.as_ref(),
+            self.right_marker,
+            self.marker_count,
+        );
+        let right_header = theme
+            .header
+            .unwrap_or(theme.right)
+            .apply(right_header)
+            .paint(self.color_enabled());
+        writeln!(f, "{left_header}")?;
+        writeln!(f, "{right_header}")
     }
 }
 
@@ -264,11 +1242,43 @@ impl Default for DiffSettings {
             right_marker: DEFAULT_RIGHT_MARKER,
             marker_count: DEFAULT_MARKER_COUNT,
             indent_spaces: DEFAULT_INDENT_SPACES,
-            force_color: false,
-            left_color: Some(DEFAULT_LEFT_COLOR),
-            right_color: Some(DEFAULT_RIGHT_COLOR),
-            no_color: false,
+            no_line_numbers: false,
+            no_header: false,
+            theme: Theme::classic(),
+            left_color: None,
+            right_color: None,
+            left_background: None,
+            right_background: None,
+            color_mode: ColorMode::Auto,
             max_line_number: None,
+            context_lines: None,
+            only_changes: false,
+            preprocess: Vec::new(),
+            normalizers: Vec::new(),
+            normalize_unicode: None,
+            ignore_all_space: false,
+            ignore_space_change: false,
+            ignore_blank_lines: false,
+            ignore_matching_lines: Vec::new(),
+            ignore_case: false,
+            strip_trailing_cr: false,
+            show_line_endings: false,
+            show_whitespace: false,
+            control_chars: ControlChars::Escape,
+            ignore_ansi: false,
+            unordered_arrays: Vec::new(),
+            key_column: None,
+            algorithm: Algorithm::Myers,
+            word_diff: None,
+            tokenizer: None,
+            hyperlinks: false,
+            hyperlink_format: None,
+            #[cfg(feature = "highlight")]
+            highlight: false,
+            #[cfg(feature = "highlight")]
+            syntax: None,
+            side_by_side: false,
+            column_width: None,
         }
     }
 }
@@ -0,0 +1,124 @@
+use crate::{Diff, visible_lines};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LineKind {
+    /// Only in the left input
+    Removed,
+    /// Only in the right input
+    Added,
+    /// In both inputs
+    Unchanged,
+}
+
+/// A single line of a diff with its 1-based line numbers on each side
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DiffLine<'d, T> {
+    pub left_lineno: Option<usize>,
+    pub right_lineno: Option<usize>,
+    pub kind: LineKind,
+    pub content: &'d T,
+}
+
+/// A run of lines containing at least one change, with limited unchanged context around it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DiffHunk<'d, T> {
+    pub lines: Vec<DiffLine<'d, T>>,
+}
+
+impl<T> DiffHunk<'_, T> {
+    /// First left line number in the hunk
+    pub fn left_start(&self) -> Option<usize> {
+        self.lines.iter().find_map(|line| line.left_lineno)
+    }
+
+    /// First right line number in the hunk
+    pub fn right_start(&self) -> Option<usize> {
+        self.lines.iter().find_map(|line| line.right_lineno)
+    }
+}
+
+impl<T> Diff<'_, T> {
+    fn results(&self) -> &[diff::Result<T>] {
+        match self {
+            Self::Same => &[],
+            Self::Diff { diff, .. } => diff,
+        }
+    }
+
+    /// Every line of the diff, in order. Empty if the inputs are the same
+    pub fn lines(&self) -> impl Iterator<Item = DiffLine<'_, T>> {
+        let mut left_lineno = 0;
+        let mut right_lineno = 0;
+        self.results().iter().map(move |line| match line {
+            diff::Result::Left(l) => {
+                left_lineno += 1;
+                DiffLine {
+                    left_lineno: Some(left_lineno),
+                    right_lineno: None,
+                    kind: LineKind::Removed,
+                    content: l,
+                }
+            }
+            diff::Result::Both(l, _) => {
+                left_lineno += 1;
+                right_lineno += 1;
+                DiffLine {
+                    left_lineno: Some(left_lineno),
+                    right_lineno: Some(right_lineno),
+                    kind: LineKind::Unchanged,
+                    content: l,
+                }
+            }
+            diff::Result::Right(r) => {
+                right_lineno += 1;
+                DiffLine {
+                    left_lineno: None,
+                    right_lineno: Some(right_lineno),
+                    kind: LineKind::Added,
+                    content: r,
+                }
+            }
+        })
+    }
+
+    /// How many rows the diff takes when printed as usual: the headers, the lines shown and a
+    /// "..." row for each run of hidden lines. Counting stops at 'limit', as when checking
+    /// whether the diff fits on screen, and nothing is rendered
+    pub fn row_count(&self, limit: usize) -> usize {
+        let Self::Diff { settings, diff } = self else {
+            return 0;
+        };
+        let mut rows = if settings.show_header() { 2 } else { 0 };
+        let mut skipping = false;
+        for visible in visible_lines(diff, settings.context()) {
+            if rows >= limit {
+                break;
+            }
+            if visible {
+                rows += 1;
+                skipping = false;
+            } else if !skipping && !settings.only_changes {
+                rows += 1;
+                skipping = true;
+            }
+        }
+        rows.min(limit)
+    }
+
+    /// Changed regions of the diff with at most 'context' unchanged lines around each change
+    pub fn hunks(&self, context: usize) -> impl Iterator<Item = DiffHunk<'_, T>> {
+        let visible = visible_lines(self.results(), Some(context));
+        let mut lines = self.lines().zip(visible).peekable();
+        std::iter::from_fn(move || {
+            // Skip to the start of the next hunk, then take lines until it ends
+            while lines.next_if(|(_, visible)| !visible).is_some() {}
+            let mut hunk = Vec::new();
+            while let Some((line, _)) = lines.next_if(|(_, visible)| *visible) {
+                hunk.push(line);
+            }
+            (!hunk.is_empty()).then_some(DiffHunk { lines: hunk })
+        })
+    }
+}
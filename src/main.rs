@@ -1,19 +1,242 @@
-use anyhow::Result;
-use clap::Parser;
-use different::{DiffSettings, line_diff};
+use anyhow::{Context, Result, bail};
+use base64::prelude::{BASE64_STANDARD, Engine};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum, error::ErrorKind};
+use different::runner::{HttpRequest, RunOptions, http_request, run_command};
+use different::{
+    ColorMode, Diff, DiffSettings, DiffSettingsBuilder, Encoding, OutputFormat, ReportFormat,
+    StructuredFormat, WordDiff, decode, hex_rows, line_diff, parse_encoding, slice_diff,
+    structured_diff, table_diff,
+};
+use glob::Pattern;
 use log::debug;
 use pathdiff::diff_paths;
+use recursive::{WalkOptions, diff_dirs};
+use std::collections::HashMap;
+use std::io::{self, IsTerminal, Read, Write};
 use std::path::Path;
 use std::path::PathBuf;
+use std::process::ExitCode;
 use std::{env, fs};
+use terminal_size::{Height, terminal_size};
+
+mod apply;
+mod archive;
+mod check;
+mod config;
+mod pager;
+mod recursive;
+mod watch;
+
+/// Unchanged lines around each change in unified diffs without --context, as in `diff -u`
+const UNIFIED_CONTEXT: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum InputType {
+    /// Compare lines
+    Text,
+    Json,
+    Yaml,
+    Toml,
+    /// Compare rows and cells of comma separated values
+    Csv,
+    /// Compare rows and cells of tab separated values
+    Tsv,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Apply a unified diff to a file or a directory tree
+    Apply {
+        /// The patch, or '-' for stdin
+        patch: PathBuf,
+
+        /// File to patch, or directory the patch's paths are relative to
+        target: PathBuf,
+
+        /// Check that the patch applies without changing anything
+        #[clap(long)]
+        dry_run: bool,
+
+        /// Undo the patch instead of applying it
+        #[clap(short = 'R', long)]
+        reverse: bool,
+    },
+
+    /// Run the checks in a TOML, YAML or JSON checks file
+    Check {
+        /// The checks file
+        checks: PathBuf,
+
+        /// Directory the checks run in. Defaults to the checks file's directory
+        #[clap(long, value_name = "DIR")]
+        base: Option<PathBuf>,
+
+        /// Set a variable, overriding the checks file
+        #[clap(long = "var", value_name = "KEY=VALUE", value_parser = parse_variable)]
+        variables: Vec<(String, String)>,
+
+        /// Also write the results as FORMAT to PATH, or instead of the usual output if PATH is '-'
+        #[clap(long = "report", value_name = "FORMAT=PATH", value_parser = parse_report)]
+        reports: Vec<(ReportFormat, PathBuf)>,
+
+        /// Run up to N checks at once
+        #[clap(short, long, value_name = "N", default_value_t = 1)]
+        jobs: usize,
+
+        /// Replace expected contents and outputs that didn't match with the actual ones
+        #[clap(long)]
+        update_snapshots: bool,
+
+        /// Stop at the first check that fails, skipping the rest
+        #[clap(long, overrides_with = "keep_going")]
+        fail_fast: bool,
+
+        /// Run every check even after one fails (the default)
+        #[clap(long, overrides_with = "fail_fast")]
+        keep_going: bool,
+
+        /// Keep running, checking again whenever the checks file or base directory changes
+        #[clap(long)]
+        watch: bool,
+    },
+
+    /// Print the completion script for SHELL
+    Completions {
+        #[clap(value_enum)]
+        shell: clap_complete::Shell,
+    },
+
+    /// Print the man page
+    Man,
+}
+
+fn parse_report(s: &str) -> Result<(ReportFormat, PathBuf), String> {
+    let Some((format, path)) = s.split_once('=') else {
+        return Err(format!("expected FORMAT=PATH, got '{s}'"));
+    };
+    let format = ReportFormat::from_str(format, true)?;
+    Ok((format, PathBuf::from(path)))
+}
+
+fn parse_header(s: &str) -> Result<(String, String), String> {
+    s.split_once(':')
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .ok_or_else(|| format!("expected NAME: VALUE, got '{s}'"))
+}
+
+fn parse_variable(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or_else(|| format!("expected KEY=VALUE, got '{s}'"))
+}
 
 #[derive(Parser)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Cli {
-    /// Input file 1
-    left: PathBuf,
+    #[clap(subcommand)]
+    command: Option<Command>,
+
+    /// Input file 1, '-' for stdin, or an http(s) URL
+    #[clap(required_unless_present_any = ["left_cmd", "right_cmd"])]
+    left: Option<PathBuf>,
+
+    /// Input file 2, '-' for stdin, or an http(s) URL
+    #[clap(required_unless_present_any = ["git_diff", "left_cmd", "right_cmd"])]
+    right: Option<PathBuf>,
+
+    /// The rest of the arguments git passes with --git-diff
+    #[clap(requires = "git_diff", hide = true)]
+    git_args: Vec<PathBuf>,
+
+    /// Take the arguments git passes to GIT_EXTERNAL_DIFF programs instead of two inputs:
+    /// PATH OLD-FILE OLD-HEX OLD-MODE NEW-FILE NEW-HEX NEW-MODE.
+    /// Exits with 0 when the files differ, since git stops at any other status
+    #[clap(long, conflicts_with_all = ["labels", "recursive", "watch", "interactive"])]
+    git_diff: bool,
+
+    /// Compare the output of this shell command instead of input file 1
+    #[clap(long, value_name = "COMMAND", conflicts_with_all = ["git_diff", "recursive", "watch"])]
+    left_cmd: Option<String>,
+
+    /// Compare the output of this shell command instead of input file 2
+    #[clap(long, value_name = "COMMAND", conflicts_with_all = ["git_diff", "recursive", "watch"])]
+    right_cmd: Option<String>,
+
+    /// Use LABEL instead of the file name, or stdin's '-', in the headers. Give it twice to label
+    /// both sides, like GNU diff. Takes precedence over --left-name and --right-name
+    #[clap(long = "label", value_name = "LABEL")]
+    labels: Vec<String>,
+
+    /// Send this header when fetching URL inputs
+    #[clap(short = 'H', long = "header", value_name = "NAME: VALUE", value_parser = parse_header)]
+    headers: Vec<(String, String)>,
+
+    /// Log in with basic authentication when fetching URL inputs
+    #[clap(long, value_name = "USER:PASSWORD")]
+    user: Option<String>,
+
+    /// Read the inputs as ENCODING, like 'latin1' or 'utf-16le', instead of detecting it
+    #[clap(long, value_name = "ENCODING", value_parser = parse_encoding)]
+    encoding: Option<&'static Encoding>,
+
+    /// Don't verify the certificates of https URL inputs
+    #[clap(long)]
+    insecure: bool,
+
+    /// Compare directories recursively. Implied when both inputs are directories
+    #[clap(short, long)]
+    recursive: bool,
 
-    /// Input file 2
-    right: PathBuf,
+    /// Skip files and directories matching PATTERN in recursive mode
+    #[clap(long, value_name = "PATTERN")]
+    exclude: Vec<String>,
+
+    /// Descend at most N directory levels in recursive mode
+    #[clap(long, value_name = "N")]
+    max_depth: Option<usize>,
+
+    /// Parse the inputs as TYPE and compare their values instead of their lines.
+    /// Detected from the file extensions when not given
+    #[clap(long = "type", value_enum, value_name = "TYPE")]
+    input_type: Option<InputType>,
+
+    /// Show a hexdump diff of binary files, highlighting the changed bytes,
+    /// instead of only reporting that they differ
+    #[clap(long)]
+    hex: bool,
+
+    /// Only report whether the inputs differ, not how
+    #[clap(short = 'q', long, visible_alias = "quiet", conflicts_with_all = ["stat", "hex"])]
+    brief: bool,
+
+    /// Say so when the inputs are the same, instead of printing nothing
+    #[clap(short = 's', long)]
+    report_identical_files: bool,
+
+    /// Only print how many lines were inserted and deleted
+    #[clap(long, conflicts_with = "output_format")]
+    stat: bool,
+
+    /// How to print the diff
+    #[clap(long, value_enum, default_value_t)]
+    output_format: OutputFormat,
+
+    /// Wrap each file's Markdown diff in a collapsible <details> block
+    #[clap(long)]
+    details: bool,
+
+    /// Keep running, clearing the screen and printing the diff again whenever an input changes
+    #[clap(long)]
+    watch: bool,
+
+    /// Browse the diff in the terminal, with scrolling, change navigation and search.
+    /// Used automatically for line diffs taller than the terminal
+    #[clap(long, conflicts_with_all = ["output_format", "stat", "brief", "side_by_side", "watch"])]
+    interactive: bool,
+
+    /// Always print the diff, even when it doesn't fit in the terminal
+    #[clap(long, conflicts_with = "interactive")]
+    no_interactive: bool,
 
     #[clap(flatten)]
     settings: DiffSettings,
@@ -25,35 +248,610 @@ fn display_name(path: &Path, cwd: &Path) -> String {
         .unwrap_or(path.display().to_string())
 }
 
-/// Returns (Name: String, contents: String, num_lines: usize)
-fn process_file(path: &Path, cwd: &Path) -> Result<(String, String, usize)> {
-    let path = path.canonicalize()?;
-    let name = display_name(&path, cwd);
-    let contents = fs::read_to_string(path)?;
-    let num_lines = contents.lines().count();
-    Ok((name, contents, num_lines))
+/// One side of the diff, read into memory
+struct Input {
+    /// Where it was read from, to tell its type by. Empty for the output of a command
+    path: PathBuf,
+    name: String,
+    contents: Vec<u8>,
 }
 
-fn main() -> Result<()> {
-    env_logger::init();
+/// Read a file, stdin, or the body of a URL
+fn read_input(path: &Path, cwd: &Path, args: &Cli) -> Result<Input> {
+    match path.to_str() {
+        Some(url) if url.starts_with("http://") || url.starts_with("https://") => {
+            process_url(url, args)
+        }
+        _ => process_file(path, cwd),
+    }
+}
+
+fn process_file(path: &Path, cwd: &Path) -> Result<Input> {
+    if path == Path::new("-") {
+        let mut contents = Vec::new();
+        io::stdin()
+            .read_to_end(&mut contents)
+            .context("Unable to read stdin")?;
+        return Ok(Input {
+            path: path.to_path_buf(),
+            name: String::from("<stdin>"),
+            contents,
+        });
+    }
+
+    let contents = fs::read(path).with_context(|| format!("Unable to read {}", path.display()))?;
+    // Paths from process substitution (/dev/fd/63) can be read but not canonicalized
+    let name = match path.canonicalize() {
+        Ok(path) => display_name(&path, cwd),
+        Err(_) => path.display().to_string(),
+    };
+    Ok(Input {
+        path: path.to_path_buf(),
+        name,
+        contents,
+    })
+}
+
+/// Fetch 'url' with the headers and login from the arguments. Error statuses are errors
+fn process_url(url: &str, args: &Cli) -> Result<Input> {
+    let mut request = HttpRequest::new("GET", url);
+    request.headers = args.headers.clone();
+    if let Some(user) = &args.user {
+        let credentials = BASE64_STANDARD.encode(user);
+        request
+            .headers
+            .push(("Authorization".to_string(), format!("Basic {credentials}")));
+    }
+    request.verify_tls = !args.insecure;
+    let response = http_request(&request).with_context(|| format!("Unable to fetch {url}"))?;
+    if response.status >= 400 {
+        bail!("Fetching {url} failed with status {}", response.status);
+    }
+    // The URL without its query, so the extension of its path tells the type
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    Ok(Input {
+        path: PathBuf::from(path),
+        name: url.to_string(),
+        contents: response.body,
+    })
+}
+
+/// Run 'cmd' with the system shell and take its output, named after the command.
+/// Its errors are passed on to stderr
+fn process_command(cmd: &str, cwd: &Path) -> Result<Input> {
+    let Some(output) = run_command(cmd, cwd, &HashMap::new(), None, true, None)? else {
+        unreachable!("commands without a timeout aren't killed");
+    };
+    io::stderr().write_all(&output.stderr)?;
+    if !output.status.success() {
+        eprintln!("Command '{cmd}' failed with {}", output.status);
+    }
+    Ok(Input {
+        path: PathBuf::new(),
+        name: cmd.to_string(),
+        contents: output.stdout,
+    })
+}
+
+/// Returns true if the inputs are the same
+fn run() -> Result<bool> {
     let cwd = env::current_dir()?;
-    let args = Cli::parse();
+    let mut args = Cli::parse_from(config::with_defaults(
+        Cli::command(),
+        env::args_os().collect(),
+    )?);
+    if args.labels.len() > 2 {
+        Cli::command()
+            .error(
+                ErrorKind::TooManyValues,
+                "--label may be given at most twice",
+            )
+            .exit();
+    }
+    if let Err(e) = args.settings.validate() {
+        Cli::command().error(ErrorKind::ArgumentConflict, e).exit();
+    }
+
+    match &args.command {
+        Some(Command::Apply {
+            patch,
+            target,
+            dry_run,
+            reverse,
+        }) => {
+            apply::apply(patch, target, *dry_run, *reverse)?;
+            return Ok(true);
+        }
+        Some(Command::Check {
+            checks,
+            base,
+            variables,
+            reports,
+            jobs,
+            update_snapshots,
+            fail_fast,
+            keep_going: _,
+            watch,
+        }) => {
+            let options = RunOptions {
+                jobs: *jobs,
+                fail_fast: *fail_fast,
+                ..Default::default()
+            };
+            return check::check(
+                checks,
+                base.as_deref(),
+                variables,
+                reports,
+                options,
+                *update_snapshots,
+                *watch,
+            );
+        }
+        Some(Command::Completions { shell }) => {
+            // clap_complete panics if it can't write, so write it out ourselves
+            let mut script = Vec::new();
+            clap_complete::generate(
+                *shell,
+                &mut Cli::command(),
+                env!("CARGO_BIN_NAME"),
+                &mut script,
+            );
+            io::stdout()
+                .write_all(&script)
+                .context("Unable to write the completion script")?;
+            return Ok(true);
+        }
+        Some(Command::Man) => {
+            let command = Cli::command()
+                .name(env!("CARGO_BIN_NAME"))
+                .about(env!("CARGO_PKG_DESCRIPTION"))
+                .version(env!("CARGO_PKG_VERSION"));
+            clap_mangen::Man::new(command)
+                .render(&mut io::stdout())
+                .context("Unable to write the man page")?;
+            return Ok(true);
+        }
+        None => {}
+    }
+
+    if args.git_diff {
+        // Git colors its own output when it pipes it to a pager, so match it
+        if env::var_os("GIT_PAGER_IN_USE").is_some()
+            && args.settings.color_mode() == ColorMode::Auto
+        {
+            args.settings = DiffSettingsBuilder::from(args.settings)
+                .color_mode(ColorMode::Always)
+                .build();
+        }
+        git_diff(&cwd, &args)?;
+        return Ok(true);
+    }
+
+    if args.left_cmd.is_some() || args.right_cmd.is_some() {
+        return diff_commands(&cwd, &args);
+    }
+
+    // Both are required unless there is a subcommand
+    let (Some(left), Some(right)) = (&args.left, &args.right) else {
+        unreachable!("clap enforces both inputs");
+    };
+    if left == Path::new("-") && right == Path::new("-") {
+        bail!("Only one input can be read from stdin");
+    }
+
+    if args.watch {
+        if left == Path::new("-") || right == Path::new("-") {
+            bail!("--watch can't read from stdin");
+        }
+        watch::watch(&[left, right], || {
+            // Clear the screen and move to the top left
+            write!(io::stdout(), "\x1b[2J\x1b[H")?;
+            diff_inputs(left, right, &cwd, &args).map(|_| ())
+        })?;
+        unreachable!("watching only stops with an error");
+    }
+
+    diff_inputs(left, right, &cwd, &args)
+}
+
+/// Diff a file as git asks external diff programs to, naming the sides like git does.
+/// Git passes just the path of unmerged files, and two more arguments for renames
+fn git_diff(cwd: &Path, args: &Cli) -> Result<()> {
+    let git_args: Vec<String> = args
+        .left
+        .iter()
+        .chain(&args.right)
+        .chain(&args.git_args)
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect();
+    let (path, old_file, old_mode, new_file, new_mode, new_path) = match git_args.as_slice() {
+        [path] => {
+            writeln!(io::stdout(), "* Unmerged path {path}")?;
+            return Ok(());
+        }
+        [path, old_file, _, old_mode, new_file, _, new_mode] => {
+            (path, old_file, old_mode, new_file, new_mode, path)
+        }
+        [
+            path,
+            old_file,
+            _,
+            old_mode,
+            new_file,
+            _,
+            new_mode,
+            new_path,
+            _,
+        ] => (path, old_file, old_mode, new_file, new_mode, new_path),
+        _ => bail!(
+            "--git-diff takes the 1, 7 or 9 arguments git passes, not {}",
+            git_args.len()
+        ),
+    };
+
+    // A missing side is /dev/null, with '.' for its hash and mode
+    let name = |file: &str, prefix: &str, path: &str| {
+        if file == "/dev/null" {
+            file.to_string()
+        } else {
+            format!("{prefix}/{path}")
+        }
+    };
+    let labels = [name(old_file, "a", path), name(new_file, "b", new_path)];
+    if old_mode != new_mode && old_mode != "." && new_mode != "." {
+        writeln!(io::stdout(), "old mode {old_mode}")?;
+        writeln!(io::stdout(), "new mode {new_mode}")?;
+    }
+    diff_files(
+        Path::new(old_file),
+        Path::new(new_file),
+        cwd,
+        &labels,
+        args,
+        false,
+    )?;
+    Ok(())
+}
+
+/// Diff the output of --left-cmd and --right-cmd, or of one of them and the input file given.
+/// Returns true if they are the same
+fn diff_commands(cwd: &Path, args: &Cli) -> Result<bool> {
+    let mut files = args.left.iter().chain(&args.right);
+    // Input files fill in the sides without a command, in order
+    let mut input = |cmd: &Option<String>, side: &str| match cmd {
+        Some(cmd) => process_command(cmd, cwd),
+        None => match files.next() {
+            Some(file) => read_input(file, cwd, args),
+            None => bail!("Missing the {side} input"),
+        },
+    };
+    let left = input(&args.left_cmd, "left")?;
+    let right = input(&args.right_cmd, "right")?;
+    if let Some(file) = files.next() {
+        bail!("Unexpected input {}", file.display());
+    }
+    diff_contents(left, right, &args.labels, args, true)
+}
+
+/// Diff files or directories as the arguments say. Returns true if they are the same
+fn diff_inputs(left: &Path, right: &Path, cwd: &Path, args: &Cli) -> Result<bool> {
+    let directories = args.recursive || (left.is_dir() && right.is_dir());
+    let archives = archive::is_archive(left) && archive::is_archive(right);
+    if !directories && !archives {
+        return diff_files(left, right, cwd, &args.labels, args, !args.watch);
+    }
+
+    let exclude = args
+        .exclude
+        .iter()
+        .map(|pattern| Pattern::new(pattern))
+        .collect::<Result<_, _>>()
+        .context("Invalid --exclude pattern")?;
+    let options = WalkOptions {
+        exclude,
+        max_depth: args.max_depth,
+    };
+    if args.interactive {
+        bail!("--interactive only compares two files, not directories or archives");
+    }
+    if directories {
+        return diff_dirs(left, right, &options, |left, right| {
+            diff_files(left, right, cwd, &[], args, false)
+        });
+    }
+    // Files in archives are named like 'release.tar:bin/tool'
+    let entry = |archive: &Path, path: &Path, contents| Input {
+        path: path.to_path_buf(),
+        name: format!("{}:{}", archive.display(), path.display()),
+        contents,
+    };
+    archive::diff_archives(
+        left,
+        right,
+        &options,
+        |path, left_contents, right_contents| {
+            let left = entry(left, path, left_contents);
+            let right = entry(right, path, right_contents);
+            diff_contents(left, right, &[], args, false)
+        },
+    )
+}
 
-    let left = args.left;
-    let right = args.right;
+/// Diff two files and print the result, or browse it if 'pageable' and the arguments call for it.
+/// Returns true if they are the same
+fn diff_files(
+    left: &Path,
+    right: &Path,
+    cwd: &Path,
+    labels: &[String],
+    args: &Cli,
+    pageable: bool,
+) -> Result<bool> {
+    let left = read_input(left, cwd, args)?;
+    let right = read_input(right, cwd, args)?;
+    diff_contents(left, right, labels, args, pageable)
+}
+
+/// Diff two inputs like [`diff_files`]
+fn diff_contents(
+    left: Input,
+    right: Input,
+    labels: &[String],
+    args: &Cli,
+    pageable: bool,
+) -> Result<bool> {
+    // Labels win over --left-name and --right-name, which win over the paths
+    let mut labels = labels.iter().cloned();
+    let left_name = labels
+        .next()
+        .or_else(|| args.settings.left_name().map(str::to_string))
+        .unwrap_or(left.name);
+    let right_name = labels
+        .next()
+        .or_else(|| args.settings.right_name().map(str::to_string))
+        .unwrap_or(right.name);
+    let (left, left_bytes) = (left.path.as_path(), left.contents);
+    let (right, right_bytes) = (right.path.as_path(), right.contents);
 
-    let (left_name, left_contents, left_num_lines) = process_file(&left, &cwd)?;
-    let (right_name, right_contents, right_num_lines) = process_file(&right, &cwd)?;
+    // Inputs in other encodings are compared as UTF-8, inputs in none of them are binary
+    let decoded = (
+        decode(&left_bytes, args.encoding),
+        decode(&right_bytes, args.encoding),
+    );
+    let (Some(left_contents), Some(right_contents)) = decoded else {
+        let same = left_bytes == right_bytes;
+        if print_summary(same, &left_name, &right_name, args)? || same {
+            return Ok(same);
+        }
+        if !args.hex {
+            writeln!(
+                io::stdout(),
+                "Binary files {left_name} and {right_name} differ"
+            )?;
+            return Ok(false);
+        }
 
-    let num_lines = std::cmp::max(left_num_lines, right_num_lines);
-    let settings = args
-        .settings
-        .names(left_name, right_name)
-        .max_line_number(num_lines);
+        let left_rows = hex_rows(&left_bytes);
+        let right_rows = hex_rows(&right_bytes);
+        let settings = DiffSettingsBuilder::from(args.settings.clone())
+            .names(left_name, right_name)
+            .max_line_number(std::cmp::max(left_rows.len(), right_rows.len()))
+            .word_diff(WordDiff::Word)
+            .build();
+        slice_diff(&left_rows, &right_rows, &settings).write_to(&mut io::stdout().lock())?;
+        writeln!(io::stdout())?;
+        return Ok(false);
+    };
+
+    let num_lines = std::cmp::max(
+        left_contents.lines().count(),
+        right_contents.lines().count(),
+    );
+    let settings = DiffSettingsBuilder::from(args.settings.clone())
+        .names(left_name.clone(), right_name.clone())
+        .max_line_number(num_lines)
+        .build();
     debug!("{settings:?}");
 
+    if let Some(delimiter) = table_delimiter(left, right, args.input_type) {
+        if args.output_format != OutputFormat::Text || args.stat {
+            bail!("--output-format and --stat are only supported for line diffs");
+        }
+        let diff = table_diff(&left_contents, &right_contents, delimiter, &settings)?;
+        let same = diff.changes().is_empty();
+        if !print_summary(same, &left_name, &right_name, args)? {
+            write!(io::stdout(), "{diff}")?;
+        }
+        return Ok(same);
+    }
+
+    if let Some(format) = structured_format(left, right, args.input_type) {
+        if !matches!(
+            args.output_format,
+            OutputFormat::Text | OutputFormat::JsonPatch
+        ) || args.stat
+        {
+            bail!("--output-format and --stat are only supported for line diffs");
+        }
+        let diff = structured_diff(&left_contents, &right_contents, format, &settings)?;
+        let same = diff.changes().is_empty();
+        if print_summary(same, &left_name, &right_name, args)? {
+            return Ok(same);
+        }
+        match args.output_format {
+            OutputFormat::JsonPatch => writeln!(io::stdout(), "{}", diff.to_json_patch()?)?,
+            _ => write!(io::stdout(), "{diff}")?,
+        }
+        return Ok(same);
+    }
+    if args.output_format == OutputFormat::JsonPatch {
+        bail!("--output-format json-patch is only supported for JSON, YAML and TOML inputs");
+    }
+
     let diff = line_diff(&left_contents, &right_contents, &settings);
-    println!("{diff}");
+    let same = matches!(diff, Diff::Same);
+    if print_summary(same, &left_name, &right_name, args)? {
+        return Ok(same);
+    }
+    if args.stat {
+        writeln!(io::stdout(), "{}", diff.stats())?;
+        return Ok(same);
+    }
+    match args.output_format {
+        OutputFormat::Json => writeln!(io::stdout(), "{}", diff.to_json()?)?,
+        OutputFormat::Html => write!(io::stdout(), "{}", diff.to_html())?,
+        OutputFormat::Markdown => write!(io::stdout(), "{}", diff.to_markdown(args.details))?,
+        OutputFormat::Unified if same => {}
+        OutputFormat::Unified => {
+            let context = if settings.only_changes() {
+                0
+            } else {
+                settings.context_lines().unwrap_or(UNIFIED_CONTEXT)
+            };
+            write!(io::stdout(), "{}", diff.to_unified(context))?;
+        }
+        OutputFormat::Text if pageable && use_pager(&diff, &settings, args)? => {
+            let title = format!("{left_name} -> {right_name}");
+            pager::page(&left_contents, &right_contents, title, settings.clone())?;
+        }
+        OutputFormat::Text => {
+            if same && !args.brief && unicode_made_same(&left_contents, &right_contents, &settings)
+            {
+                eprintln!("Inputs are identical after Unicode normalization");
+            }
+            print_text(&diff)?
+        }
+        OutputFormat::JsonPatch => unreachable!("JSON Patches are only made for structured diffs"),
+    }
 
-    Ok(())
+    Ok(same)
+}
+
+/// Whether to browse the diff instead of printing it: when asked to, or when it's taller than the terminal
+fn use_pager(diff: &Diff<&str>, settings: &DiffSettings, args: &Cli) -> Result<bool> {
+    let terminal = io::stdout().is_terminal() && io::stdin().is_terminal();
+    if args.interactive {
+        if !terminal {
+            bail!("--interactive needs a terminal");
+        }
+        return Ok(true);
+    }
+    if args.no_interactive || !terminal || settings.is_side_by_side() {
+        return Ok(false);
+    }
+    let Some((_, Height(height))) = terminal_size() else {
+        return Ok(false);
+    };
+    let height = usize::from(height);
+    Ok(diff.row_count(height + 1) > height)
+}
+
+/// Print the one line summary asked for with --brief or --report-identical-files.
+/// Returns true if it was printed, in which case nothing else should be
+fn print_summary(same: bool, left_name: &str, right_name: &str, args: &Cli) -> io::Result<bool> {
+    if same && args.report_identical_files {
+        writeln!(
+            io::stdout(),
+            "Files {left_name} and {right_name} are identical"
+        )?;
+        Ok(true)
+    } else if !same && args.brief {
+        writeln!(io::stdout(), "Files {left_name} and {right_name} differ")?;
+        Ok(true)
+    } else {
+        Ok(false)
+    }
+}
+
+/// What both inputs look like according to 'detect'.
+/// Both have to agree, except that stdin goes along with the other side
+fn detect_both<T: PartialEq>(
+    left: &Path,
+    right: &Path,
+    detect: impl Fn(&Path) -> Option<T>,
+) -> Option<T> {
+    let stdin = Path::new("-");
+    match (detect(left), detect(right)) {
+        (Some(l), Some(r)) if l == r => Some(l),
+        (Some(found), None) if right == stdin => Some(found),
+        (None, Some(found)) if left == stdin => Some(found),
+        _ => None,
+    }
+}
+
+/// The format to compare the inputs as, if they are data files.
+/// Without an explicit type, it is detected from the file extensions
+fn structured_format(
+    left: &Path,
+    right: &Path,
+    input_type: Option<InputType>,
+) -> Option<StructuredFormat> {
+    match input_type {
+        Some(InputType::Json) => Some(StructuredFormat::Json),
+        Some(InputType::Yaml) => Some(StructuredFormat::Yaml),
+        Some(InputType::Toml) => Some(StructuredFormat::Toml),
+        Some(_) => None,
+        None => detect_both(left, right, StructuredFormat::from_path),
+    }
+}
+
+/// The delimiter to split the inputs' cells at, if they are tables.
+/// Without an explicit type, it is detected from the file extensions
+fn table_delimiter(left: &Path, right: &Path, input_type: Option<InputType>) -> Option<u8> {
+    let delimiter = |path: &Path| match path.extension()?.to_str()?.to_lowercase().as_str() {
+        "csv" => Some(b','),
+        "tsv" => Some(b'\t'),
+        _ => None,
+    };
+    match input_type {
+        Some(InputType::Csv) => Some(b','),
+        Some(InputType::Tsv) => Some(b'\t'),
+        Some(_) => None,
+        None => detect_both(left, right, delimiter),
+    }
+}
+
+fn print_text(diff: &Diff<&str>) -> io::Result<()> {
+    if let Diff::Same = diff {
+        return Ok(());
+    }
+    let mut stdout = io::stdout().lock();
+    diff.write_to(&mut stdout)?;
+    writeln!(stdout)
+}
+
+/// Whether the inputs only compare the same because of --normalize-unicode
+fn unicode_made_same(left: &str, right: &str, settings: &DiffSettings) -> bool {
+    if settings.normalize_unicode().is_none() || left == right {
+        return false;
+    }
+    let without = DiffSettingsBuilder::from(settings.clone())
+        .normalize_unicode(None)
+        .build();
+    !matches!(line_diff(left, right, &without), Diff::Same)
+}
+
+/// Exit codes follow diff(1): 0 if the inputs are the same, 1 if they differ, 2 on trouble
+fn main() -> ExitCode {
+    env_logger::init();
+    match run() {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        // The reader went away, like `head` does once it has enough lines, so there's no one to tell
+        Err(e) if is_broken_pipe(&e) => ExitCode::from(2),
+        Err(e) => {
+            eprintln!("Error: {e:#}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// Whether 'error' comes from writing to a pipe whose reader has closed it
+fn is_broken_pipe(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        cause
+            .downcast_ref::<io::Error>()
+            .is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe)
+    })
 }
@@ -0,0 +1,120 @@
+use crate::html::escape;
+use crate::{Diff, DiffLine, DiffSettings, LineKind, visible_lines};
+use std::fmt::{Display, Write};
+
+/// A fence longer than any run of backticks in 'text', so the text can't close it early
+fn fence(text: &str) -> String {
+    let longest = text
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default();
+    "`".repeat((longest + 1).max(3))
+}
+
+/// A hunk range the way GNU diff writes it: 1-based start, length omitted when it is 1.
+/// An empty side starts at the line before it
+fn format_range(before: usize, len: usize) -> String {
+    match len {
+        0 => format!("{before},0"),
+        1 => format!("{}", before + 1),
+        len => format!("{},{len}", before + 1),
+    }
+}
+
+/// Write a hunk, given how many lines of each side come before it
+fn write_hunk<T: Display>(
+    out: &mut String,
+    settings: &DiffSettings,
+    (left_before, right_before): (usize, usize),
+    hunk: &[DiffLine<'_, T>],
+) -> std::fmt::Result {
+    let left_len = hunk
+        .iter()
+        .filter(|line| line.left_lineno.is_some())
+        .count();
+    let right_len = hunk
+        .iter()
+        .filter(|line| line.right_lineno.is_some())
+        .count();
+    writeln!(
+        out,
+        "@@ -{} +{} @@",
+        format_range(left_before, left_len),
+        format_range(right_before, right_len)
+    )?;
+    for line in hunk {
+        let marker = match line.kind {
+            LineKind::Removed => '-',
+            LineKind::Added => '+',
+            LineKind::Unchanged => ' ',
+        };
+        let content = settings.display_line(line.content.to_string(), marker != ' ');
+        writeln!(out, "{marker}{content}")?;
+    }
+    Ok(())
+}
+
+impl<T: Display> Diff<'_, T> {
+    /// The diff as a fenced ```diff block for pasting into Markdown, like GitHub comments.
+    /// With 'collapsible', it is wrapped in a `<details>` block titled with the input names.
+    /// Empty if the inputs are the same
+    pub fn to_markdown(&self, collapsible: bool) -> String {
+        let mut markdown = String::new();
+        self.write_markdown(&mut markdown, collapsible)
+            .expect("writing to a String can't fail");
+        markdown
+    }
+
+    fn write_markdown(&self, markdown: &mut String, collapsible: bool) -> std::fmt::Result {
+        let Self::Diff { settings, diff } = self else {
+            return Ok(());
+        };
+        let left = settings.left_name.as_deref().unwrap_or("left");
+        let right = settings.right_name.as_deref().unwrap_or("right");
+
+        let mut body = String::new();
+        writeln!(body, "--- {left}")?;
+        writeln!(body, "+++ {right}")?;
+        let visible = visible_lines(diff, settings.context());
+        let mut hunk = Vec::new();
+        // Lines of each side before the current hunk, and before the current line
+        let mut hunk_start = (0, 0);
+        let mut seen = (0, 0);
+        for (line, visible) in self.lines().zip(visible) {
+            let before = seen;
+            seen = (
+                line.left_lineno.unwrap_or(seen.0),
+                line.right_lineno.unwrap_or(seen.1),
+            );
+            if visible {
+                if hunk.is_empty() {
+                    hunk_start = before;
+                }
+                hunk.push(line);
+            } else if !hunk.is_empty() {
+                write_hunk(&mut body, settings, hunk_start, &hunk)?;
+                hunk.clear();
+            }
+        }
+        if !hunk.is_empty() {
+            write_hunk(&mut body, settings, hunk_start, &hunk)?;
+        }
+
+        if collapsible {
+            writeln!(markdown, "<details>")?;
+            writeln!(
+                markdown,
+                "<summary>{} → {}</summary>\n",
+                escape(left),
+                escape(right)
+            )?;
+        }
+        let fence = fence(&body);
+        write!(markdown, "{fence}diff\n{body}{fence}\n")?;
+        if collapsible {
+            writeln!(markdown, "\n</details>")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+use crate::{DifferentError, Result};
+use serde_json::{Map, Value};
+
+/// Expand every check with a `matrix` into one check per combination of its values.
+/// Each copy is named like `name[config=a,os=linux]`, gets the combination as variables
+/// and has `{{ key }}` replaced by the value in all of its strings
+pub(crate) fn expand_checks(file: &mut Value) -> Result<()> {
+    let Some(checks) = file.get_mut("checks").and_then(Value::as_array_mut) else {
+        return Ok(());
+    };
+    let mut expanded = Vec::with_capacity(checks.len());
+    for check in checks.drain(..) {
+        expanded.extend(expand(check)?);
+    }
+    *checks = expanded;
+    Ok(())
+}
+
+fn expand(mut check: Value) -> Result<Vec<Value>> {
+    let Some(matrix) = check.as_object_mut().and_then(|c| c.remove("matrix")) else {
+        return Ok(vec![check]);
+    };
+    let name = check
+        .get("name")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    let Value::Object(matrix) = matrix else {
+        return Err(DifferentError::Invalid(format!(
+            "The matrix of check '{name}' has to map variables to lists of values"
+        )));
+    };
+
+    // Every combination, as (key, value) pairs in key order
+    let mut combinations: Vec<Vec<(String, String)>> = vec![Vec::new()];
+    for (key, values) in &matrix {
+        let Some(values) = values.as_array() else {
+            return Err(DifferentError::Invalid(format!(
+                "Matrix variable '{key}' of check '{name}' has to be a list"
+            )));
+        };
+        let values: Vec<String> = values.iter().map(scalar).collect::<Result<_>>()?;
+        combinations = combinations
+            .into_iter()
+            .flat_map(|combination| {
+                values.iter().map(move |value| {
+                    let mut combination = combination.clone();
+                    combination.push((key.clone(), value.clone()));
+                    combination
+                })
+            })
+            .collect();
+    }
+
+    Ok(combinations
+        .into_iter()
+        .map(|combination| {
+            let mut check = check.clone();
+            substitute(&mut check, &combination);
+            let label: Vec<String> = combination
+                .iter()
+                .map(|(k, v)| format!("{k}={v}"))
+                .collect();
+            let object = check.as_object_mut().expect("checks are objects");
+            object.insert(
+                String::from("name"),
+                Value::String(format!("{name}[{}]", label.join(","))),
+            );
+            let variables = object
+                .entry("variables")
+                .or_insert_with(|| Value::Object(Map::new()));
+            if let Value::Object(variables) = variables {
+                for (key, value) in combination {
+                    variables.insert(key, Value::String(value));
+                }
+            }
+            check
+        })
+        .collect())
+}
+
+fn scalar(value: &Value) -> Result<String> {
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        Value::Number(_) | Value::Bool(_) => Ok(value.to_string()),
+        _ => Err(DifferentError::Invalid(format!(
+            "Matrix values have to be strings, numbers or booleans, not {value}"
+        ))),
+    }
+}
+
+/// Replace `{{ key }}` (with or without the spaces) in every string of 'value'
+fn substitute(value: &mut Value, combination: &[(String, String)]) {
+    match value {
+        Value::String(s) => {
+            for (key, replacement) in combination {
+                *s = s
+                    .replace(&format!("{{{{ {key} }}}}"), replacement)
+                    .replace(&format!("{{{{{key}}}}}"), replacement);
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(|v| substitute(v, combination)),
+        Value::Object(map) => map.values_mut().for_each(|v| substitute(v, combination)),
+        _ => {}
+    }
+}
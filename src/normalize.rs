@@ -0,0 +1,339 @@
+use crate::algorithm::{self, Algorithm, Edit};
+use crate::split_lines;
+use crate::{DifferentError, Result};
+use regex::{Regex, RegexBuilder};
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use unicode_normalization::UnicodeNormalization;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum UnicodeForm {
+    /// Canonical composition
+    Nfc,
+    /// Canonical decomposition
+    Nfd,
+}
+
+impl UnicodeForm {
+    pub(crate) fn apply<'a>(&self, line: Cow<'a, str>) -> Cow<'a, str> {
+        let normalized: String = match self {
+            Self::Nfc => line.nfc().collect(),
+            Self::Nfd => line.nfd().collect(),
+        };
+        if normalized == line {
+            line
+        } else {
+            Cow::Owned(normalized)
+        }
+    }
+}
+
+/// What to do with control characters, like escape, when drawing lines
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum ControlChars {
+    /// Show them like `cat -v` does, such as '^[' for escape
+    #[default]
+    Escape,
+    /// Leave them out, along with the rest of the ANSI escape sequences they start
+    Strip,
+    /// Write them as they are, so escape sequences take effect
+    Raw,
+}
+
+impl ControlChars {
+    /// Make 'line' safe to write to a terminal. Tabs are left alone
+    pub(crate) fn apply(self, line: String) -> String {
+        let is_control = |c: char| c.is_control() && c != '\t';
+        if !line.contains(is_control) {
+            return line;
+        }
+        match self {
+            Self::Raw => line,
+            Self::Strip => strip_ansi(Cow::Owned(line))
+                .chars()
+                .filter(|&c| !is_control(c))
+                .collect(),
+            Self::Escape => {
+                let mut escaped = String::with_capacity(line.len());
+                for c in line.chars() {
+                    if !is_control(c) {
+                        escaped.push(c);
+                        continue;
+                    }
+                    let code = c as u32;
+                    if code >= 0x80 {
+                        escaped.push_str("M-");
+                    }
+                    match code & 0x7f {
+                        0x7f => escaped.push_str("^?"),
+                        low => {
+                            escaped.push('^');
+                            escaped.push(char::from(low as u8 + 0x40));
+                        }
+                    }
+                }
+                escaped
+            }
+        }
+    }
+}
+
+/// A sed-like 's/REGEX/REPLACEMENT/FLAGS' substitution, run on lines before comparing them.
+/// Any character can stand in for '/'. The replacement can refer to groups as '\1' and to the
+/// whole match as '&'. The 'g' flag replaces every match instead of the first, 'i' ignores case
+#[derive(Debug, Clone)]
+pub struct Substitution {
+    regex: Regex,
+    replacement: String,
+    global: bool,
+}
+
+/// Split 'text' at each 'delimiter' that isn't escaped with a backslash.
+/// Other escapes are kept for the regex and the replacement to handle
+fn split_unescaped(text: &str, delimiter: char) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        let part = parts.last_mut().expect("there is always a part");
+        match c {
+            '\\' => match chars.next() {
+                Some(next) if next == delimiter => part.push(next),
+                Some(next) => {
+                    part.push('\\');
+                    part.push(next);
+                }
+                None => part.push('\\'),
+            },
+            c if c == delimiter => parts.push(String::new()),
+            c => part.push(c),
+        }
+    }
+    parts
+}
+
+/// Turn a sed replacement into the regex crate's syntax
+fn regex_replacement(sed: &str) -> String {
+    let mut replacement = String::with_capacity(sed.len());
+    let mut chars = sed.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(digit @ '0'..='9') => replacement.push_str(&format!("${{{digit}}}")),
+                Some('$') => replacement.push_str("$$"),
+                Some(other) => replacement.push(other),
+                None => replacement.push('\\'),
+            },
+            '&' => replacement.push_str("${0}"),
+            '$' => replacement.push_str("$$"),
+            c => replacement.push(c),
+        }
+    }
+    replacement
+}
+
+impl FromStr for Substitution {
+    type Err = DifferentError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut chars = s.chars();
+        let delimiter = match (chars.next(), chars.next()) {
+            (Some('s'), Some(delimiter)) if !delimiter.is_alphanumeric() && delimiter != '\\' => {
+                delimiter
+            }
+            _ => {
+                return Err(DifferentError::InvalidSubstitution(format!(
+                    "Expected a substitution like 's/REGEX/REPLACEMENT/', not '{s}'"
+                )));
+            }
+        };
+        let [pattern, replacement, flags] = &split_unescaped(chars.as_str(), delimiter)[..] else {
+            return Err(DifferentError::InvalidSubstitution(format!(
+                "Expected a substitution like 's{delimiter}REGEX{delimiter}REPLACEMENT{delimiter}', not '{s}'"
+            )));
+        };
+        let mut global = false;
+        let mut builder = RegexBuilder::new(pattern);
+        for flag in flags.chars() {
+            match flag {
+                'g' => global = true,
+                'i' => {
+                    builder.case_insensitive(true);
+                }
+                _ => {
+                    return Err(DifferentError::InvalidSubstitution(format!(
+                        "Unknown flag '{flag}' in '{s}', expected 'g' or 'i'"
+                    )));
+                }
+            }
+        }
+        Ok(Self {
+            regex: builder.build()?,
+            replacement: regex_replacement(replacement),
+            global,
+        })
+    }
+}
+
+impl Substitution {
+    pub(crate) fn apply<'a>(&self, line: Cow<'a, str>) -> Cow<'a, str> {
+        let limit = if self.global { 0 } else { 1 };
+        let replaced = match self.regex.replacen(&line, limit, self.replacement.as_str()) {
+            Cow::Owned(replaced) => Some(replaced),
+            Cow::Borrowed(_) => None,
+        };
+        match replaced {
+            Some(replaced) => Cow::Owned(replaced),
+            None => line,
+        }
+    }
+}
+
+type NormalizeFn = dyn Fn(&str) -> Cow<'_, str> + Send + Sync;
+
+/// A function from library users that rewrites lines before they are compared
+#[derive(Clone)]
+pub(crate) struct Normalizer(pub(crate) Arc<NormalizeFn>);
+
+impl fmt::Debug for Normalizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Normalizer")
+    }
+}
+
+impl Normalizer {
+    pub(crate) fn apply<'a>(&self, line: Cow<'a, str>) -> Cow<'a, str> {
+        let normalized = match (self.0)(&line) {
+            Cow::Owned(normalized) => Some(normalized),
+            Cow::Borrowed(normalized) if normalized != line => Some(normalized.to_string()),
+            Cow::Borrowed(_) => None,
+        };
+        match normalized {
+            Some(normalized) => Cow::Owned(normalized),
+            None => line,
+        }
+    }
+}
+
+/// Length of the ANSI escape sequence at the start of 's', which starts with ESC
+fn escape_sequence_len(s: &str) -> usize {
+    let rest = &s[1..];
+    let len = match rest.chars().next() {
+        // CSI, like colors: parameters up to a final byte from '@' to '~'
+        Some('[') => rest[1..]
+            .bytes()
+            .position(|b| (0x40..=0x7e).contains(&b))
+            .map_or(rest.len(), |i| i + 2),
+        // OSC, like hyperlinks: up to BEL or ESC \
+        Some(']') => match (rest.find('\x07'), rest.find("\x1b\\")) {
+            (Some(bel), Some(st)) if st < bel => st + 2,
+            (Some(bel), _) => bel + 1,
+            (None, Some(st)) => st + 2,
+            (None, None) => rest.len(),
+        },
+        Some(c) => c.len_utf8(),
+        None => 0,
+    };
+    1 + len
+}
+
+/// Remove ANSI escape sequences, like colors, from the line
+pub(crate) fn strip_ansi(line: Cow<'_, str>) -> Cow<'_, str> {
+    if !line.contains('\x1b') {
+        return line;
+    }
+    let mut stripped = String::with_capacity(line.len());
+    let mut rest = line.as_ref();
+    while let Some(start) = rest.find('\x1b') {
+        stripped.push_str(&rest[..start]);
+        rest = &rest[start..];
+        rest = &rest[escape_sequence_len(rest)..];
+    }
+    stripped.push_str(rest);
+    Cow::Owned(stripped)
+}
+
+/// Drop every whitespace character, like `diff -w`
+pub(crate) fn strip_whitespace(line: Cow<'_, str>) -> Cow<'_, str> {
+    if line.contains(char::is_whitespace) {
+        Cow::Owned(line.chars().filter(|c| !c.is_whitespace()).collect())
+    } else {
+        line
+    }
+}
+
+/// Collapse runs of whitespace to a single space and drop trailing whitespace, like `diff -b`
+pub(crate) fn squeeze_whitespace(line: Cow<'_, str>) -> Cow<'_, str> {
+    let mut squeezed = String::with_capacity(line.len());
+    let mut in_space = false;
+    for c in line.trim_end().chars() {
+        if c.is_whitespace() {
+            if !in_space {
+                squeezed.push(' ');
+            }
+            in_space = true;
+        } else {
+            squeezed.push(c);
+            in_space = false;
+        }
+    }
+    if squeezed == line {
+        line
+    } else {
+        Cow::Owned(squeezed)
+    }
+}
+
+/// Lowercase the line, like `diff -i`
+pub(crate) fn fold_case(line: Cow<'_, str>) -> Cow<'_, str> {
+    if line.chars().any(char::is_uppercase) {
+        Cow::Owned(line.to_lowercase())
+    } else {
+        line
+    }
+}
+
+/// Replace tabs with '→', trailing spaces with '·' and non-breaking spaces with '⍽'
+pub(crate) fn visible_whitespace(line: &str) -> String {
+    let trailing = line.len() - line.trim_end_matches(' ').len();
+    let (text, spaces) = line.split_at(line.len() - trailing);
+    let mut visible: String = text
+        .chars()
+        .map(|c| match c {
+            '\t' => '→',
+            '\u{a0}' => '⍽',
+            c => c,
+        })
+        .collect();
+    visible.extend(spaces.chars().map(|_| '·'));
+    visible
+}
+
+/// Diff the lines of 'left' and 'right' by comparing keys derived from each line,
+/// while keeping the original lines in the result
+pub(crate) fn diff_by_key<'a, F>(
+    left: &'a str,
+    right: &'a str,
+    key: F,
+    algorithm: Algorithm,
+) -> Vec<diff::Result<&'a str>>
+where
+    F: Fn(&'a str) -> Cow<'a, str>,
+{
+    let left_lines = split_lines(left);
+    let right_lines = split_lines(right);
+    let left_keys: Vec<_> = left_lines.iter().map(|line| key(line)).collect();
+    let right_keys: Vec<_> = right_lines.iter().map(|line| key(line)).collect();
+
+    algorithm::diff(&left_keys, &right_keys, algorithm)
+        .into_iter()
+        .map(|edit| match edit {
+            Edit::Delete(i) => diff::Result::Left(left_lines[i]),
+            Edit::Equal(i, j) => diff::Result::Both(left_lines[i], right_lines[j]),
+            Edit::Insert(j) => diff::Result::Right(right_lines[j]),
+        })
+        .collect()
+}
@@ -0,0 +1,170 @@
+use crate::algorithm::{self, Algorithm, Edit};
+use std::fmt;
+use std::hash::Hash;
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpTag {
+    Equal,
+    Delete,
+    Insert,
+    Replace,
+}
+
+impl fmt::Display for OpTag {
+    /// The tag as difflib names it, e.g. "replace"
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Equal => "equal",
+            Self::Delete => "delete",
+            Self::Insert => "insert",
+            Self::Replace => "replace",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A range-level edit operation, in the spirit of difflib's opcodes.
+/// 'left' and 'right' are line index ranges into the respective inputs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Op {
+    pub tag: OpTag,
+    pub left: Range<usize>,
+    pub right: Range<usize>,
+}
+
+impl Op {
+    pub fn new(tag: OpTag, left: Range<usize>, right: Range<usize>) -> Self {
+        Self { tag, left, right }
+    }
+}
+
+/// A group of ops surrounded by at most 'context' lines of unchanged content
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    pub ops: Vec<Op>,
+}
+
+impl Hunk {
+    pub fn left_range(&self) -> Range<usize> {
+        match (self.ops.first(), self.ops.last()) {
+            (Some(first), Some(last)) => first.left.start..last.left.end,
+            _ => 0..0,
+        }
+    }
+
+    pub fn right_range(&self) -> Range<usize> {
+        match (self.ops.first(), self.ops.last()) {
+            (Some(first), Some(last)) => first.right.start..last.right.end,
+            _ => 0..0,
+        }
+    }
+}
+
+/// The ops turning 'left' into 'right', like difflib's `SequenceMatcher(None, left, right).get_opcodes()`.
+/// Works on any sequence, such as lines, words or characters.
+/// Unlike [`crate::Diff::ops`], equal inputs give a single Equal op
+pub fn opcodes<T: Hash + Eq>(left: &[T], right: &[T]) -> Vec<Op> {
+    let results: Vec<diff::Result<()>> = algorithm::diff(left, right, Algorithm::Myers)
+        .into_iter()
+        .map(|edit| match edit {
+            Edit::Equal(..) => diff::Result::Both((), ()),
+            Edit::Delete(_) => diff::Result::Left(()),
+            Edit::Insert(_) => diff::Result::Right(()),
+        })
+        .collect();
+    ops_from_results(&results)
+}
+
+/// Turn per-line diff results into a flat list of range ops.
+/// Adjacent removals and additions are merged into a single Replace
+pub(crate) fn ops_from_results<T>(results: &[diff::Result<T>]) -> Vec<Op> {
+    let mut ops = Vec::new();
+    let mut i = 0;
+    let mut j = 0;
+    let mut pos = 0;
+
+    while pos < results.len() {
+        let (start_i, start_j) = (i, j);
+        if let diff::Result::Both(_, _) = results[pos] {
+            while let Some(diff::Result::Both(_, _)) = results.get(pos) {
+                i += 1;
+                j += 1;
+                pos += 1;
+            }
+            ops.push(Op::new(OpTag::Equal, start_i..i, start_j..j));
+            continue;
+        }
+
+        loop {
+            match results.get(pos) {
+                Some(diff::Result::Left(_)) => i += 1,
+                Some(diff::Result::Right(_)) => j += 1,
+                _ => break,
+            }
+            pos += 1;
+        }
+        let tag = match (i > start_i, j > start_j) {
+            (true, true) => OpTag::Replace,
+            (true, false) => OpTag::Delete,
+            _ => OpTag::Insert,
+        };
+        ops.push(Op::new(tag, start_i..i, start_j..j));
+    }
+
+    ops
+}
+
+/// Group a flat op list into hunks with at most 'context' unchanged lines around each change,
+/// mirroring difflib's get_grouped_opcodes. Returns no hunks if nothing changed
+pub fn group_ops(ops: &[Op], context: usize) -> Vec<Hunk> {
+    let mut ops = ops.to_vec();
+    if let Some(first) = ops.first_mut()
+        && first.tag == OpTag::Equal
+    {
+        first.left.start = std::cmp::max(first.left.start, first.left.end.saturating_sub(context));
+        first.right.start =
+            std::cmp::max(first.right.start, first.right.end.saturating_sub(context));
+    }
+    if let Some(last) = ops.last_mut()
+        && last.tag == OpTag::Equal
+    {
+        last.left.end = std::cmp::min(last.left.end, last.left.start + context);
+        last.right.end = std::cmp::min(last.right.end, last.right.start + context);
+    }
+
+    let mut hunks = Vec::new();
+    let mut group = Vec::new();
+    for op in ops {
+        let Op {
+            tag,
+            mut left,
+            mut right,
+        } = op;
+        // End the current group whenever a run of unchanged lines is too long to be shared context
+        if tag == OpTag::Equal && left.len() > context * 2 {
+            group.push(Op::new(
+                tag,
+                left.start..std::cmp::min(left.end, left.start + context),
+                right.start..std::cmp::min(right.end, right.start + context),
+            ));
+            hunks.push(group);
+            group = Vec::new();
+            left.start = std::cmp::max(left.start, left.end - context);
+            right.start = std::cmp::max(right.start, right.end - context);
+        }
+        group.push(Op::new(tag, left, right));
+    }
+    hunks.push(group);
+
+    hunks
+        .into_iter()
+        .map(|ops| {
+            ops.into_iter()
+                .filter(|op| op.tag != OpTag::Equal || !op.left.is_empty())
+                .collect::<Vec<_>>()
+        })
+        .filter(|ops| ops.iter().any(|op| op.tag != OpTag::Equal))
+        .map(|ops| Hunk { ops })
+        .collect()
+}
@@ -0,0 +1,56 @@
+use crate::{Diff, DiffHunk};
+use serde::Serialize;
+
+/// How the CLI renders a diff
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum OutputFormat {
+    /// Colored, human readable lines
+    #[default]
+    Text,
+    /// Hunks, line numbers and change kinds as a JSON document
+    Json,
+    /// A self-contained HTML page
+    Html,
+    /// An RFC 6902 JSON Patch that turns the left document into the right one.
+    /// Only for JSON, YAML and TOML inputs
+    JsonPatch,
+    /// A fenced diff block to paste into Markdown, like GitHub comments
+    Markdown,
+    /// A unified diff like `diff -u` prints, headed by the labels or file names
+    Unified,
+}
+
+#[derive(Serialize)]
+struct JsonReport<'d, T> {
+    same: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    left: Option<&'d str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    right: Option<&'d str>,
+    hunks: Vec<DiffHunk<'d, T>>,
+}
+
+impl<T: Serialize> Diff<'_, T> {
+    /// The diff as pretty printed JSON, for tools that don't want to parse the text output.
+    /// Hunks respect the context setting and include every line when it is unset
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        let report = match self {
+            Self::Same => JsonReport {
+                same: true,
+                left: None,
+                right: None,
+                hunks: Vec::new(),
+            },
+            Self::Diff { settings, .. } => JsonReport {
+                same: false,
+                left: settings.left_name.as_deref(),
+                right: settings.right_name.as_deref(),
+                hunks: self
+                    .hunks(settings.context().unwrap_or(usize::MAX))
+                    .collect(),
+            },
+        };
+        serde_json::to_string_pretty(&report)
+    }
+}
@@ -0,0 +1,354 @@
+use anyhow::Result;
+use different::{
+    DiffSettings, DiffSettingsBuilder, LineKind, Segment, Theme, WordDiff, inline_diff_with,
+    line_diff,
+};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::Paragraph;
+use ratatui::{DefaultTerminal, Frame};
+
+const HELP: &str = "n/p hunk  / search  w whitespace  d word diff  q quit";
+
+/// A line of the diff as shown in the pager
+struct Row {
+    kind: LineKind,
+    left_lineno: Option<usize>,
+    right_lineno: Option<usize>,
+    segments: Vec<Segment>,
+}
+
+impl Row {
+    fn text(&self) -> String {
+        self.segments.iter().map(|s| s.text.as_str()).collect()
+    }
+}
+
+/// Scrollable view of a line diff that can be re-diffed with other settings
+struct Pager<'a> {
+    left: &'a str,
+    right: &'a str,
+    title: String,
+    settings: DiffSettings,
+    ignore_space: bool,
+    word_diff: bool,
+    rows: Vec<Row>,
+    /// Indices of the rows that start a run of changes
+    hunks: Vec<usize>,
+    /// First row on screen
+    top: usize,
+    /// Rows that fit on screen, as of the last draw
+    height: usize,
+    /// The search being typed, if any
+    input: Option<String>,
+    search: String,
+    message: Option<String>,
+}
+
+impl<'a> Pager<'a> {
+    fn new(left: &'a str, right: &'a str, title: String, settings: DiffSettings) -> Self {
+        let mut pager = Self {
+            left,
+            right,
+            title,
+            ignore_space: settings.ignore_all_space(),
+            settings,
+            word_diff: true,
+            rows: Vec::new(),
+            hunks: Vec::new(),
+            top: 0,
+            height: 0,
+            input: None,
+            search: String::new(),
+            message: None,
+        };
+        pager.rediff();
+        pager
+    }
+
+    /// Diff the inputs again after a toggle
+    fn rediff(&mut self) {
+        let settings = DiffSettingsBuilder::from(self.settings.clone())
+            .ignore_all_space(self.ignore_space)
+            .build();
+        let diff = line_diff(self.left, self.right, &settings);
+        self.rows = diff
+            .lines()
+            .map(|line| Row {
+                kind: line.kind,
+                left_lineno: line.left_lineno,
+                right_lineno: line.right_lineno,
+                segments: vec![Segment {
+                    text: settings
+                        .display_line(line.content.to_string(), line.kind != LineKind::Unchanged),
+                    changed: false,
+                }],
+            })
+            .collect();
+
+        self.hunks.clear();
+        let mut i = 0;
+        while i < self.rows.len() {
+            if self.rows[i].kind == LineKind::Unchanged {
+                i += 1;
+                continue;
+            }
+            self.hunks.push(i);
+            let removed = i;
+            while i < self.rows.len() && self.rows[i].kind == LineKind::Removed {
+                i += 1;
+            }
+            let added = i;
+            while i < self.rows.len() && self.rows[i].kind == LineKind::Added {
+                i += 1;
+            }
+            // Pair up the removed and added lines of a change to highlight what differs within them
+            if self.word_diff {
+                for (l, r) in (removed..added).zip(added..i) {
+                    let tokenizer = self.settings.tokenizer().unwrap_or(&WordDiff::Word);
+                    let (left, right) =
+                        inline_diff_with(&self.rows[l].text(), &self.rows[r].text(), tokenizer);
+                    self.rows[l].segments = left;
+                    self.rows[r].segments = right;
+                }
+            }
+        }
+        self.top = self.top.min(self.last_top());
+    }
+
+    fn last_top(&self) -> usize {
+        self.rows.len().saturating_sub(self.height.max(1))
+    }
+
+    fn scroll_to(&mut self, row: usize) {
+        self.top = row.min(self.last_top());
+    }
+
+    fn scroll_by(&mut self, rows: isize) {
+        self.scroll_to(self.top.saturating_add_signed(rows));
+    }
+
+    fn next_hunk(&mut self) {
+        match self.hunks.iter().find(|&&hunk| hunk > self.top) {
+            Some(&hunk) => self.scroll_to(hunk),
+            None => self.message = Some("No more changes".to_string()),
+        }
+    }
+
+    fn previous_hunk(&mut self) {
+        match self.hunks.iter().rev().find(|&&hunk| hunk < self.top) {
+            Some(&hunk) => self.scroll_to(hunk),
+            None => self.message = Some("No earlier changes".to_string()),
+        }
+    }
+
+    /// Scroll to the next row containing the search, wrapping around at the end
+    fn find_next(&mut self) {
+        if self.search.is_empty() {
+            return;
+        }
+        let count = self.rows.len();
+        let found = (1..=count)
+            .map(|offset| (self.top + offset) % count)
+            .find(|&i| self.rows[i].text().contains(&self.search));
+        match found {
+            Some(i) => self.scroll_to(i),
+            None => self.message = Some(format!("Pattern not found: {}", self.search)),
+        }
+    }
+
+    /// Handle a key press. Returns false to quit
+    fn key(&mut self, key: KeyEvent) -> bool {
+        self.message = None;
+        if let Some(input) = &mut self.input {
+            match key.code {
+                KeyCode::Enter => {
+                    // An empty search repeats the last one
+                    if !input.is_empty() {
+                        self.search = std::mem::take(input);
+                    }
+                    self.input = None;
+                    self.find_next();
+                }
+                KeyCode::Esc => self.input = None,
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Char(c) => input.push(c),
+                _ => {}
+            }
+            return true;
+        }
+
+        let page = self.height.max(1) as isize;
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Char('j') | KeyCode::Down | KeyCode::Enter => self.scroll_by(1),
+            KeyCode::Char('k') | KeyCode::Up => self.scroll_by(-1),
+            KeyCode::Char(' ') | KeyCode::Char('f') | KeyCode::PageDown => self.scroll_by(page),
+            KeyCode::Char('b') | KeyCode::PageUp => self.scroll_by(-page),
+            KeyCode::Char('g') | KeyCode::Home => self.scroll_to(0),
+            KeyCode::Char('G') | KeyCode::End => self.scroll_to(self.rows.len()),
+            KeyCode::Char('n') => self.next_hunk(),
+            KeyCode::Char('p') => self.previous_hunk(),
+            KeyCode::Char('/') => self.input = Some(String::new()),
+            KeyCode::Char('w') => {
+                self.ignore_space = !self.ignore_space;
+                self.rediff();
+            }
+            KeyCode::Char('d') => {
+                self.word_diff = !self.word_diff;
+                self.rediff();
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [header, body, footer] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        self.height = usize::from(body.height);
+        self.top = self.top.min(self.last_top());
+
+        let title = Line::from(self.title.as_str());
+        let title = match self.settings.theme().header {
+            Some(style) => title.style(tui_style(style)),
+            None => title.bold(),
+        };
+        frame.render_widget(title, header);
+
+        let theme = self.settings.theme();
+        let width = self.rows.len().max(1).to_string().len();
+        let lines: Vec<Line> = self
+            .rows
+            .iter()
+            .skip(self.top)
+            .take(self.height)
+            .map(|row| row_line(row, width, &theme))
+            .collect();
+        frame.render_widget(Paragraph::new(lines), body);
+
+        let status = match (&self.input, &self.message) {
+            (Some(input), _) => format!("/{input}"),
+            (None, Some(message)) => message.clone(),
+            (None, None) => {
+                // Changes up to the bottom of the screen count as seen
+                let bottom = self.top + self.height;
+                let hunk = self.hunks.iter().filter(|&&hunk| hunk < bottom).count();
+                let on_off = |on| if on { "on" } else { "off" };
+                format!(
+                    "line {}/{}  change {hunk}/{}  whitespace {}  word diff {}  |  {HELP}",
+                    self.top + 1,
+                    self.rows.len(),
+                    self.hunks.len(),
+                    on_off(!self.ignore_space),
+                    on_off(self.word_diff),
+                )
+            }
+        };
+        frame.render_widget(Line::from(status).reversed(), footer);
+    }
+}
+
+/// The terminal style for a theme style
+fn tui_style(style: different::Style) -> Style {
+    let mut tui = Style::new();
+    if let Some(fg) = style.fg {
+        tui = tui.fg(tui_color(fg));
+    }
+    if let Some(bg) = style.bg {
+        tui = tui.bg(tui_color(bg));
+    }
+    for (enabled, modifier) in [
+        (style.bold, Modifier::BOLD),
+        (style.dimmed, Modifier::DIM),
+        (style.italic, Modifier::ITALIC),
+        (style.underline, Modifier::UNDERLINED),
+    ] {
+        if enabled {
+            tui = tui.add_modifier(modifier);
+        }
+    }
+    tui
+}
+
+fn tui_color(color: colored::Color) -> Color {
+    use colored::Color as C;
+    match color {
+        C::Black => Color::Black,
+        C::Red => Color::Red,
+        C::Green => Color::Green,
+        C::Yellow => Color::Yellow,
+        C::Blue => Color::Blue,
+        C::Magenta => Color::Magenta,
+        C::Cyan => Color::Cyan,
+        C::White => Color::Gray,
+        C::BrightBlack => Color::DarkGray,
+        C::BrightRed => Color::LightRed,
+        C::BrightGreen => Color::LightGreen,
+        C::BrightYellow => Color::LightYellow,
+        C::BrightBlue => Color::LightBlue,
+        C::BrightMagenta => Color::LightMagenta,
+        C::BrightCyan => Color::LightCyan,
+        C::BrightWhite => Color::White,
+        C::TrueColor { r, g, b } => Color::Rgb(r, g, b),
+    }
+}
+
+fn row_line<'r>(row: &'r Row, width: usize, theme: &Theme) -> Line<'r> {
+    let number = |n: Option<usize>| n.map(|n| n.to_string()).unwrap_or_default();
+    let (marker, style, changed) = match row.kind {
+        LineKind::Removed => ('-', theme.left, theme.left_changed),
+        LineKind::Added => ('+', theme.right, theme.right_changed),
+        LineKind::Unchanged => ('|', theme.unchanged, None),
+    };
+    let numbers = format!(
+        "{:>width$} {:>width$}",
+        number(row.left_lineno),
+        number(row.right_lineno),
+    );
+    let mut spans = vec![
+        Span::styled(numbers, tui_style(theme.line_number.unwrap_or(style))),
+        Span::raw(" "),
+        Span::styled(marker.to_string(), tui_style(theme.marker.unwrap_or(style))),
+        Span::raw(" "),
+    ];
+    let style = tui_style(style);
+    let changed = match changed {
+        Some(changed) => tui_style(changed),
+        None => style.add_modifier(Modifier::REVERSED),
+    };
+    spans.extend(row.segments.iter().map(|segment| {
+        let style = if segment.changed { changed } else { style };
+        Span::styled(segment.text.as_str(), style)
+    }));
+    Line::from(spans)
+}
+
+fn run(terminal: &mut DefaultTerminal, pager: &mut Pager) -> Result<()> {
+    loop {
+        terminal.draw(|frame| pager.draw(frame))?;
+        if let Event::Key(key) = event::read()?
+            && key.kind == KeyEventKind::Press
+            && !pager.key(key)
+        {
+            return Ok(());
+        }
+    }
+}
+
+/// Browse the line diff of 'left' and 'right' in the terminal until the user quits
+pub fn page(left: &str, right: &str, title: String, settings: DiffSettings) -> Result<()> {
+    let mut pager = Pager::new(left, right, title, settings);
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal, &mut pager);
+    ratatui::restore();
+    result
+}
@@ -0,0 +1,91 @@
+use colored::{ColoredString, Styles};
+use std::env;
+use std::fmt::Display;
+use std::io::{self, IsTerminal};
+
+/// When to emit color escape codes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum ColorMode {
+    /// Color if stdout is a terminal, unless NO_COLOR is set. CLICOLOR_FORCE colors regardless
+    #[default]
+    Auto,
+    /// Always color
+    Always,
+    /// Never color
+    Never,
+}
+
+impl ColorMode {
+    pub(crate) fn enabled(self) -> bool {
+        match self {
+            Self::Always => true,
+            Self::Never => false,
+            // See https://no-color.org and https://bixense.com/clicolors
+            Self::Auto => {
+                let var = |name| env::var(name).ok().filter(|value| !value.is_empty());
+                if var("NO_COLOR").is_some() {
+                    false
+                } else if var("CLICOLOR_FORCE").is_some_and(|value| value != "0") {
+                    true
+                } else {
+                    var("CLICOLOR").is_none_or(|value| value != "0") && io::stdout().is_terminal()
+                }
+            }
+        }
+    }
+}
+
+/// Same order colored uses, so output is byte for byte what it would have printed
+const STYLE_CODES: &[(Styles, &str)] = &[
+    (Styles::Bold, "1"),
+    (Styles::Dimmed, "2"),
+    (Styles::Underline, "4"),
+    (Styles::Reversed, "7"),
+    (Styles::Italic, "3"),
+    (Styles::Blink, "5"),
+    (Styles::Hidden, "8"),
+    (Styles::Strikethrough, "9"),
+];
+
+/// A styled string that is only colored if asked to.
+/// `ColoredString`'s own Display consults colored's process wide override instead
+pub(crate) struct Painted {
+    text: ColoredString,
+    enabled: bool,
+}
+
+pub(crate) trait Paint {
+    fn paint(self, enabled: bool) -> Painted;
+}
+
+impl Paint for ColoredString {
+    fn paint(self, enabled: bool) -> Painted {
+        Painted {
+            text: self,
+            enabled,
+        }
+    }
+}
+
+impl Display for Painted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = &self.text;
+        if !self.enabled || text.is_plain() {
+            return write!(f, "{}", text.input);
+        }
+
+        let mut codes: Vec<String> = STYLE_CODES
+            .iter()
+            .filter(|(style, _)| text.style.contains(*style))
+            .map(|(_, code)| code.to_string())
+            .collect();
+        if let Some(bg) = text.bgcolor {
+            codes.push(bg.to_bg_str().into_owned());
+        }
+        if let Some(fg) = text.fgcolor {
+            codes.push(fg.to_fg_str().into_owned());
+        }
+        write!(f, "\x1B[{}m{}\x1B[0m", codes.join(";"), text.input)
+    }
+}
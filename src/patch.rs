@@ -0,0 +1,292 @@
+use crate::{DifferentError, Result};
+
+const NO_NEWLINE_MARKER: &str = "\\ No newline at end of file";
+const DEV_NULL: &str = "/dev/null";
+
+/// A line of a patch hunk. The flag is set if the line has no trailing newline
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchLine {
+    Context(String, bool),
+    Remove(String, bool),
+    Add(String, bool),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatchHunk {
+    /// 1-based first line in the old file, 0 if the hunk has no old lines
+    pub old_start: usize,
+    pub new_start: usize,
+    pub lines: Vec<PatchLine>,
+}
+
+impl PatchHunk {
+    /// Lines the hunk expects to find, with their missing newline flags
+    fn old_lines(&self) -> Vec<(&str, bool)> {
+        self.lines
+            .iter()
+            .filter_map(|line| match line {
+                PatchLine::Context(l, n) | PatchLine::Remove(l, n) => Some((l.as_str(), *n)),
+                PatchLine::Add(..) => None,
+            })
+            .collect()
+    }
+
+    /// Lines the hunk leaves in their place
+    fn new_lines(&self) -> Vec<(&str, bool)> {
+        self.lines
+            .iter()
+            .filter_map(|line| match line {
+                PatchLine::Context(l, n) | PatchLine::Add(l, n) => Some((l.as_str(), *n)),
+                PatchLine::Remove(..) => None,
+            })
+            .collect()
+    }
+}
+
+/// The changes to a single file, as introduced by a `---`/`+++` header pair
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilePatch {
+    pub old_path: String,
+    pub new_path: String,
+    pub hunks: Vec<PatchHunk>,
+}
+
+impl FilePatch {
+    /// The file is created by the patch
+    pub fn creates(&self) -> bool {
+        self.old_path == DEV_NULL
+    }
+
+    /// The file is removed by the patch
+    pub fn deletes(&self) -> bool {
+        self.new_path == DEV_NULL
+    }
+
+    /// The patch that undoes this one
+    pub fn reverse(&self) -> Self {
+        let hunks = self
+            .hunks
+            .iter()
+            .map(|hunk| PatchHunk {
+                old_start: hunk.new_start,
+                new_start: hunk.old_start,
+                lines: hunk
+                    .lines
+                    .iter()
+                    .map(|line| match line {
+                        PatchLine::Context(l, n) => PatchLine::Context(l.clone(), *n),
+                        PatchLine::Remove(l, n) => PatchLine::Add(l.clone(), *n),
+                        PatchLine::Add(l, n) => PatchLine::Remove(l.clone(), *n),
+                    })
+                    .collect(),
+            })
+            .collect();
+        Self {
+            old_path: self.new_path.clone(),
+            new_path: self.old_path.clone(),
+            hunks,
+        }
+    }
+
+    /// Apply the hunks to 'text'. Hunks are searched for near their stated position,
+    /// so a patch still applies if unrelated lines were added or removed above it
+    pub fn apply(&self, text: &str) -> Result<String> {
+        let mut lines = file_lines(text);
+        // How far the file has shifted from the hunk headers so far
+        let mut offset: isize = 0;
+        for (n, hunk) in self.hunks.iter().enumerate() {
+            let old = hunk.old_lines();
+            // A hunk without old lines inserts after line 'old_start' rather than at it
+            let index = if old.is_empty() {
+                hunk.old_start
+            } else {
+                hunk.old_start.max(1) - 1
+            };
+            let expected = index.saturating_add_signed(offset);
+            let Some(start) = find_hunk(&lines, &old, expected) else {
+                return Err(DifferentError::Mismatch(format!(
+                    "Hunk {} (at line {}) does not apply to {}",
+                    n + 1,
+                    hunk.old_start,
+                    self.old_path
+                )));
+            };
+            let new: Vec<(String, bool)> = hunk
+                .new_lines()
+                .into_iter()
+                .map(|(l, n)| (l.to_string(), n))
+                .collect();
+            offset += new.len() as isize - old.len() as isize;
+            lines.splice(start..start + old.len(), new);
+        }
+
+        let mut out = String::new();
+        for (line, no_newline) in lines {
+            out.push_str(&line);
+            if !no_newline {
+                out.push('\n');
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// A parsed unified diff, possibly touching several files
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Patch {
+    pub files: Vec<FilePatch>,
+}
+
+impl Patch {
+    /// Parse a unified diff, like the ones `diff -u`, `git diff` and [`crate::unified_diff`] produce.
+    /// Anything outside of file headers and hunks is ignored
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut files = Vec::new();
+        let mut lines = split_lines(text).enumerate().peekable();
+        while let Some((_, line)) = lines.next() {
+            let Some(old_path) = line.strip_prefix("--- ") else {
+                continue;
+            };
+            let Some((_, new_line)) = lines.next_if(|(_, l)| l.starts_with("+++ ")) else {
+                continue;
+            };
+            let mut file = FilePatch {
+                old_path: header_path(old_path),
+                new_path: header_path(&new_line[4..]),
+                hunks: Vec::new(),
+            };
+
+            while let Some((i, header)) = lines.next_if(|(_, l)| l.starts_with("@@ ")) {
+                let (old_start, old_len, new_start, new_len) = parse_hunk_header(header)
+                    .ok_or_else(|| {
+                        DifferentError::Invalid(format!("Invalid hunk header on line {}", i + 1))
+                    })?;
+                let mut hunk = PatchHunk {
+                    old_start,
+                    new_start,
+                    lines: Vec::new(),
+                };
+                let (mut old_left, mut new_left) = (old_len, new_len);
+                while old_left > 0 || new_left > 0 {
+                    let Some((i, line)) = lines.next() else {
+                        return Err(DifferentError::Invalid(
+                            "Patch ended in the middle of a hunk".to_string(),
+                        ));
+                    };
+                    // Every marker is a single ASCII character
+                    let content = line.get(1..).unwrap_or_default().to_string();
+                    let line = match line.chars().next() {
+                        // Some tools strip the space from empty context lines
+                        Some(' ') | None => PatchLine::Context(content, false),
+                        Some('-') => PatchLine::Remove(content, false),
+                        Some('+') => PatchLine::Add(content, false),
+                        Some('\\') => continue,
+                        _ => {
+                            return Err(DifferentError::Invalid(format!(
+                                "Unexpected line {} in hunk: '{line}'",
+                                i + 1
+                            )));
+                        }
+                    };
+                    match line {
+                        PatchLine::Context(..) => {
+                            old_left = old_left.saturating_sub(1);
+                            new_left = new_left.saturating_sub(1);
+                        }
+                        PatchLine::Remove(..) => old_left = old_left.saturating_sub(1),
+                        PatchLine::Add(..) => new_left = new_left.saturating_sub(1),
+                    }
+                    hunk.lines.push(line);
+                    if lines
+                        .next_if(|(_, l)| l.trim_end_matches('\r') == NO_NEWLINE_MARKER)
+                        .is_some()
+                        && let Some(
+                            PatchLine::Context(_, n)
+                            | PatchLine::Remove(_, n)
+                            | PatchLine::Add(_, n),
+                        ) = hunk.lines.last_mut()
+                    {
+                        *n = true;
+                    }
+                }
+                file.hunks.push(hunk);
+            }
+            files.push(file);
+        }
+
+        if files.is_empty() {
+            return Err(DifferentError::Invalid(
+                "No file changes found in patch".to_string(),
+            ));
+        }
+        Ok(Self { files })
+    }
+
+    /// The patch that undoes this one
+    pub fn reverse(&self) -> Self {
+        Self {
+            files: self.files.iter().map(FilePatch::reverse).collect(),
+        }
+    }
+}
+
+/// The path in a `---`/`+++` header, without a trailing timestamp
+fn header_path(header: &str) -> String {
+    header
+        .split_once('\t')
+        .map_or(header, |(path, _)| path)
+        .trim_end()
+        .to_string()
+}
+
+/// Parse "@@ -a,b +c,d @@", where a missing length means 1
+fn parse_hunk_header(header: &str) -> Option<(usize, usize, usize, usize)> {
+    let ranges = header.strip_prefix("@@ ")?.split(" @@").next()?;
+    let (old, new) = ranges.split_once(' ')?;
+    let range = |r: &str| -> Option<(usize, usize)> {
+        match r.split_once(',') {
+            Some((start, len)) => Some((start.parse().ok()?, len.parse().ok()?)),
+            None => Some((r.parse().ok()?, 1)),
+        }
+    };
+    let (old_start, old_len) = range(old.strip_prefix('-')?)?;
+    let (new_start, new_len) = range(new.strip_prefix('+')?)?;
+    Some((old_start, old_len, new_start, new_len))
+}
+
+/// Lines of 'text' split on '\n' alone, so CRLF lines keep their '\r' and are written back as they were
+fn split_lines(text: &str) -> impl Iterator<Item = &str> {
+    text.split_inclusive('\n')
+        .map(|line| line.strip_suffix('\n').unwrap_or(line))
+}
+
+/// Lines of 'text', flagging the last one if it has no trailing newline
+fn file_lines(text: &str) -> Vec<(String, bool)> {
+    let mut lines: Vec<(String, bool)> =
+        split_lines(text).map(|l| (l.to_string(), false)).collect();
+    if !text.is_empty()
+        && !text.ends_with('\n')
+        && let Some(last) = lines.last_mut()
+    {
+        last.1 = true;
+    }
+    lines
+}
+
+/// Find where 'old' occurs in 'lines', trying 'expected' first and then moving outwards
+fn find_hunk(lines: &[(String, bool)], old: &[(&str, bool)], expected: usize) -> Option<usize> {
+    let matches_at = |start: usize| {
+        start + old.len() <= lines.len()
+            && lines[start..start + old.len()]
+                .iter()
+                .zip(old)
+                .all(|((line, n), (expected, m))| line == expected && n == m)
+    };
+    let last_start = lines.len().checked_sub(old.len())?;
+    let expected = expected.min(last_start);
+    (0..=last_start).find_map(|distance| {
+        let before = expected.checked_sub(distance).filter(|&s| matches_at(s));
+        let after = Some(expected + distance).filter(|&s| s <= last_start && matches_at(s));
+        before.or(after)
+    })
+}
@@ -0,0 +1,146 @@
+use anyhow::{Context, Result, bail};
+use glob::Pattern;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Default)]
+pub struct WalkOptions {
+    /// Skip files and directories whose name or relative path matches any of these
+    pub exclude: Vec<Pattern>,
+    /// How many directory levels to descend. 1 only compares the top level files
+    pub max_depth: Option<usize>,
+}
+
+impl WalkOptions {
+    /// Whether 'relative' is left out by the exclude patterns
+    pub fn excluded(&self, relative: &Path) -> bool {
+        let name = relative
+            .file_name()
+            .map(|n| n.to_string_lossy())
+            .unwrap_or_default();
+        self.exclude
+            .iter()
+            .any(|pattern| pattern.matches(&name) || pattern.matches_path(relative))
+    }
+}
+
+/// A file found while walking a directory tree
+#[derive(Debug, PartialEq, Eq)]
+enum Entry {
+    File,
+    /// A symbolic link and its target. Links aren't followed, so a link back up the tree can't loop
+    Link(PathBuf),
+}
+
+/// Collect the paths of all files and symbolic links under 'dir', relative to 'root'
+fn collect(
+    root: &Path,
+    dir: &Path,
+    depth: usize,
+    options: &WalkOptions,
+    files: &mut BTreeMap<PathBuf, Entry>,
+) -> Result<()> {
+    let entries =
+        fs::read_dir(dir).with_context(|| format!("Unable to read directory {}", dir.display()))?;
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path();
+        let relative = path.strip_prefix(root)?.to_path_buf();
+        if options.excluded(&relative) {
+            continue;
+        }
+
+        let file_type = entry.file_type()?;
+        if file_type.is_symlink() {
+            let target = fs::read_link(&path)
+                .with_context(|| format!("Unable to read link {}", path.display()))?;
+            files.insert(relative, Entry::Link(target));
+        } else if file_type.is_dir() {
+            if options.max_depth.is_none_or(|max| depth < max) {
+                collect(root, &path, depth + 1, options, files)?;
+            }
+        } else {
+            files.insert(relative, Entry::File);
+        }
+    }
+    Ok(())
+}
+
+/// Compare two directory trees. Files only present on one side are reported,
+/// files on both sides are handed to 'diff_file'. Symbolic links are compared by their target.
+/// Returns true if the trees are the same
+pub fn diff_dirs(
+    left: &Path,
+    right: &Path,
+    options: &WalkOptions,
+    mut diff_file: impl FnMut(&Path, &Path) -> Result<bool>,
+) -> Result<bool> {
+    if !left.is_dir() || !right.is_dir() {
+        bail!("Recursive mode needs two directories");
+    }
+
+    let mut left_files = BTreeMap::new();
+    collect(left, left, 1, options, &mut left_files)?;
+    let mut right_files = BTreeMap::new();
+    collect(right, right, 1, options, &mut right_files)?;
+
+    let paths: BTreeSet<&PathBuf> = left_files.keys().chain(right_files.keys()).collect();
+    let mut same = true;
+    for path in paths {
+        let (left_path, right_path) = (left.join(path), right.join(path));
+        match (left_files.get(path), right_files.get(path)) {
+            (Some(Entry::File), Some(Entry::File)) => {
+                same &= diff_file(&left_path, &right_path)?;
+            }
+            (Some(Entry::Link(left_target)), Some(Entry::Link(right_target))) => {
+                if left_target != right_target {
+                    writeln!(
+                        io::stdout(),
+                        "Symbolic links {} -> {} and {} -> {} differ",
+                        left_path.display(),
+                        left_target.display(),
+                        right_path.display(),
+                        right_target.display()
+                    )?;
+                    same = false;
+                }
+            }
+            (Some(left_entry), Some(right_entry)) => {
+                let kind = |entry: &Entry| match entry {
+                    Entry::File => "regular file",
+                    Entry::Link(_) => "symbolic link",
+                };
+                writeln!(
+                    io::stdout(),
+                    "File {} is a {} while file {} is a {}",
+                    left_path.display(),
+                    kind(left_entry),
+                    right_path.display(),
+                    kind(right_entry)
+                )?;
+                same = false;
+            }
+            (Some(_), None) => {
+                writeln!(
+                    io::stdout(),
+                    "Only in {}: {}",
+                    left.display(),
+                    path.display()
+                )?;
+                same = false;
+            }
+            _ => {
+                writeln!(
+                    io::stdout(),
+                    "Only in {}: {}",
+                    right.display(),
+                    path.display()
+                )?;
+                same = false;
+            }
+        }
+    }
+    Ok(same)
+}
@@ -0,0 +1,124 @@
+use crate::html::escape;
+use crate::normalize::strip_ansi;
+use crate::runner::{CheckStatus, Summary};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::fmt::Write;
+
+/// Machine readable formats for check results
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum ReportFormat {
+    /// JUnit XML, as understood by most CI systems
+    Junit,
+    /// Every check with its type, status, duration and reason as JSON
+    Json,
+}
+
+#[derive(Serialize)]
+struct JsonReport<'s> {
+    passed: usize,
+    failed: usize,
+    skipped: usize,
+    errored: usize,
+    checks: Vec<JsonCheck<'s>>,
+}
+
+#[derive(Serialize)]
+struct JsonCheck<'s> {
+    name: &'s str,
+    #[serde(rename = "type")]
+    kind: Option<&'static str>,
+    status: &'static str,
+    /// Seconds
+    duration: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+}
+
+/// Escape text for XML, dropping color codes and the control characters XML can't hold
+fn xml_escape(text: &str) -> String {
+    let plain: String = plain_text(text)
+        .chars()
+        .filter(|&c| matches!(c, '\t' | '\n' | '\r') || !c.is_control())
+        .collect();
+    escape(&plain)
+}
+
+/// 'text' without color codes or hyperlinks, which reasons have when they were printed to a terminal
+fn plain_text(text: &str) -> String {
+    strip_ansi(Cow::Borrowed(text)).into_owned()
+}
+
+impl Summary {
+    /// The results as pretty printed JSON
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        let checks = self
+            .results
+            .iter()
+            .map(|result| {
+                let (status, reason) = match &result.outcome {
+                    Ok(CheckStatus::Success) => ("passed", None),
+                    Ok(CheckStatus::Fail { reason }) => ("failed", Some(plain_text(reason))),
+                    Ok(CheckStatus::Skip { reason }) => ("skipped", Some(plain_text(reason))),
+                    Err(e) => ("errored", Some(plain_text(&e.full_message()))),
+                };
+                JsonCheck {
+                    name: &result.name,
+                    kind: result.kind,
+                    status,
+                    duration: result.duration.as_secs_f64(),
+                    reason,
+                }
+            })
+            .collect();
+        serde_json::to_string_pretty(&JsonReport {
+            passed: self.passed(),
+            failed: self.failed(),
+            skipped: self.skipped(),
+            errored: self.errored(),
+            checks,
+        })
+    }
+
+    /// The results as a JUnit XML test suite called 'name'
+    pub fn to_junit(&self, name: &str) -> String {
+        let name = xml_escape(name);
+        let total: f64 = self.results.iter().map(|r| r.duration.as_secs_f64()).sum();
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites>\n");
+        let _ = writeln!(
+            xml,
+            "  <testsuite name=\"{name}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{total:.3}\">",
+            self.results.len(),
+            self.failed(),
+            self.errored(),
+            self.skipped(),
+        );
+        for result in &self.results {
+            let _ = write!(
+                xml,
+                "    <testcase name=\"{}\" classname=\"{name}\" time=\"{:.3}\"",
+                xml_escape(&result.name),
+                result.duration.as_secs_f64()
+            );
+            let (element, reason) = match &result.outcome {
+                Ok(CheckStatus::Success) => {
+                    xml.push_str("/>\n");
+                    continue;
+                }
+                Ok(CheckStatus::Fail { reason }) => ("failure", reason.clone()),
+                Ok(CheckStatus::Skip { reason }) => ("skipped", reason.clone()),
+                Err(e) => ("error", e.full_message()),
+            };
+            // The first line makes a short message, the whole reason can hold a diff
+            let message = xml_escape(reason.lines().next().unwrap_or_default());
+            let _ = writeln!(
+                xml,
+                ">\n      <{element} message=\"{message}\">{}</{element}>\n    </testcase>",
+                xml_escape(&reason)
+            );
+        }
+        xml.push_str("  </testsuite>\n</testsuites>\n");
+        xml
+    }
+}
@@ -1,11 +1,32 @@
-use crate::types::{Check, CheckType};
-use anyhow::{Context, Result, bail};
+use crate::error::Context;
+use crate::types::{Check, CheckType, ChecksFile, Defaults, DiffOptions, HttpAuth, Stdin};
+use crate::{Diff, DiffSettings, StructuredFormat, line_diff, matrix, value_diff};
+use crate::{DifferentError, Result};
+use base64::prelude::{BASE64_STANDARD, Engine};
 use log::debug;
-use minijinja::{Environment, path_loader, value::Value};
-use std::collections::HashMap;
+use minijinja::Environment;
+use regex::Regex;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
 use std::fs;
-use std::path::Path;
-use std::process::{Command, Output};
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex, mpsc};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// How often a command with a timeout is checked on
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// Wait before the first retry of a check that doesn't set its own
+const DEFAULT_RETRY_DELAY: f64 = 1.0;
+/// The largest HTTP response body read unless a request sets its own limit
+pub const DEFAULT_MAX_BODY_SIZE: u64 = 100 * 1024 * 1024;
 
 #[macro_export]
 macro_rules! fail {
@@ -18,48 +39,415 @@ macro_rules! fail {
 pub enum CheckStatus {
     Success,
     Fail { reason: String },
+    Skip { reason: String },
 }
 
+/// Check a command's output stream (or any other text) against an exact expected value,
+/// fragments it must and must not contain, and regexes it must match
 fn stream_matches(
-    stream: &Vec<u8>,
+    stream: &[u8],
     expected_match: Option<&String>,
     contains: &[String],
+    not_contains: &[String],
+    matches: &[String],
     stream_type: &str,
-) -> CheckStatus {
-    if let Some(expected_match) = expected_match {
-        let actual = String::from_utf8_lossy(stream);
-        if actual != *expected_match {
-            return CheckStatus::Fail {
-                reason: format!("{stream_type} did not match expected output"),
-            };
+) -> Result<CheckStatus> {
+    let actual = String::from_utf8_lossy(stream);
+    if let Some(expected_match) = expected_match
+        && actual != *expected_match
+    {
+        fail!("{stream_type} did not match expected output");
+    }
+
+    for fragment in contains {
+        if !actual.contains(fragment) {
+            fail!("{stream_type} did not contain expected fragment '{fragment}'");
         }
+    }
 
-        for fragment in contains {
-            if !actual.contains(fragment) {
-                return CheckStatus::Fail {
-                    reason: format!("{stream_type} did not contain expected fragment '{fragment}'"),
-                };
-            }
+    for fragment in not_contains {
+        if actual.contains(fragment) {
+            fail!("{stream_type} contained unexpected fragment '{fragment}'");
+        }
+    }
+
+    for pattern in matches {
+        let re = Regex::new(pattern)
+            .with_context(|| format!("Invalid regex '{pattern}' for {stream_type}"))?;
+        if !re.is_match(&actual) {
+            fail!("{stream_type} did not match pattern '{pattern}'");
+        }
+    }
+
+    Ok(CheckStatus::Success)
+}
+
+/// Check a file's permissions, size and modification time
+fn metadata_matches(
+    path: &str,
+    metadata: &fs::Metadata,
+    mode: Option<&str>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    newer_than: Option<&str>,
+    base: &Path,
+) -> Result<CheckStatus> {
+    if let Some(mode) = mode {
+        let expected = u32::from_str_radix(mode, 8).with_context(|| {
+            format!("Invalid mode '{mode}' for {path}, expected octal like 755")
+        })?;
+        let actual = permission_bits(metadata)?;
+        if actual != expected {
+            fail!("File {path} has mode {actual:o} (expected {expected:o})");
+        }
+    }
+
+    let size = metadata.len();
+    if let Some(min) = min_size
+        && size < min
+    {
+        fail!("File {path} has size {size} (expected at least {min})");
+    }
+    if let Some(max) = max_size
+        && size > max
+    {
+        fail!("File {path} has size {size} (expected at most {max})");
+    }
+
+    if let Some(other) = newer_than {
+        let other_modified = base
+            .join(other)
+            .metadata()
+            .and_then(|m| m.modified())
+            .with_context(|| format!("Unable to read modification time of {other}"))?;
+        let modified = metadata.modified()?;
+        if let Ok(age) = other_modified.duration_since(modified) {
+            fail!("File {path} was modified {age:?} before {other} (expected after)");
+        }
+    }
+
+    Ok(CheckStatus::Success)
+}
+
+#[cfg(unix)]
+fn permission_bits(metadata: &fs::Metadata) -> Result<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Ok(metadata.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn permission_bits(_metadata: &fs::Metadata) -> Result<u32> {
+    Err(DifferentError::Check(
+        "File mode checks are only supported on unix".into(),
+    ))
+}
+
+/// What came back from an HTTP request
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    /// The body as it was sent, whatever its encoding
+    pub body: Vec<u8>,
+    /// How long the request took, including reading the body
+    pub latency: Duration,
+}
+
+impl HttpResponse {
+    /// The value of the first header called 'name', ignoring case
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// An HTTP request to make
+#[derive(Debug, Clone)]
+pub struct HttpRequest<'a> {
+    pub method: &'a str,
+    pub url: &'a str,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<String>,
+    pub follow_redirects: bool,
+    pub verify_tls: bool,
+    pub timeout: Option<Duration>,
+    /// Fail instead of reading a response body larger than this many bytes
+    pub max_body_size: u64,
+}
+
+impl<'a> HttpRequest<'a> {
+    /// A request without a body that follows redirects, verifies certificates
+    /// and reads responses of up to [`DEFAULT_MAX_BODY_SIZE`]
+    pub fn new(method: &'a str, url: &'a str) -> Self {
+        Self {
+            method,
+            url,
+            headers: Vec::new(),
+            body: None,
+            follow_redirects: true,
+            verify_tls: true,
+            timeout: None,
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
         }
     }
+}
+
+/// Perform an HTTP request.
+/// Error statuses are returned like any other, only transport problems are errors
+pub fn http_request(request: &HttpRequest) -> Result<HttpResponse> {
+    let HttpRequest { method, url, .. } = request;
+    let tls = ureq::tls::TlsConfig::builder()
+        .disable_verification(!request.verify_tls)
+        .build();
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .http_status_as_error(false)
+        .timeout_global(request.timeout)
+        .max_redirects(if request.follow_redirects { 10 } else { 0 })
+        .tls_config(tls)
+        .build()
+        .into();
+    let mut builder = ureq::http::Request::builder().method(*method).uri(*url);
+    for (name, value) in &request.headers {
+        builder = builder.header(name, value);
+    }
+    let body = request.body.clone().unwrap_or_default();
+    let http_request = builder
+        .body(body)
+        .map_err(ureq::Error::from)
+        .with_context(|| format!("Invalid request {method} {url}"))?;
+    let start = Instant::now();
+    let mut response = agent.run(http_request)?;
+    let status = response.status().as_u16();
+    let headers = response
+        .headers()
+        .iter()
+        .map(|(name, value)| {
+            let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+            (name.to_string(), value)
+        })
+        .collect();
+    let body = response
+        .body_mut()
+        .with_config()
+        .limit(request.max_body_size)
+        .read_to_vec()?;
+    Ok(HttpResponse {
+        status,
+        headers,
+        body,
+        latency: start.elapsed(),
+    })
+}
+
+/// Read everything from 'reader' on another thread
+fn read_in_background(mut reader: impl Read + Send + 'static) -> JoinHandle<io::Result<Vec<u8>>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).map(|_| buf)
+    })
+}
+
+/// The system shell running 'cmd'
+fn shell_command(cmd: &str) -> Command {
+    if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.arg("/C").arg(cmd);
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(cmd);
+        command
+    }
+}
+
+/// Put a command that may be killed in its own process group on unix,
+/// so that killing the group also stops the processes it started, like a shell's children
+fn isolate(command: &mut Command) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    #[cfg(not(unix))]
+    let _ = command;
+}
 
-    CheckStatus::Success
+/// Kill 'child' along with the rest of its process group on unix
+fn kill_group(child: &mut Child) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        let pgid = child.id() as libc::pid_t;
+        // SAFETY: kill only sends a signal, the group is the one isolate() made for the child
+        if unsafe { libc::kill(-pgid, libc::SIGKILL) } == 0 {
+            return Ok(());
+        }
+    }
+    child.kill()
 }
 
-pub fn run_command(cmd: &str, cwd: &Path, variables: &HashMap<String, String>) -> Result<Output> {
-    let args = shlex::split(cmd).unwrap();
-    let Some((exec, args)) = args.split_first() else {
-        bail!("Unable to parse command {cmd}");
+/// Run 'cmd' to completion and capture its output. With 'shell', the system shell runs it,
+/// otherwise it's split like a shell would and run directly. 'stdin' is fed to the command.
+/// Returns None if it was killed, with every process it started, for running longer than 'timeout'
+pub fn run_command(
+    cmd: &str,
+    cwd: &Path,
+    variables: &HashMap<String, String>,
+    timeout: Option<Duration>,
+    shell: bool,
+    stdin: Option<Vec<u8>>,
+) -> Result<Option<Output>> {
+    let mut command = if shell {
+        shell_command(cmd)
+    } else {
+        let Some(args) = shlex::split(cmd) else {
+            return Err(DifferentError::Check(format!(
+                "Unable to parse command {cmd}"
+            )));
+        };
+        let Some((exec, args)) = args.split_first() else {
+            return Err(DifferentError::Check(format!(
+                "Unable to parse command {cmd}"
+            )));
+        };
+        let mut command = Command::new(exec);
+        command.args(args);
+        command
     };
-    let Ok(output) = Command::new(exec)
-        .args(args)
+    if timeout.is_some() {
+        isolate(&mut command);
+    }
+    let Ok(mut child) = command
         .current_dir(cwd)
         .envs(variables)
-        .output()
+        .stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
     else {
-        bail!("Unable to run command {cmd}");
+        return Err(DifferentError::Check(format!(
+            "Unable to run command {cmd}"
+        )));
+    };
+    if let (Some(input), Some(mut pipe)) = (stdin, child.stdin.take()) {
+        // A command that exits without reading all of its input is not an error
+        thread::spawn(move || pipe.write_all(&input));
+    }
+    let Some(timeout) = timeout else {
+        return Ok(Some(child.wait_with_output()?));
+    };
+
+    // Drain the pipes while waiting, a command blocked on a full pipe would never finish
+    let stdout = child.stdout.take().map(read_in_background);
+    let stderr = child.stderr.take().map(read_in_background);
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            kill_group(&mut child)?;
+            child.wait()?;
+            return Ok(None);
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+
+    let collect = |handle: Option<JoinHandle<io::Result<Vec<u8>>>>| -> Result<Vec<u8>> {
+        match handle {
+            Some(handle) => Ok(handle.join().map_err(|_| {
+                DifferentError::Check(format!("Output reader for {cmd} panicked"))
+            })??),
+            None => Ok(Vec::new()),
+        }
+    };
+    Ok(Some(Output {
+        status,
+        stdout: collect(stdout)?,
+        stderr: collect(stderr)?,
+    }))
+}
+
+/// Run a setup, teardown, before or after command, which has to succeed
+fn run_hook(
+    cmd: &str,
+    base: &Path,
+    variables: &HashMap<String, String>,
+    timeout: Option<Duration>,
+) -> Result<()> {
+    let Some(output) = run_command(cmd, base, variables, timeout, false, None)? else {
+        return Err(DifferentError::Check(format!(
+            "{cmd} timed out after {:?}",
+            timeout.unwrap_or_default()
+        )));
     };
-    Ok(output)
+    if !output.status.success() {
+        return Err(DifferentError::Check(format!(
+            "{cmd} exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        )));
+    }
+    Ok(())
+}
+
+/// The check's own timeout, or the default one
+fn check_timeout(check: &Check, defaults: &Defaults) -> Result<Option<Duration>> {
+    check
+        .timeout
+        .or(defaults.timeout)
+        .map(Duration::try_from_secs_f64)
+        .transpose()
+        .with_context(|| format!("Invalid timeout for check '{}'", check.name))
+}
+
+/// How often to retry the check, and how long to wait before the first retry
+fn check_retries(check: &Check, defaults: &Defaults) -> Result<(u32, Duration)> {
+    let retries = check.retries.or(defaults.retries).unwrap_or_default();
+    let delay = check
+        .retry_delay
+        .or(defaults.retry_delay)
+        .unwrap_or(DEFAULT_RETRY_DELAY);
+    let delay = Duration::try_from_secs_f64(delay)
+        .with_context(|| format!("Invalid retry delay for check '{}'", check.name))?;
+    Ok((retries, delay))
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Recursively collect every file under 'dir', keyed by its '/'-separated path relative to 'root'
+fn collect_files(
+    root: &Path,
+    dir: &Path,
+    files: &mut BTreeMap<String, fs::Metadata>,
+) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            collect_files(root, &path, files)?;
+        } else {
+            let relative = path.strip_prefix(root).unwrap_or(&path);
+            let key = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            files.insert(key, metadata);
+        }
+    }
+    Ok(())
 }
 
 #[derive(Debug)]
@@ -74,86 +462,752 @@ impl<'a> DiffInput<'a> {
     }
 }
 
-fn display_str(num: Option<usize>) -> String {
-    if let Some(num) = num {
-        num.to_string()
+/// Compare 'expected' to 'actual', where 'actual' is (well probably) a modified version of 'expected'
+/// Returns None if the inputs are the same, otherwise the diff to show with the failure.
+/// The diff is empty if printing is disabled in 'options'
+fn string_diff(expected: DiffInput, actual: DiffInput, options: &DiffOptions) -> Option<String> {
+    let num_lines = std::cmp::max(
+        expected.content.lines().count(),
+        actual.content.lines().count(),
+    );
+    let mut settings = DiffSettings::builder()
+        .names(expected.name.to_string(), actual.name.to_string())
+        .max_line_number(num_lines);
+    if let Some(context) = options.context {
+        settings = settings.context_lines(context);
+    }
+    if let Some(color) = options.color {
+        settings = settings.color(color);
+    }
+    let settings = settings.build();
+
+    let diff = line_diff(expected.content, actual.content, &settings);
+    if let Diff::Same = diff {
+        return None;
+    }
+
+    let mut shown = Vec::new();
+    if options.print {
+        let rendered = diff.to_string();
+        let mut lines = rendered.lines();
+        match options.max_lines {
+            Some(max) => {
+                shown.extend(lines.by_ref().take(max).map(String::from));
+                let remaining = lines.count();
+                if remaining > 0 {
+                    shown.push(format!("... ({remaining} more lines)"));
+                }
+            }
+            None => shown.extend(lines.map(String::from)),
+        }
+    }
+    Some(shown.join("\n"))
+}
+
+/// A failure reason followed by more detail, such as a diff, if there is any
+fn with_details(reason: &str, details: &str) -> String {
+    if details.is_empty() {
+        reason.to_string()
     } else {
-        String::from(" ")
+        format!("{reason}\n{details}")
     }
 }
 
-/// Compare 'expected' to 'actual', where 'actual' is (well probably) a modified version of 'expected'
-/// Returns true if the inputs are the same, false if different.
-/// If 'print' is true and there are differences, prints the diff
-fn string_diff(expected: DiffInput, actual: DiffInput, print: bool) -> bool {
-    // TODO: pull this function out into its own crate?
-    let diff = diff::lines(expected.content, actual.content);
-    let same = diff.len() == 0;
-
-    if !same && print {
-        println!("---- expected: {}", expected.name);
-        println!("++++ actual: {}", actual.name);
-
-        let indent = "  ";
-
-        let mut line_num_a = 0;
-        let mut line_num_b = 0;
-        for line in diff {
-            let (sep, content, line_num_a_display, line_num_b_display) = match line {
-                diff::Result::Left(l) => {
-                    line_num_a += 1;
-                    ('-', l, Some(line_num_a), None)
-                }
-                diff::Result::Both(l, _) => {
-                    line_num_a += 1;
-                    line_num_b += 1;
-                    ('|', l, Some(line_num_a), Some(line_num_b))
-                }
-                diff::Result::Right(r) => {
-                    line_num_b += 1;
-                    ('+', r, None, Some(line_num_b))
+#[derive(Debug)]
+pub struct CheckResult {
+    pub name: String,
+    /// The check's type, None for suite setup and teardown
+    pub kind: Option<&'static str>,
+    /// Err if the check could not be executed at all
+    pub outcome: Result<CheckStatus>,
+    pub duration: Duration,
+    /// Expected values that didn't match, see [`CheckOutput`]
+    pub snapshots: Vec<Snapshot>,
+}
+
+/// An expected value in a check that didn't match, with what was found instead
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    /// The check's field holding the expected value, like `expected_stdout`
+    pub field: &'static str,
+    pub actual: String,
+}
+
+/// What a check produces besides its status
+#[derive(Debug, Default)]
+pub struct CheckOutput {
+    /// Variables captured for later checks
+    pub captured: HashMap<String, String>,
+    /// Expected values to replace to make the check pass, for `--update-snapshots`
+    pub snapshots: Vec<Snapshot>,
+}
+
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub results: Vec<CheckResult>,
+}
+
+impl Summary {
+    /// Record a result, printing it to stdout unless 'quiet'
+    fn push(&mut self, result: CheckResult, quiet: bool) -> io::Result<()> {
+        if !quiet {
+            let name = &result.name;
+            let mut out = io::stdout().lock();
+            match &result.outcome {
+                Ok(CheckStatus::Success) => writeln!(out, "{name}: ok")?,
+                Ok(CheckStatus::Fail { reason }) => writeln!(out, "{name}: FAILED - {reason}")?,
+                Ok(CheckStatus::Skip { reason }) => writeln!(out, "{name}: skipped - {reason}")?,
+                Err(e) => writeln!(out, "{name}: ERROR - {}", e.full_message())?,
+            }
+        }
+        self.results.push(result);
+        Ok(())
+    }
+
+    fn count(&self, f: impl Fn(&Result<CheckStatus>) -> bool) -> usize {
+        self.results.iter().filter(|r| f(&r.outcome)).count()
+    }
+
+    pub fn passed(&self) -> usize {
+        self.count(|o| matches!(o, Ok(CheckStatus::Success)))
+    }
+
+    pub fn failed(&self) -> usize {
+        self.count(|o| matches!(o, Ok(CheckStatus::Fail { .. })))
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.count(|o| matches!(o, Ok(CheckStatus::Skip { .. })))
+    }
+
+    pub fn errored(&self) -> usize {
+        self.count(|o| o.is_err())
+    }
+
+    /// 0 if everything passed, 1 if any check failed, 2 if any check could not be run.
+    /// Errors take priority over failures
+    pub fn exit_code(&self) -> i32 {
+        if self.errored() > 0 {
+            2
+        } else if self.failed() > 0 {
+            1
+        } else {
+            0
+        }
+    }
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} passed, {} failed, {} skipped, {} errored",
+            self.passed(),
+            self.failed(),
+            self.skipped(),
+            self.errored()
+        )
+    }
+}
+
+/// Load a checks file. The format is picked by extension: TOML, YAML or JSON
+pub fn load_checks(path: &Path) -> Result<ChecksFile> {
+    load_with_includes(path, &mut Vec::new())
+}
+
+/// Load a checks file and merge in the files it includes. 'loading' is the chain of files
+/// currently being loaded, to catch include cycles.
+/// Included files come first: their checks, setup and teardown commands run before the
+/// including file's, and its variables override theirs.
+/// Templates, diff options and defaults only come from the including file
+fn load_with_includes(path: &Path, loading: &mut Vec<PathBuf>) -> Result<ChecksFile> {
+    let canonical = path
+        .canonicalize()
+        .with_context(|| format!("Unable to read {}", path.display()))?;
+    if loading.contains(&canonical) {
+        return Err(DifferentError::Check(format!(
+            "{} includes itself",
+            path.display()
+        )));
+    }
+
+    let file = parse_checks(path)?;
+    if file.include.is_empty() {
+        return Ok(file);
+    }
+
+    loading.push(canonical);
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut merged = ChecksFile::default();
+    for pattern in &file.include {
+        let full = dir.join(pattern);
+        let mut paths: Vec<PathBuf> = glob::glob(&full.to_string_lossy())
+            .with_context(|| format!("Invalid include pattern '{pattern}'"))?
+            .collect::<Result<_, _>>()?;
+        if paths.is_empty() {
+            return Err(DifferentError::Check(format!(
+                "Include '{pattern}' of {} matched no files",
+                path.display()
+            )));
+        }
+        paths.sort();
+        for included in paths {
+            let included = load_with_includes(&included, loading)?;
+            merged.variables.extend(included.variables);
+            merged.setup.extend(included.setup);
+            merged.teardown.extend(included.teardown);
+            merged.checks.extend(included.checks);
+        }
+    }
+    loading.pop();
+
+    merged.variables.extend(file.variables);
+    merged.setup.extend(file.setup);
+    merged.teardown.extend(file.teardown);
+    merged.checks.extend(file.checks);
+    Ok(ChecksFile {
+        templates: file.templates,
+        diff: file.diff,
+        defaults: file.defaults,
+        ..merged
+    })
+}
+
+fn parse_checks(path: &Path) -> Result<ChecksFile> {
+    let Some(format) = StructuredFormat::from_path(path) else {
+        return Err(DifferentError::Check(format!(
+            "Unknown checks file format {}, expected .toml, .yaml or .json",
+            path.display()
+        )));
+    };
+    let text =
+        fs::read_to_string(path).with_context(|| format!("Unable to read {}", path.display()))?;
+    let mut value = format
+        .parse(&text)
+        .with_context(|| format!("Unable to parse {}", path.display()))?;
+    matrix::expand_checks(&mut value)?;
+    serde_json::from_value(value).with_context(|| format!("Invalid checks file {}", path.display()))
+}
+
+/// Indices of the checks each check needs, or why they can't be resolved
+fn resolve_needs(checks: &[Check]) -> Vec<Result<Vec<usize>, String>> {
+    let index: HashMap<&str, usize> = checks
+        .iter()
+        .enumerate()
+        .map(|(i, check)| (check.name.as_str(), i))
+        .collect();
+    let mut needs: Vec<Result<Vec<usize>, String>> = checks
+        .iter()
+        .map(|check| {
+            check
+                .needs
+                .iter()
+                .map(|name| {
+                    index
+                        .get(name.as_str())
+                        .copied()
+                        .ok_or_else(|| format!("Unknown check '{name}' in needs"))
+                })
+                .collect()
+        })
+        .collect();
+
+    // A check that (indirectly) needs itself could never start
+    let on_cycle: Vec<bool> = (0..checks.len())
+        .map(|start| {
+            let mut seen = vec![false; checks.len()];
+            let mut stack = vec![start];
+            while let Some(i) = stack.pop() {
+                for &need in needs[i].as_deref().unwrap_or_default() {
+                    if need == start {
+                        return true;
+                    }
+                    if !seen[need] {
+                        seen[need] = true;
+                        stack.push(need);
+                    }
                 }
+            }
+            false
+        })
+        .collect();
+    for (i, cyclic) in on_cycle.into_iter().enumerate() {
+        if cyclic {
+            needs[i] = Err(format!("Check '{}' depends on itself", checks[i].name));
+        }
+    }
+    needs
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Waiting,
+    Running,
+    /// Finished with the outcome
+    Done(Finished),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Finished {
+    Passed,
+    Failed,
+    Skipped,
+}
+
+/// Which checks have run, shared between the workers
+struct Schedule<'c> {
+    checks: &'c [Check],
+    needs: Vec<Result<Vec<usize>, String>>,
+    states: Mutex<Vec<State>>,
+    changed: Condvar,
+    /// Skip the checks that haven't started once one fails
+    fail_fast: bool,
+    /// Whether any check has failed or errored so far
+    failed: AtomicBool,
+}
+
+impl Schedule<'_> {
+    /// Wait for the first check whose needs have all finished and mark it as running.
+    /// Returns None once every check has been started
+    fn next(&self) -> Option<usize> {
+        let mut states = self.states.lock().unwrap();
+        loop {
+            let ready = (0..states.len()).find(|&i| {
+                states[i] == State::Waiting
+                    && self.needs[i]
+                        .as_deref()
+                        .unwrap_or_default()
+                        .iter()
+                        .all(|&need| matches!(states[need], State::Done(_)))
+            });
+            if let Some(i) = ready {
+                states[i] = State::Running;
+                return Some(i);
+            }
+            if !states.contains(&State::Waiting) {
+                return None;
+            }
+            states = self.changed.wait(states).unwrap();
+        }
+    }
+
+    /// The outcome of a check whose needs can't be met, if that's the case
+    fn blocked(&self, i: usize) -> Option<Result<CheckStatus>> {
+        if self.fail_fast && self.failed.load(Ordering::SeqCst) {
+            return Some(Ok(CheckStatus::Skip {
+                reason: "stopped after an earlier check failed".to_string(),
+            }));
+        }
+        let needs = match &self.needs[i] {
+            Ok(needs) => needs,
+            Err(e) => return Some(Err(DifferentError::Check(e.clone()))),
+        };
+        let states = self.states.lock().unwrap();
+        needs.iter().find_map(|&need| {
+            let name = &self.checks[need].name;
+            let reason = match states[need] {
+                State::Done(Finished::Failed) => format!("dependency '{name}' failed"),
+                State::Done(Finished::Skipped) => format!("dependency '{name}' was skipped"),
+                _ => return None,
             };
+            Some(Ok(CheckStatus::Skip { reason }))
+        })
+    }
+
+    fn finish(&self, i: usize, outcome: &Result<CheckStatus>) {
+        if matches!(outcome, Ok(CheckStatus::Fail { .. }) | Err(_)) {
+            self.failed.store(true, Ordering::SeqCst);
+        }
+        let finished = match outcome {
+            Ok(CheckStatus::Success) => Finished::Passed,
+            Ok(CheckStatus::Skip { .. }) => Finished::Skipped,
+            Ok(CheckStatus::Fail { .. }) | Err(_) => Finished::Failed,
+        };
+        self.states.lock().unwrap()[i] = State::Done(finished);
+        self.changed.notify_all();
+    }
+}
+
+/// How checks are run
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    /// Checks run at the same time. 0 and 1 both run them one by one
+    pub jobs: usize,
+    /// Don't print results as they come in
+    pub quiet: bool,
+    /// Stop at the first check that fails or errors, skipping the ones that haven't started.
+    /// By default every check runs regardless
+    pub fail_fast: bool,
+}
+
+/// Run every check, up to 'options.jobs' of them at a time. A check only starts once the checks it needs
+/// have passed, and is skipped if any of them didn't. With 'options.fail_fast', the first failure
+/// skips every check that hasn't started yet.
+/// Results are printed in the order the checks were given, each as soon as all earlier ones are done.
+/// If printing fails, the checks already started are finished but no more are started
+pub fn run_all(
+    checks: &[Check],
+    base: &Path,
+    variables: &HashMap<String, String>,
+    jinja_env: &Environment,
+    diff_options: &DiffOptions,
+    defaults: &Defaults,
+    options: &RunOptions,
+) -> io::Result<Summary> {
+    let schedule = Schedule {
+        checks,
+        needs: resolve_needs(checks),
+        states: Mutex::new(vec![State::Waiting; checks.len()]),
+        changed: Condvar::new(),
+        fail_fast: options.fail_fast,
+        failed: AtomicBool::new(false),
+    };
+    let shared = Mutex::new(variables.clone());
+    let (sender, receiver) = mpsc::channel();
+    let mut finished: Vec<Option<CheckResult>> = checks.iter().map(|_| None).collect();
+    let mut summary = Summary::default();
 
-            let line_num_a_display = display_str(line_num_a_display);
-            let line_num_b_display = display_str(line_num_b_display);
+    thread::scope(|scope| -> io::Result<()> {
+        for _ in 0..options.jobs.clamp(1, checks.len().max(1)) {
+            let sender = sender.clone();
+            let schedule = &schedule;
+            let shared = &shared;
+            scope.spawn(move || {
+                while let Some(index) = schedule.next() {
+                    let start = Instant::now();
+                    let mut output = CheckOutput::default();
+                    let outcome = schedule.blocked(index).unwrap_or_else(|| {
+                        // Checks see what was captured by the checks finished before they start
+                        let variables = shared.lock().unwrap().clone();
+                        run(
+                            &checks[index],
+                            base,
+                            &variables,
+                            jinja_env,
+                            diff_options,
+                            defaults,
+                            &mut output,
+                        )
+                    });
+                    shared.lock().unwrap().extend(output.captured);
+                    schedule.finish(index, &outcome);
+                    let check = &checks[index];
+                    let result = CheckResult {
+                        name: check.name.clone(),
+                        kind: Some(check.check.kind()),
+                        outcome,
+                        duration: start.elapsed(),
+                        snapshots: output.snapshots,
+                    };
+                    if sender.send((index, result)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+        for (index, result) in receiver {
+            finished[index] = Some(result);
+            while let Some(result) = finished
+                .get_mut(summary.results.len())
+                .and_then(Option::take)
+            {
+                // Returning drops the receiver, so the workers stop after their current check
+                summary.push(result, options.quiet)?;
+            }
+        }
+        Ok(())
+    })?;
+    Ok(summary)
+}
+
+fn hook_result(name: &str, outcome: Result<CheckStatus>, duration: Duration) -> CheckResult {
+    CheckResult {
+        name: name.to_string(),
+        kind: None,
+        outcome,
+        duration,
+        snapshots: Vec::new(),
+    }
+}
 
-            println!("{indent}{line_num_a_display}{indent}{line_num_b_display} {sep} {content}");
+/// Run a whole checks file: its setup commands, then the checks, then its teardown commands.
+/// Teardown always runs, even if setup or checks failed.
+/// Setup and teardown problems are reported as errored results.
+/// Fails only if the results could not be printed
+pub fn run_suite(
+    file: &ChecksFile,
+    base: &Path,
+    jinja_env: &Environment,
+    options: &RunOptions,
+) -> io::Result<Summary> {
+    let variables = &file.variables;
+    let mut summary = Summary::default();
+    let timeout = match file.defaults.timeout.map(Duration::try_from_secs_f64) {
+        None => None,
+        Some(Ok(timeout)) => Some(timeout),
+        Some(Err(e)) => {
+            let e = DifferentError::Check(format!("Invalid default timeout: {e}"));
+            summary.push(hook_result("setup", Err(e), Duration::ZERO), options.quiet)?;
+            return Ok(summary);
+        }
+    };
+
+    let start = Instant::now();
+    let setup = file
+        .setup
+        .iter()
+        .try_for_each(|cmd| run_hook(cmd, base, variables, timeout));
+    let mut printed = match setup {
+        Ok(()) => run_all(
+            &file.checks,
+            base,
+            variables,
+            jinja_env,
+            &file.diff,
+            &file.defaults,
+            options,
+        )
+        .map(|results| summary = results),
+        Err(e) => summary.push(hook_result("setup", Err(e), start.elapsed()), options.quiet),
+    };
+
+    // Teardown still runs if the results could not be printed, but isn't reported
+    for cmd in &file.teardown {
+        let start = Instant::now();
+        if let Err(e) = run_hook(cmd, base, variables, timeout) {
+            let result = hook_result("teardown", Err(e), start.elapsed());
+            let quiet = options.quiet || printed.is_err();
+            if let Err(e) = summary.push(result, quiet) {
+                printed = Err(e);
+            }
         }
     }
+    printed.map(|()| summary)
+}
+
+/// Evaluate a condition such as `platform == 'linux'` (optionally wrapped in `{{ }}`)
+/// against the variables map. `platform`, `arch` and `family` are the OS' unless set as variables
+pub fn eval_condition(
+    condition: &str,
+    variables: &HashMap<String, String>,
+    jinja_env: &Environment,
+) -> Result<bool> {
+    let trimmed = condition.trim();
+    let expr = trimmed
+        .strip_prefix("{{")
+        .and_then(|s| s.strip_suffix("}}"))
+        .unwrap_or(trimmed);
+    let expr = jinja_env
+        .compile_expression(expr)
+        .with_context(|| format!("Invalid condition '{condition}'"))?;
+    let mut context = HashMap::from([
+        ("platform".to_string(), std::env::consts::OS.to_string()),
+        ("arch".to_string(), std::env::consts::ARCH.to_string()),
+        ("family".to_string(), std::env::consts::FAMILY.to_string()),
+    ]);
+    context.extend(variables.iter().map(|(k, v)| (k.clone(), v.clone())));
+    let result = expr
+        .eval(context)
+        .with_context(|| format!("Unable to evaluate condition '{condition}'"))?;
+    Ok(result.is_true())
+}
+
+/// Run a check, honoring its `when` and `skip_if` conditions
+pub fn run(
+    check: &Check,
+    base: &Path,
+    variables: &HashMap<String, String>,
+    jinja_env: &Environment,
+    diff_options: &DiffOptions,
+    defaults: &Defaults,
+    output: &mut CheckOutput,
+) -> Result<CheckStatus> {
+    let variables = if check.variables.is_empty() {
+        Cow::Borrowed(variables)
+    } else {
+        let mut variables = variables.clone();
+        variables.extend(check.variables.clone());
+        Cow::Owned(variables)
+    };
+    let variables = variables.as_ref();
+
+    if let Some(condition) = &check.when
+        && !eval_condition(condition, variables, jinja_env)?
+    {
+        return Ok(CheckStatus::Skip {
+            reason: format!("condition '{condition}' was false"),
+        });
+    }
+    if let Some(condition) = &check.skip_if
+        && eval_condition(condition, variables, jinja_env)?
+    {
+        return Ok(CheckStatus::Skip {
+            reason: format!("condition '{condition}' was true"),
+        });
+    }
+
+    let timeout = check_timeout(check, defaults)?;
+    for cmd in &check.before {
+        run_hook(cmd, base, variables, timeout)
+            .with_context(|| format!("Before hook of check '{}' failed", check.name))?;
+    }
+    let (retries, delay) = check_retries(check, defaults)?;
+    let status = with_retries(&check.name, retries, delay, || {
+        // Only the last attempt's captures and snapshots count
+        *output = CheckOutput::default();
+        run_check(
+            &check.check,
+            base,
+            variables,
+            jinja_env,
+            diff_options,
+            timeout,
+            output,
+        )
+    });
+    // After hooks run even if the check failed
+    let after = check.after.iter().try_for_each(|cmd| {
+        run_hook(cmd, base, variables, timeout)
+            .with_context(|| format!("After hook of check '{}' failed", check.name))
+    });
+    let status = status?;
+    after?;
 
-    return same;
+    // Custom messages supplement the generated reason, they don't replace it
+    if let CheckStatus::Fail { reason } = &status
+        && let Some(message) = &check.message
+    {
+        let mut context = variables.clone();
+        context.insert(String::from("name"), check.name.clone());
+        context.insert(String::from("reason"), reason.clone());
+        let message = jinja_env
+            .render_str(message, context)
+            .with_context(|| format!("Unable to render message for check '{}'", check.name))?;
+        return Ok(CheckStatus::Fail {
+            reason: format!("{reason}\n{message}"),
+        });
+    }
+
+    Ok(status)
+}
+
+/// Call 'attempt' until it doesn't fail or the retries run out.
+/// Waits 'delay' before the first retry, doubling it each time
+fn with_retries(
+    name: &str,
+    retries: u32,
+    mut delay: Duration,
+    mut attempt: impl FnMut() -> Result<CheckStatus>,
+) -> Result<CheckStatus> {
+    let mut attempts = 1;
+    loop {
+        match attempt()? {
+            CheckStatus::Fail { reason } if retries > 0 && attempts > retries => {
+                return Ok(CheckStatus::Fail {
+                    reason: format!("{reason} (after {attempts} attempts)"),
+                });
+            }
+            CheckStatus::Fail { reason } if attempts <= retries => {
+                debug!("Check '{name}' failed, retrying in {delay:?}: {reason}");
+                thread::sleep(delay);
+                delay *= 2;
+                attempts += 1;
+            }
+            status => return Ok(status),
+        }
+    }
 }
 
+/// Run a check, recording what it captures and which expected values didn't match in 'out'
 pub fn run_check(
     check: &CheckType,
     base: &Path,
     variables: &HashMap<String, String>,
     jinja_env: &Environment,
+    diff_options: &DiffOptions,
+    timeout: Option<Duration>,
+    out: &mut CheckOutput,
 ) -> Result<CheckStatus> {
-    let print_diffs = true; // TODO: make configurable
+    debug!("Running check {check:?}");
 
     match check {
         CheckType::File {
             path,
+            absent,
             contains,
+            not_contains,
+            matches,
             template,
             contents,
+            mode,
+            min_size,
+            max_size,
+            newer_than,
+            sha256,
         } => {
             let full = base.join(path);
+            if *absent {
+                if full.exists() {
+                    fail!("File {path} exists but should be absent");
+                }
+                return Ok(CheckStatus::Success);
+            }
             if !full.is_file() {
                 fail!("Missing file {path}");
             }
 
-            let Ok(actual_contents) = fs::read_to_string(&full) else {
+            let metadata = full
+                .metadata()
+                .with_context(|| format!("Unable to read metadata of {path}"))?;
+            let status = metadata_matches(
+                path,
+                &metadata,
+                mode.as_deref(),
+                *min_size,
+                *max_size,
+                newer_than.as_deref(),
+                base,
+            )?;
+            if let CheckStatus::Fail { .. } = status {
+                return Ok(status);
+            }
+
+            let Ok(bytes) = fs::read(&full) else {
+                fail!("Unable to read file {}", full.display());
+            };
+
+            if let Some(expected_hash) = sha256 {
+                let actual_hash = sha256_hex(&bytes);
+                if !actual_hash.eq_ignore_ascii_case(expected_hash) {
+                    fail!("File {path} has sha256 {actual_hash} (expected {expected_hash})");
+                }
+            }
+
+            let text_checks = contents.is_some()
+                || template.is_some()
+                || !contains.is_empty()
+                || !not_contains.is_empty()
+                || !matches.is_empty();
+            if !text_checks {
+                return Ok(CheckStatus::Success);
+            }
+            let Ok(actual_contents) = String::from_utf8(bytes) else {
                 fail!("Unable to read file {}", full.display());
             };
 
             if let Some(expected_contents) = contents {
                 let expected = DiffInput::new("Expected", expected_contents);
                 let actual = DiffInput::new("Actual", &actual_contents);
-                if !string_diff(expected, actual, print_diffs) {
-                    fail!("File contents do not match expected contents");
+                if let Some(diff) = string_diff(expected, actual, diff_options) {
+                    out.snapshots.push(Snapshot {
+                        field: "contents",
+                        actual: actual_contents,
+                    });
+                    fail!(
+                        "{}",
+                        with_details("File contents do not match expected contents", &diff)
+                    );
                 }
             };
 
@@ -172,24 +1226,40 @@ pub fn run_check(
 
                 let expected = DiffInput::new("Template", &rendered);
                 let actual = DiffInput::new("Actual", &actual_contents);
-                if !string_diff(expected, actual, print_diffs) {
-                    fail!("File contents do not match rendered template");
+                if let Some(diff) = string_diff(expected, actual, diff_options) {
+                    fail!(
+                        "{}",
+                        with_details("File contents do not match rendered template", &diff)
+                    );
                 }
             }
 
-            // TODO: regex matching would be nice
-            if !contains.is_empty() {
-                // TODO: turn this into function to be more DRY
-                for fragment in contains {
-                    if !actual_contents.contains(fragment) {
-                        fail!("{path}  did not contain expected fragment '{fragment}'");
-                    }
-                }
+            let status = stream_matches(
+                actual_contents.as_bytes(),
+                None,
+                contains,
+                not_contains,
+                matches,
+                path,
+            )?;
+            if let CheckStatus::Fail { .. } = status {
+                return Ok(status);
             }
         }
 
-        CheckType::Directory { path, children } => {
+        CheckType::Directory {
+            path,
+            absent,
+            children,
+            forbidden_children,
+        } => {
             let full = base.join(path);
+            if *absent {
+                if full.exists() {
+                    fail!("Directory {path} exists but should be absent");
+                }
+                return Ok(CheckStatus::Success);
+            }
             if !full.is_dir() {
                 fail!("Missing directory: {path}");
             }
@@ -209,6 +1279,67 @@ pub fn run_check(
                     fail!("Expected child {child} of {path} does not exist");
                 }
             }
+
+            for child in forbidden_children {
+                if actual_children.contains(child) {
+                    fail!("Forbidden child {child} of {path} exists");
+                }
+            }
+        }
+
+        CheckType::Tree { path, manifest } => {
+            let full = base.join(path);
+            if !full.is_dir() {
+                fail!("Missing directory: {path}");
+            }
+
+            let mut actual = BTreeMap::new();
+            if collect_files(&full, &full, &mut actual).is_err() {
+                fail!("Unable to read directory tree {path}");
+            }
+
+            let mut problems = Vec::new();
+            for entry in manifest {
+                let Some(metadata) = actual.remove(&entry.path) else {
+                    problems.push(format!("missing {}", entry.path));
+                    continue;
+                };
+
+                if let Some(size) = entry.size
+                    && metadata.len() != size
+                {
+                    problems.push(format!(
+                        "{} has size {} (expected {size})",
+                        entry.path,
+                        metadata.len()
+                    ));
+                }
+
+                if let Some(expected_hash) = &entry.sha256 {
+                    let Ok(bytes) = fs::read(full.join(&entry.path)) else {
+                        fail!("Unable to read file {}", entry.path);
+                    };
+                    let actual_hash = sha256_hex(&bytes);
+                    if !actual_hash.eq_ignore_ascii_case(expected_hash) {
+                        problems.push(format!(
+                            "{} has sha256 {actual_hash} (expected {expected_hash})",
+                            entry.path
+                        ));
+                    }
+                }
+            }
+
+            // Whatever is left over was not in the manifest
+            for extra in actual.keys() {
+                problems.push(format!("unexpected {extra}"));
+            }
+
+            if !problems.is_empty() {
+                fail!(
+                    "Tree {path} does not match manifest: {}",
+                    problems.join(", ")
+                );
+            }
         }
 
         CheckType::Command {
@@ -218,56 +1349,277 @@ pub fn run_check(
             expected_stderr,
             stdout_contains,
             stderr_contains,
+            stdout_not_contains,
+            stderr_not_contains,
+            stdout_matches,
+            stderr_matches,
+            capture,
+            shell,
+            stdin,
         } => {
-            let output = match run_command(cmd, base, variables) {
-                Ok(output) => output,
-                Err(e) => fail!("Command did not run successfully: {e}"),
+            let input = match stdin {
+                None => None,
+                Some(Stdin::Text(text)) => Some(text.clone().into_bytes()),
+                Some(Stdin::File { file }) => match fs::read(base.join(file)) {
+                    Ok(bytes) => Some(bytes),
+                    Err(_) => fail!("Unable to read stdin file {file}"),
+                },
+            };
+            // Not being able to run the command at all is an error, not a failed check
+            let Some(output) = run_command(cmd, base, variables, timeout, *shell, input)? else {
+                fail!(
+                    "Command {cmd} timed out after {:?}",
+                    timeout.unwrap_or_default()
+                );
             };
 
-            if output.status.code() != Some(*code) {
-                fail!("Command {} exited with unexpected code", cmd);
+            if !code.matches(output.status.code()) {
+                match output.status.code() {
+                    Some(actual) => {
+                        fail!("Command {cmd} exited with code {actual} (expected {code})")
+                    }
+                    None => fail!("Command {cmd} was killed by a signal (expected code {code})"),
+                }
             }
 
-            let stdout = &output.stdout;
-            if let CheckStatus::Fail { reason } =
-                stream_matches(stdout, expected_stdout.as_ref(), &stdout_contains, "stdout")
-            {
-                return Ok(CheckStatus::Fail { reason });
-            };
+            for (field, expected, actual) in [
+                ("expected_stdout", expected_stdout, &output.stdout),
+                ("expected_stderr", expected_stderr, &output.stderr),
+            ] {
+                let actual = String::from_utf8_lossy(actual);
+                if expected
+                    .as_ref()
+                    .is_some_and(|expected| *expected != actual)
+                {
+                    out.snapshots.push(Snapshot {
+                        field,
+                        actual: actual.into_owned(),
+                    });
+                }
+            }
 
-            let stderr = &output.stderr;
-            if let CheckStatus::Fail { reason } =
-                stream_matches(stderr, expected_stderr.as_ref(), &stderr_contains, "stderr")
-            {
-                return Ok(CheckStatus::Fail { reason });
-            };
+            let status = stream_matches(
+                &output.stdout,
+                expected_stdout.as_ref(),
+                stdout_contains,
+                stdout_not_contains,
+                stdout_matches,
+                "stdout",
+            )?;
+            if let CheckStatus::Fail { .. } = status {
+                return Ok(status);
+            }
+
+            let status = stream_matches(
+                &output.stderr,
+                expected_stderr.as_ref(),
+                stderr_contains,
+                stderr_not_contains,
+                stderr_matches,
+                "stderr",
+            )?;
+            if let CheckStatus::Fail { .. } = status {
+                return Ok(status);
+            }
+
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            for (key, pattern) in capture {
+                let re = Regex::new(pattern)
+                    .with_context(|| format!("Invalid regex '{pattern}' to capture '{key}'"))?;
+                let Some(captures) = re.captures(&stdout) else {
+                    fail!("stdout did not match pattern '{pattern}' to capture '{key}'");
+                };
+                let value = captures
+                    .get(1)
+                    .or(captures.get(0))
+                    .map_or("", |m| m.as_str());
+                out.captured.insert(key.clone(), value.to_string());
+            }
         }
 
         CheckType::Http {
             method,
             code,
             url,
+            body,
+            body_template,
+            headers,
+            auth,
+            follow_redirects,
+            verify_tls,
             body_contains,
             expected_body,
+            expected_headers,
+            json,
+            max_latency_ms,
         } => {
-            todo!();
-        }
+            let render = |value: &str| {
+                jinja_env
+                    .render_str(value, variables)
+                    .with_context(|| format!("Unable to render '{value}' for {url}"))
+            };
+            let mut request = HttpRequest::new(method, url);
+            for (name, value) in headers {
+                request.headers.push((name.clone(), render(value)?));
+            }
+            match auth {
+                Some(HttpAuth::Basic { username, password }) => {
+                    let credentials = format!("{}:{}", render(username)?, render(password)?);
+                    let encoded = BASE64_STANDARD.encode(credentials);
+                    request
+                        .headers
+                        .push(("Authorization".to_string(), format!("Basic {encoded}")));
+                }
+                Some(HttpAuth::Bearer(token)) => {
+                    let value = format!("Bearer {}", render(token)?);
+                    request.headers.push(("Authorization".to_string(), value));
+                }
+                None => {}
+            }
+            request.body = match (body, body_template) {
+                (Some(body), _) => Some(body.clone()),
+                (None, Some(template)) => {
+                    Some(jinja_env.get_template(template)?.render(variables)?)
+                }
+                (None, None) => None,
+            };
+            request.follow_redirects = *follow_redirects;
+            request.verify_tls = *verify_tls;
+            request.timeout = timeout;
+
+            let response = match http_request(&request) {
+                Ok(response) => response,
+                Err(DifferentError::Http(e))
+                    if matches!(e.downcast_ref(), Some(ureq::Error::Timeout(_))) =>
+                {
+                    fail!(
+                        "Request {method} {url} timed out after {:?}",
+                        timeout.unwrap_or_default()
+                    )
+                }
+                Err(e) => fail!("Request {method} {url} failed: {}", e.full_message()),
+            };
 
-        CheckType::VarSet { key, value } => {
-            if let Some(value) = value {
-                if let Some(actual_value) = variables.get(key) {
-                    if actual_value != value {
-                        fail!(
-                            "Variable '{key}' did not match expected value '{value}' (was '{actual_value}')"
-                        );
+            let status = response.status;
+            if status != *code {
+                fail!("Request {method} {url} returned status {status} (expected {code})");
+            }
+
+            if let Some(max) = max_latency_ms {
+                let latency = response.latency.as_millis();
+                if latency > u128::from(*max) {
+                    fail!("Request {method} {url} took {latency}ms (expected at most {max}ms)");
+                }
+            }
+
+            for (name, expected) in expected_headers {
+                match response.header(name) {
+                    None => fail!("Response has no header {name}"),
+                    Some(actual) if actual != expected => {
+                        fail!("Response header {name} is '{actual}' (expected '{expected}')")
+                    }
+                    Some(_) => {}
+                }
+            }
+
+            let body = &response.body;
+            if !json.is_empty() {
+                let Ok(document) = serde_json::from_slice::<Value>(body) else {
+                    fail!("Response body is not JSON");
+                };
+                for assertion in json {
+                    let path = &assertion.path;
+                    let Some(actual) = document.pointer(&assertion.pointer) else {
+                        fail!("Response body has no value at {path}");
+                    };
+                    let Some(expected) = &assertion.expected else {
+                        continue;
+                    };
+                    match (expected, actual) {
+                        _ if actual == expected => {}
+                        // Nested values are too long to show whole, list what differs
+                        (Value::Object(_), Value::Object(_))
+                        | (Value::Array(_), Value::Array(_)) => {
+                            let changes: Vec<String> = value_diff(expected, actual)
+                                .iter()
+                                .map(|change| format!("  {change}"))
+                                .collect();
+                            fail!(
+                                "Response body differs from the expected value at {path}:\n{}",
+                                changes.join("\n")
+                            );
+                        }
+                        _ => fail!("Response body has {actual} at {path} (expected {expected})"),
                     }
-                } else {
-                    fail!("Variable '{key}' not set");
                 }
-            } else {
-                // Just check if key exists
-                if !variables.contains_key(key) {
-                    fail!("Variable '{key}' not set");
+            }
+
+            let status = stream_matches(
+                body,
+                expected_body.as_ref(),
+                body_contains,
+                &[],
+                &[],
+                "response body",
+            )?;
+            if let CheckStatus::Fail { .. } = status {
+                return Ok(status);
+            }
+        }
+
+        CheckType::Tcp { host, port } => {
+            let Ok(addrs) = (host.as_str(), *port).to_socket_addrs() else {
+                fail!("Unable to resolve {host}");
+            };
+            let mut error = None;
+            for addr in addrs {
+                let connected = match timeout {
+                    Some(timeout) => TcpStream::connect_timeout(&addr, timeout),
+                    None => TcpStream::connect(addr),
+                };
+                match connected {
+                    Ok(_) => return Ok(CheckStatus::Success),
+                    Err(e) => error = Some(e),
+                }
+            }
+            match error {
+                None => fail!("{host} did not resolve to any address"),
+                Some(e) if e.kind() == io::ErrorKind::ConnectionRefused => {
+                    fail!("Connection to {host}:{port} refused")
+                }
+                Some(e) if e.kind() == io::ErrorKind::TimedOut => fail!(
+                    "Connection to {host}:{port} timed out after {:?}",
+                    timeout.unwrap_or_default()
+                ),
+                Some(e) => fail!("Connection to {host}:{port} failed: {e}"),
+            }
+        }
+
+        CheckType::VarSet {
+            key,
+            value,
+            matches,
+        } => {
+            // Existence is always checked, value and pattern only if specified
+            let Some(actual_value) = variables.get(key) else {
+                fail!("Variable '{key}' not set");
+            };
+
+            if let Some(value) = value
+                && actual_value != value
+            {
+                fail!(
+                    "Variable '{key}' did not match expected value '{value}' (was '{actual_value}')"
+                );
+            }
+
+            if let Some(pattern) = matches {
+                let re = Regex::new(pattern)
+                    .with_context(|| format!("Invalid regex '{pattern}' for variable '{key}'"))?;
+                if !re.is_match(actual_value) {
+                    fail!(
+                        "Variable '{key}' did not match pattern '{pattern}' (was '{actual_value}')"
+                    );
                 }
             }
         }
@@ -0,0 +1,177 @@
+use crate::hyperlink::Links;
+use crate::paint::Paint;
+use crate::width::fit;
+use crate::{DiffSettings, Side, Style, Theme, display_str, visible_lines};
+use std::env;
+use std::fmt::Display;
+
+const DEFAULT_TERMINAL_WIDTH: usize = 80;
+const MIN_COLUMN_WIDTH: usize = 10;
+
+fn terminal_width() -> usize {
+    terminal_size::terminal_size()
+        .map(|(width, _)| width.0 as usize)
+        .or_else(|| env::var("COLUMNS").ok()?.parse().ok())
+        .unwrap_or(DEFAULT_TERMINAL_WIDTH)
+}
+
+/// One half of a row: line number, text, and how to color it
+struct Cell {
+    num: usize,
+    text: String,
+    style: Style,
+}
+
+struct Columns<'a> {
+    settings: &'a DiffSettings,
+    theme: Theme,
+    num_width: Option<usize>,
+    column_width: usize,
+    links: Links<'a>,
+}
+
+impl Columns<'_> {
+    fn write_row(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+        left: Option<Cell>,
+        sep: char,
+        right: Option<Cell>,
+    ) -> std::fmt::Result {
+        let indent = " ".repeat(self.settings.indent_spaces);
+
+        let render = |cell: Option<&Cell>, side: Side| {
+            let (num, text) = match cell {
+                Some(cell) => (Some(cell.num), cell.text.as_str()),
+                None => (None, ""),
+            };
+            let text = fit(text, self.column_width);
+            let half = if self.settings.show_line_numbers() {
+                let num = self.links.wrap(side, num, display_str(num, self.num_width));
+                format!("{num} {text}")
+            } else {
+                text
+            };
+            // Only the left half needs padding, the right half would just leave trailing spaces
+            let half = if side == Side::Left {
+                half
+            } else {
+                half.trim_end().to_string()
+            };
+            let style = cell.map_or(self.theme.unchanged, |c| c.style);
+            let half = style.apply(half);
+            half.paint(self.settings.color_enabled())
+        };
+
+        let left_half = render(left.as_ref(), Side::Left);
+        let right_half = render(right.as_ref(), Side::Right);
+        writeln!(f, "{indent}{left_half} {sep} {right_half}")
+    }
+}
+
+/// Render the diff as two columns, like `diff -y`.
+/// Unchanged lines have no marker, changed pairs get '|', and unpaired lines get '<' or '>'
+pub(crate) fn write_side_by_side<T: Display>(
+    f: &mut std::fmt::Formatter<'_>,
+    settings: &DiffSettings,
+    diff: &[diff::Result<T>],
+) -> std::fmt::Result {
+    let num_width = settings
+        .max_line_number
+        .map(|x| x.max(1).ilog10() as usize + 1);
+
+    let column_width = settings.column_width.unwrap_or_else(|| {
+        let numbers = if settings.show_line_numbers() {
+            2 * (num_width.unwrap_or(1) + 1)
+        } else {
+            0
+        };
+        let fixed = settings.indent_spaces + numbers + 3;
+        terminal_width().saturating_sub(fixed) / 2
+    });
+    let theme = settings.theme();
+    let columns = Columns {
+        settings,
+        theme: theme.clone(),
+        num_width,
+        column_width: column_width.max(MIN_COLUMN_WIDTH),
+        links: Links::new(settings),
+    };
+
+    let visible = visible_lines(diff, settings.context());
+
+    let mut line_num_a = 0;
+    let mut line_num_b = 0;
+    let mut lefts: Vec<Cell> = Vec::new();
+    let mut rights: Vec<Cell> = Vec::new();
+    let mut skipping = false;
+
+    let flush = |f: &mut std::fmt::Formatter<'_>, lefts: &mut Vec<Cell>, rights: &mut Vec<Cell>| {
+        let mut lefts = lefts.drain(..);
+        let mut rights = rights.drain(..);
+        loop {
+            match (lefts.next(), rights.next()) {
+                (None, None) => return Ok(()),
+                (left, right) => {
+                    let sep = match (&left, &right) {
+                        (Some(_), Some(_)) => '|',
+                        (Some(_), None) => '<',
+                        _ => '>',
+                    };
+                    columns.write_row(f, left, sep, right)?;
+                }
+            }
+        }
+    };
+
+    for (line, visible) in diff.iter().zip(visible) {
+        match line {
+            diff::Result::Left(l) => {
+                skipping = false;
+                line_num_a += 1;
+                lefts.push(Cell {
+                    num: line_num_a,
+                    text: settings.display_line(l.to_string(), true),
+                    style: theme.left,
+                });
+            }
+            diff::Result::Right(r) => {
+                skipping = false;
+                line_num_b += 1;
+                rights.push(Cell {
+                    num: line_num_b,
+                    text: settings.display_line(r.to_string(), true),
+                    style: theme.right,
+                });
+            }
+            diff::Result::Both(l, r) => {
+                flush(f, &mut lefts, &mut rights)?;
+                line_num_a += 1;
+                line_num_b += 1;
+                if !visible {
+                    if !skipping && !settings.only_changes() {
+                        let indent = " ".repeat(settings.indent_spaces);
+                        let dots = theme.unchanged.apply(format!("{indent}..."));
+                        writeln!(f, "{}", dots.paint(settings.color_enabled()))?;
+                        skipping = true;
+                    }
+                    continue;
+                }
+                skipping = false;
+
+                let left = Cell {
+                    num: line_num_a,
+                    text: settings.display_line(l.to_string(), false),
+                    style: theme.unchanged,
+                };
+                let right = Cell {
+                    num: line_num_b,
+                    text: settings.display_line(r.to_string(), false),
+                    style: theme.unchanged,
+                };
+                columns.write_row(f, Some(left), ' ', Some(right))?;
+            }
+        }
+    }
+    flush(f, &mut lefts, &mut rights)
+}
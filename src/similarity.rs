@@ -0,0 +1,50 @@
+use crate::algorithm::{self, Algorithm, Edit};
+use crate::split_lines;
+use std::hash::Hash;
+
+/// How many items the diff of 'left' and 'right' keeps, and how many it changes
+fn count_edits<T: Hash + Eq>(left: &[T], right: &[T]) -> (usize, usize) {
+    let edits = algorithm::diff(left, right, Algorithm::Myers);
+    let kept = edits
+        .iter()
+        .filter(|edit| matches!(edit, Edit::Equal(..)))
+        .count();
+    (kept, edits.len() - kept)
+}
+
+/// Twice the kept items over the total, like Python's `difflib.SequenceMatcher.ratio`
+fn ratio<T: Hash + Eq>(left: &[T], right: &[T]) -> f64 {
+    let total = left.len() + right.len();
+    if total == 0 {
+        return 1.0;
+    }
+    let (kept, _) = count_edits(left, right);
+    (2 * kept) as f64 / total as f64
+}
+
+fn chars(text: &str) -> Vec<char> {
+    text.chars().collect()
+}
+
+/// How alike the lines of 'left' and 'right' are, from 0.0 for nothing in common to 1.0 for equal
+pub fn similarity(left: &str, right: &str) -> f64 {
+    ratio(&split_lines(left), &split_lines(right))
+}
+
+/// How alike the characters of 'left' and 'right' are, from 0.0 to 1.0, e.g. to rank
+/// "did you mean" suggestions
+pub fn char_similarity(left: &str, right: &str) -> f64 {
+    ratio(&chars(left), &chars(right))
+}
+
+/// How many lines have to be removed or added to turn 'left' into 'right'.
+/// A changed line counts as one of each
+pub fn edit_distance(left: &str, right: &str) -> usize {
+    count_edits(&split_lines(left), &split_lines(right)).1
+}
+
+/// How many characters have to be removed or added to turn 'left' into 'right'.
+/// A replaced character counts as one of each
+pub fn char_edit_distance(left: &str, right: &str) -> usize {
+    count_edits(&chars(left), &chars(right)).1
+}
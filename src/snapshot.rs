@@ -0,0 +1,104 @@
+use crate::error::Context;
+use crate::runner::{Snapshot, Summary};
+use crate::structured::StructuredFormat;
+use crate::{DifferentError, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Replace the expected values of the failed checks in 'summary' with what they found instead,
+/// like accepting snapshots with cargo-insta.
+/// Only checks defined in 'path' itself can be updated, not included or matrix ones.
+/// Returns the names of the checks that were updated
+pub fn update_snapshots(path: &Path, summary: &Summary) -> Result<Vec<String>> {
+    let snapshots: HashMap<&str, &[Snapshot]> = summary
+        .results
+        .iter()
+        .filter(|result| !result.snapshots.is_empty())
+        .map(|result| (result.name.as_str(), result.snapshots.as_slice()))
+        .collect();
+    if snapshots.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let Some(format) = StructuredFormat::from_path(path) else {
+        return Err(DifferentError::Invalid(format!(
+            "Unknown checks file format {}",
+            path.display()
+        )));
+    };
+    let text =
+        fs::read_to_string(path).with_context(|| format!("Unable to read {}", path.display()))?;
+    let (text, updated) = match format {
+        StructuredFormat::Toml => update_toml(&text, &snapshots),
+        StructuredFormat::Yaml | StructuredFormat::Json => update_yaml(&text, &snapshots, format),
+    }
+    .with_context(|| format!("Unable to update snapshots in {}", path.display()))?;
+
+    if !updated.is_empty() {
+        fs::write(path, text).with_context(|| format!("Unable to write {}", path.display()))?;
+    }
+    Ok(updated)
+}
+
+/// Edit the TOML in place so comments and formatting survive
+fn update_toml(
+    text: &str,
+    snapshots: &HashMap<&str, &[Snapshot]>,
+) -> Result<(String, Vec<String>)> {
+    let mut doc: toml_edit::DocumentMut = text.parse()?;
+    let mut updated = Vec::new();
+    if let Some(checks) = doc
+        .get_mut("checks")
+        .and_then(|checks| checks.as_array_of_tables_mut())
+    {
+        for check in checks.iter_mut() {
+            let Some(name) = check.get("name").and_then(|name| name.as_str()) else {
+                continue;
+            };
+            let Some(found) = snapshots.get(name) else {
+                continue;
+            };
+            let name = name.to_string();
+            for snapshot in found.iter() {
+                check[snapshot.field] = toml_edit::value(snapshot.actual.as_str());
+            }
+            updated.push(name);
+        }
+    }
+    Ok((doc.to_string(), updated))
+}
+
+/// YAML and JSON files are rewritten, keeping the order of keys but not comments
+fn update_yaml(
+    text: &str,
+    snapshots: &HashMap<&str, &[Snapshot]>,
+    format: StructuredFormat,
+) -> Result<(String, Vec<String>)> {
+    let mut doc: serde_yaml::Value = serde_yaml::from_str(text)?;
+    let mut updated = Vec::new();
+    if let Some(checks) = doc
+        .get_mut("checks")
+        .and_then(|checks| checks.as_sequence_mut())
+    {
+        for check in checks.iter_mut() {
+            let Some(name) = check.get("name").and_then(|name| name.as_str()) else {
+                continue;
+            };
+            let Some(found) = snapshots.get(name) else {
+                continue;
+            };
+            let name = name.to_string();
+            for snapshot in found.iter() {
+                check[snapshot.field] = snapshot.actual.clone().into();
+            }
+            updated.push(name);
+        }
+    }
+
+    let text = match format {
+        StructuredFormat::Json => serde_json::to_string_pretty(&doc)? + "\n",
+        _ => serde_yaml::to_string(&doc)?,
+    };
+    Ok((text, updated))
+}
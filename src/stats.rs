@@ -0,0 +1,60 @@
+use crate::Diff;
+use serde::Serialize;
+use std::fmt::Display;
+
+/// Line counts of a diff
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct DiffStats {
+    /// Lines only in the right input
+    pub insertions: usize,
+    /// Lines only in the left input
+    pub deletions: usize,
+    /// Lines in both inputs. Always 0 for [`Diff::Same`], which doesn't keep the lines around
+    pub unchanged: usize,
+    /// Share of lines the inputs have in common, from 0.0 (nothing) to 1.0 (the same)
+    pub similarity: f64,
+}
+
+impl<T> Diff<'_, T> {
+    /// Count the inserted, deleted and unchanged lines
+    pub fn stats(&self) -> DiffStats {
+        let Self::Diff { diff, .. } = self else {
+            return DiffStats {
+                similarity: 1.0,
+                ..DiffStats::default()
+            };
+        };
+
+        let mut stats = DiffStats::default();
+        for line in diff {
+            match line {
+                diff::Result::Left(_) => stats.deletions += 1,
+                diff::Result::Both(..) => stats.unchanged += 1,
+                diff::Result::Right(_) => stats.insertions += 1,
+            }
+        }
+        // Every unchanged line is on both sides
+        let total = 2 * stats.unchanged + stats.insertions + stats.deletions;
+        stats.similarity = if total == 0 {
+            1.0
+        } else {
+            (2 * stats.unchanged) as f64 / total as f64
+        };
+        stats
+    }
+}
+
+impl Display for DiffStats {
+    /// git's summary line, e.g. "3 insertions(+), 1 deletion(-)"
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let plural = |n: usize| if n == 1 { "" } else { "s" };
+        write!(
+            f,
+            "{} insertion{}(+), {} deletion{}(-)",
+            self.insertions,
+            plural(self.insertions),
+            self.deletions,
+            plural(self.deletions)
+        )
+    }
+}
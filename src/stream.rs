@@ -0,0 +1,156 @@
+use crate::DiffSettings;
+use crate::algorithm::{self, Edit};
+use std::collections::VecDeque;
+use std::io::{self, BufRead};
+
+/// Lines buffered on each side when looking for the end of a change.
+/// Changes spanning more lines than this are still reported, just not aligned as well
+const WINDOW: usize = 256;
+
+/// Lines of a reader, split the same way [`crate::line_diff`] splits text
+struct Lines<R> {
+    reader: R,
+    ended_with_newline: bool,
+    done: bool,
+}
+
+impl<R> Lines<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            reader,
+            ended_with_newline: false,
+            done: false,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Lines<R> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let mut line = String::new();
+        match self.reader.read_line(&mut line) {
+            Err(e) => Some(Err(e)),
+            Ok(0) => {
+                self.done = true;
+                // Text ending in a newline has one last empty line
+                self.ended_with_newline.then(|| Ok(String::new()))
+            }
+            Ok(_) => {
+                self.ended_with_newline = line.ends_with('\n');
+                // A carriage return before the newline stays, see `DiffSettings::normalize`
+                if self.ended_with_newline {
+                    line.pop();
+                }
+                Some(Ok(line))
+            }
+        }
+    }
+}
+
+/// A lazily computed line diff, see [`line_diff_iter`]
+pub struct LineDiffIter<'s, L, R> {
+    settings: &'s DiffSettings,
+    left: Lines<L>,
+    right: Lines<R>,
+    left_buf: VecDeque<String>,
+    right_buf: VecDeque<String>,
+    pending: VecDeque<diff::Result<String>>,
+}
+
+impl<L: BufRead, R: BufRead> LineDiffIter<'_, L, R> {
+    fn fill(&mut self, lines: usize) -> io::Result<()> {
+        while self.left_buf.len() < lines {
+            match self.left.next().transpose()? {
+                Some(line) => self.left_buf.push_back(line),
+                None => break,
+            }
+        }
+        while self.right_buf.len() < lines {
+            match self.right.next().transpose()? {
+                Some(line) => self.right_buf.push_back(line),
+                None => break,
+            }
+        }
+        Ok(())
+    }
+
+    /// Diff the buffered lines and queue up everything until the two sides line up again
+    fn queue_change(&mut self) {
+        let left_keys: Vec<_> = self
+            .left_buf
+            .iter()
+            .map(|line| self.settings.normalize(line))
+            .collect();
+        let right_keys: Vec<_> = self
+            .right_buf
+            .iter()
+            .map(|line| self.settings.normalize(line))
+            .collect();
+        let edits = algorithm::diff(&left_keys, &right_keys, self.settings.algorithm);
+
+        let (mut removed, mut added) = (0, 0);
+        for edit in edits {
+            match edit {
+                Edit::Equal(..) if removed + added > 0 => break,
+                Edit::Equal(..) => unreachable!("the first lines of both buffers differ"),
+                Edit::Delete(_) => removed += 1,
+                Edit::Insert(_) => added += 1,
+            }
+        }
+        self.pending
+            .extend(self.left_buf.drain(..removed).map(diff::Result::Left));
+        self.pending
+            .extend(self.right_buf.drain(..added).map(diff::Result::Right));
+    }
+}
+
+impl<L: BufRead, R: BufRead> Iterator for LineDiffIter<'_, L, R> {
+    type Item = io::Result<diff::Result<String>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(line) = self.pending.pop_front() {
+            return Some(Ok(line));
+        }
+        if let Err(e) = self.fill(1) {
+            return Some(Err(e));
+        }
+
+        match (self.left_buf.front(), self.right_buf.front()) {
+            (None, None) => None,
+            (Some(l), Some(r)) if self.settings.normalize(l) == self.settings.normalize(r) => {
+                let l = self.left_buf.pop_front()?;
+                let r = self.right_buf.pop_front()?;
+                Some(Ok(diff::Result::Both(l, r)))
+            }
+            _ => {
+                if let Err(e) = self.fill(WINDOW) {
+                    return Some(Err(e));
+                }
+                self.queue_change();
+                self.pending.pop_front().map(Ok)
+            }
+        }
+    }
+}
+
+/// Diff two readers line by line without reading either of them into memory.
+/// Only a window of lines around each change is buffered, so inputs of any size can be compared.
+/// Lines are yielded as soon as they are known, read errors are yielded in their place
+pub fn line_diff_iter<L: BufRead, R: BufRead>(
+    left: L,
+    right: R,
+    settings: &DiffSettings,
+) -> LineDiffIter<'_, L, R> {
+    LineDiffIter {
+        settings,
+        left: Lines::new(left),
+        right: Lines::new(right),
+        left_buf: VecDeque::new(),
+        right_buf: VecDeque::new(),
+        pending: VecDeque::new(),
+    }
+}
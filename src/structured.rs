@@ -0,0 +1,137 @@
+use crate::error::Context;
+use crate::json::json_patch;
+use crate::paint::Paint;
+use crate::value::readable_path;
+use crate::{DiffSettings, JsonOp, Result, ValueChange, json_diff};
+use serde_json::Value;
+use std::fmt::Display;
+use std::path::Path;
+
+/// Data formats that can be compared as value trees instead of lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum StructuredFormat {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl StructuredFormat {
+    /// Guess the format from a file extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "json" => Some(Self::Json),
+            "yaml" | "yml" => Some(Self::Yaml),
+            "toml" => Some(Self::Toml),
+            _ => None,
+        }
+    }
+
+    pub fn parse(&self, text: &str) -> Result<Value> {
+        let value = match self {
+            Self::Json => serde_json::from_str(text)?,
+            Self::Yaml => serde_yaml::from_str(text)?,
+            Self::Toml => serde_json::to_value(text.parse::<toml::Table>()?)?,
+        };
+        Ok(value)
+    }
+}
+
+/// A change between two documents, with a readable path like `spec.containers[0].image`
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructuredChange {
+    pub path: String,
+    pub op: JsonOp,
+}
+
+impl From<&StructuredChange> for ValueChange {
+    fn from(change: &StructuredChange) -> Self {
+        let path = change.path.clone();
+        match &change.op {
+            JsonOp::Add { value, .. } => Self::Added {
+                path,
+                new: value.clone(),
+            },
+            JsonOp::Remove { value, .. } => Self::Removed {
+                path,
+                old: value.clone(),
+            },
+            JsonOp::Replace { old, new, .. } => Self::Changed {
+                path,
+                old: old.clone(),
+                new: new.clone(),
+            },
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum StructuredDiff<'a> {
+    Same,
+    Diff {
+        settings: &'a DiffSettings,
+        changes: Vec<StructuredChange>,
+    },
+}
+
+impl StructuredDiff<'_> {
+    pub fn changes(&self) -> &[StructuredChange] {
+        match self {
+            Self::Same => &[],
+            Self::Diff { changes, .. } => changes,
+        }
+    }
+    /// The diff as an RFC 6902 JSON Patch, an empty one if the documents are the same
+    pub fn to_json_patch(&self) -> serde_json::Result<String> {
+        json_patch(self.changes().iter().map(|change| &change.op))
+    }
+}
+
+impl Display for StructuredDiff<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self::Diff { settings, changes } = self else {
+            return Ok(());
+        };
+        let theme = settings.theme();
+        let indent = " ".repeat(settings.indent_spaces);
+        let paint = settings.color_enabled();
+
+        settings.write_headers(f)?;
+        for change in changes {
+            let style = match change.op {
+                JsonOp::Remove { .. } => theme.left,
+                JsonOp::Add { .. } | JsonOp::Replace { .. } => theme.right,
+            };
+            let line = format!("{indent}{}", ValueChange::from(change));
+            writeln!(f, "{}", style.apply(line).paint(paint))?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse both inputs in 'format' and compare the resulting value trees
+pub fn structured_diff<'a>(
+    left: &str,
+    right: &str,
+    format: StructuredFormat,
+    settings: &'a DiffSettings,
+) -> Result<StructuredDiff<'a>> {
+    let left = format.parse(left).context("Unable to parse left input")?;
+    let right = format.parse(right).context("Unable to parse right input")?;
+
+    let changes: Vec<_> = json_diff(&left, &right, settings)
+        .ops()
+        .iter()
+        .map(|op| StructuredChange {
+            path: readable_path(op.path(), &left, &right),
+            op: op.clone(),
+        })
+        .collect();
+
+    if changes.is_empty() {
+        Ok(StructuredDiff::Same)
+    } else {
+        Ok(StructuredDiff::Diff { settings, changes })
+    }
+}
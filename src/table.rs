@@ -0,0 +1,232 @@
+use crate::DiffSettings;
+use crate::error::Context;
+use crate::paint::Paint;
+use crate::{DifferentError, Result};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+
+/// A cell that differs between two rows with the same key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CellChange {
+    pub column: String,
+    pub old: String,
+    pub new: String,
+}
+
+/// A change between two tables. Rows are addressed by the value in their key column
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TableChange {
+    AddedColumn(String),
+    RemovedColumn(String),
+    AddedRow { key: String, row: Vec<String> },
+    RemovedRow { key: String, row: Vec<String> },
+    ChangedRow { key: String, cells: Vec<CellChange> },
+}
+
+#[derive(Debug)]
+pub enum TableDiff<'a> {
+    Same,
+    Diff {
+        settings: &'a DiffSettings,
+        /// Name of the column rows are matched by
+        key_column: String,
+        changes: Vec<TableChange>,
+    },
+}
+
+impl TableDiff<'_> {
+    pub fn changes(&self) -> &[TableChange] {
+        match self {
+            Self::Same => &[],
+            Self::Diff { changes, .. } => changes,
+        }
+    }
+}
+
+impl Display for TableDiff<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self::Diff {
+            settings,
+            key_column,
+            changes,
+        } = self
+        else {
+            return Ok(());
+        };
+        let theme = settings.theme();
+        let indent = " ".repeat(settings.indent_spaces);
+        let paint = settings.color_enabled();
+
+        settings.write_headers(f)?;
+        for change in changes {
+            let (style, line) = match change {
+                TableChange::AddedColumn(column) => (theme.right, format!("+ column {column}")),
+                TableChange::RemovedColumn(column) => (theme.left, format!("- column {column}")),
+                TableChange::AddedRow { key, row } => (
+                    theme.right,
+                    format!("+ {key_column}={key}: {}", row.join(",")),
+                ),
+                TableChange::RemovedRow { key, row } => (
+                    theme.left,
+                    format!("- {key_column}={key}: {}", row.join(",")),
+                ),
+                TableChange::ChangedRow { key, cells } => {
+                    for CellChange { column, old, new } in cells {
+                        let line =
+                            format!("{indent}~ {key_column}={key}: {column}: {old} -> {new}");
+                        writeln!(f, "{}", theme.right.apply(line).paint(paint))?;
+                    }
+                    continue;
+                }
+            };
+            writeln!(f, "{}", style.apply(format!("{indent}{line}")).paint(paint))?;
+        }
+        Ok(())
+    }
+}
+
+/// A parsed table: its header and rows keyed by the key column, in the order they appear
+struct Table {
+    columns: Vec<String>,
+    rows: Vec<(String, Vec<String>)>,
+}
+
+fn parse_table(text: &str, delimiter: u8, key_column: Option<&str>, side: &str) -> Result<Table> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_reader(text.as_bytes());
+    let columns: Vec<String> = reader
+        .headers()
+        .with_context(|| format!("Unable to parse the {side} input"))?
+        .iter()
+        .map(str::to_string)
+        .collect();
+    let key = match key_column {
+        Some(name) => match columns.iter().position(|column| column == name) {
+            Some(key) => key,
+            None => {
+                return Err(DifferentError::Invalid(format!(
+                    "The {side} input has no column '{name}'"
+                )));
+            }
+        },
+        None if columns.is_empty() => {
+            return Err(DifferentError::Invalid(format!(
+                "The {side} input has no columns"
+            )));
+        }
+        None => 0,
+    };
+
+    let mut seen = HashMap::new();
+    let mut rows = Vec::new();
+    for (i, record) in reader.records().enumerate() {
+        let record = record.with_context(|| format!("Unable to parse the {side} input"))?;
+        let mut row: Vec<String> = record.iter().map(str::to_string).collect();
+        // Short rows are missing their last cells
+        row.resize(row.len().max(columns.len()), String::new());
+        let value = row[key].clone();
+        if let Some(first) = seen.insert(value.clone(), i) {
+            return Err(DifferentError::Invalid(format!(
+                "Rows {} and {} of the {side} input have the same {} '{value}'",
+                first + 1,
+                i + 1,
+                columns[key]
+            )));
+        }
+        rows.push((value, row));
+    }
+    Ok(Table { columns, rows })
+}
+
+/// Compare two delimited tables, like CSV or TSV files with a header row.
+/// Rows are matched by their value in the key column, named by the left input's first column
+/// unless set in 'settings', and cells by their column name, so reordering rows or columns
+/// isn't a change
+pub fn table_diff<'a>(
+    left: &str,
+    right: &str,
+    delimiter: u8,
+    settings: &'a DiffSettings,
+) -> Result<TableDiff<'a>> {
+    let left = parse_table(left, delimiter, settings.key_column.as_deref(), "left")?;
+    // The right table is keyed by the same column name, wherever it is
+    let key_column = settings
+        .key_column
+        .clone()
+        .unwrap_or_else(|| left.columns[0].clone());
+    let right = parse_table(right, delimiter, Some(&key_column), "right")?;
+
+    let mut changes = Vec::new();
+    for column in &left.columns {
+        if !right.columns.contains(column) {
+            changes.push(TableChange::RemovedColumn(column.clone()));
+        }
+    }
+    for column in &right.columns {
+        if !left.columns.contains(column) {
+            changes.push(TableChange::AddedColumn(column.clone()));
+        }
+    }
+
+    // Where each column of the left table is in the right one
+    let common: Vec<(usize, usize, &String)> = left
+        .columns
+        .iter()
+        .enumerate()
+        .filter_map(|(l, column)| {
+            let r = right.columns.iter().position(|c| c == column)?;
+            Some((l, r, column))
+        })
+        .collect();
+    let right_rows: HashMap<&str, &Vec<String>> = right
+        .rows
+        .iter()
+        .map(|(key, row)| (key.as_str(), row))
+        .collect();
+
+    for (key, left_row) in &left.rows {
+        let Some(right_row) = right_rows.get(key.as_str()) else {
+            changes.push(TableChange::RemovedRow {
+                key: key.clone(),
+                row: left_row.clone(),
+            });
+            continue;
+        };
+        let cells: Vec<CellChange> = common
+            .iter()
+            .filter(|(l, r, _)| left_row[*l] != right_row[*r])
+            .map(|(l, r, column)| CellChange {
+                column: column.to_string(),
+                old: left_row[*l].clone(),
+                new: right_row[*r].clone(),
+            })
+            .collect();
+        if !cells.is_empty() {
+            changes.push(TableChange::ChangedRow {
+                key: key.clone(),
+                cells,
+            });
+        }
+    }
+    let left_keys: HashSet<&str> = left.rows.iter().map(|(key, _)| key.as_str()).collect();
+    for (key, row) in &right.rows {
+        if !left_keys.contains(key.as_str()) {
+            changes.push(TableChange::AddedRow {
+                key: key.clone(),
+                row: row.clone(),
+            });
+        }
+    }
+
+    if changes.is_empty() {
+        Ok(TableDiff::Same)
+    } else {
+        Ok(TableDiff::Diff {
+            settings,
+            key_column,
+            changes,
+        })
+    }
+}
@@ -0,0 +1,301 @@
+#[cfg(feature = "structured")]
+use crate::error::Context;
+use crate::{DifferentError, Result, parse_color};
+use colored::{Color, ColoredString, Colorize};
+use serde::Deserialize;
+#[cfg(feature = "structured")]
+use std::fs;
+#[cfg(feature = "structured")]
+use std::path::Path;
+use std::str::FromStr;
+
+/// Names of the built-in themes
+pub const THEMES: &[&str] = &["classic", "github", "solarized", "colorblind"];
+
+/// How one part of a diff is drawn
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Style {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub bold: bool,
+    pub dimmed: bool,
+    pub italic: bool,
+    pub underline: bool,
+}
+
+impl Style {
+    /// Just a foreground color
+    pub const fn fg(color: Color) -> Self {
+        Self {
+            fg: Some(color),
+            bg: None,
+            bold: false,
+            dimmed: false,
+            italic: false,
+            underline: false,
+        }
+    }
+
+    pub(crate) fn apply(&self, text: impl Into<ColoredString>) -> ColoredString {
+        let mut text = text.into();
+        if let Some(fg) = self.fg {
+            text = text.color(fg);
+        }
+        if let Some(bg) = self.bg {
+            text = text.on_color(bg);
+        }
+        if self.bold {
+            text = text.bold();
+        }
+        if self.dimmed {
+            text = text.dimmed();
+        }
+        if self.italic {
+            text = text.italic();
+        }
+        if self.underline {
+            text = text.underline();
+        }
+        text
+    }
+}
+
+impl FromStr for Style {
+    type Err = DifferentError;
+
+    /// Parse space separated words like "bright-blue on #303030 bold".
+    /// Words are colors as accepted by [`parse_color`], background colors after "on",
+    /// or bold, dimmed, italic and underline. "none" is no styling at all
+    fn from_str(s: &str) -> Result<Self> {
+        let mut style = Self::default();
+        let mut words = s.split_whitespace();
+        while let Some(word) = words.next() {
+            match word {
+                "none" => {}
+                "on" => {
+                    let Some(color) = words.next() else {
+                        return Err(DifferentError::InvalidTheme(format!(
+                            "Expected a background color after 'on' in '{s}'"
+                        )));
+                    };
+                    style.bg = Some(parse_color(color)?);
+                }
+                "bold" => style.bold = true,
+                "dim" | "dimmed" => style.dimmed = true,
+                "italic" => style.italic = true,
+                "underline" => style.underline = true,
+                color => style.fg = Some(parse_color(color)?),
+            }
+        }
+        Ok(style)
+    }
+}
+
+impl TryFrom<String> for Style {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse().map_err(|e: DifferentError| e.full_message())
+    }
+}
+
+/// The styles a diff is drawn with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Theme {
+    /// Lines only in the left input
+    pub left: Style,
+    /// Lines only in the right input
+    pub right: Style,
+    /// Lines in both inputs
+    pub unchanged: Style,
+    /// The file names at the top. Unset, each side's header is drawn like its lines
+    pub header: Option<Style>,
+    /// Unset, line numbers are drawn like the rest of their line
+    pub line_number: Option<Style>,
+    /// The '-', '+' or '|' in front of each line. Unset, it's drawn like the rest of its line
+    pub marker: Option<Style>,
+    /// The words that differ within a changed left line, with a word diff.
+    /// Unset, they are the line's style in bold and underlined
+    pub left_changed: Option<Style>,
+    /// Like `left_changed`, for the right input
+    pub right_changed: Option<Style>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::classic()
+    }
+}
+
+impl Theme {
+    /// Green for the left input and red for the right, with dimmed unchanged lines
+    pub fn classic() -> Self {
+        Self {
+            left: Style::fg(Color::Green),
+            right: Style::fg(Color::Red),
+            unchanged: Style {
+                dimmed: true,
+                ..Style::default()
+            },
+            header: None,
+            line_number: None,
+            marker: None,
+            left_changed: None,
+            right_changed: None,
+        }
+    }
+
+    /// Red for removed lines and green for added ones, as on GitHub
+    pub fn github() -> Self {
+        Self {
+            left: Style::fg(Color::Red),
+            right: Style::fg(Color::Green),
+            unchanged: Style::default(),
+            header: Some(Style {
+                bold: true,
+                ..Style::default()
+            }),
+            line_number: Some(Style::fg(Color::BrightBlack)),
+            marker: None,
+            left_changed: Some(Style {
+                bg: Some(Color::Red),
+                ..Style::fg(Color::BrightWhite)
+            }),
+            right_changed: Some(Style {
+                bg: Some(Color::Green),
+                ..Style::fg(Color::BrightWhite)
+            }),
+        }
+    }
+
+    /// The Solarized accent colors, which work on light and dark backgrounds alike
+    pub fn solarized() -> Self {
+        let rgb = |r, g, b| Color::TrueColor { r, g, b };
+        Self {
+            left: Style::fg(rgb(0xdc, 0x32, 0x2f)),
+            right: Style::fg(rgb(0x85, 0x99, 0x00)),
+            unchanged: Style::fg(rgb(0x83, 0x94, 0x96)),
+            header: Some(Style {
+                bold: true,
+                ..Style::fg(rgb(0x26, 0x8b, 0xd2))
+            }),
+            line_number: Some(Style::fg(rgb(0x58, 0x6e, 0x75))),
+            marker: Some(Style::fg(rgb(0xb5, 0x89, 0x00))),
+            left_changed: Some(Style {
+                bg: Some(rgb(0x07, 0x36, 0x42)),
+                bold: true,
+                ..Style::fg(rgb(0xdc, 0x32, 0x2f))
+            }),
+            right_changed: Some(Style {
+                bg: Some(rgb(0x07, 0x36, 0x42)),
+                bold: true,
+                ..Style::fg(rgb(0x85, 0x99, 0x00))
+            }),
+        }
+    }
+
+    /// Orange and blue from the Okabe-Ito palette, which stay distinct with any color vision
+    pub fn colorblind() -> Self {
+        let rgb = |r, g, b| Color::TrueColor { r, g, b };
+        Self {
+            left: Style::fg(rgb(0xe6, 0x9f, 0x00)),
+            right: Style::fg(rgb(0x56, 0xb4, 0xe9)),
+            unchanged: Style {
+                dimmed: true,
+                ..Style::default()
+            },
+            header: Some(Style {
+                bold: true,
+                ..Style::default()
+            }),
+            line_number: None,
+            // Removed and added lines must be told apart by their marker too, not only by color
+            marker: Some(Style {
+                bold: true,
+                ..Style::default()
+            }),
+            left_changed: None,
+            right_changed: None,
+        }
+    }
+
+    /// One of the built-in [`THEMES`]
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "classic" => Some(Self::classic()),
+            "github" => Some(Self::github()),
+            "solarized" => Some(Self::solarized()),
+            "colorblind" => Some(Self::colorblind()),
+            _ => None,
+        }
+    }
+
+    /// Parse a theme file. Styles not given come from the `base` preset, or classic
+    #[cfg(feature = "structured")]
+    pub fn from_toml(text: &str) -> Result<Self> {
+        let file: ThemeFile = toml::from_str(text)?;
+        let base = file.base.as_deref().unwrap_or("classic");
+        let Some(mut theme) = Self::preset(base) else {
+            return Err(DifferentError::InvalidTheme(format!(
+                "Unknown base theme '{base}'"
+            )));
+        };
+        if let Some(style) = file.left {
+            theme.left = style;
+        }
+        if let Some(style) = file.right {
+            theme.right = style;
+        }
+        if let Some(style) = file.unchanged {
+            theme.unchanged = style;
+        }
+        theme.header = file.header.or(theme.header);
+        theme.line_number = file.line_number.or(theme.line_number);
+        theme.marker = file.marker.or(theme.marker);
+        theme.left_changed = file.left_changed.or(theme.left_changed);
+        theme.right_changed = file.right_changed.or(theme.right_changed);
+        Ok(theme)
+    }
+
+    #[cfg(feature = "structured")]
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Unable to read theme {}", path.display()))?;
+        Self::from_toml(&text).with_context(|| format!("Invalid theme {}", path.display()))
+    }
+}
+
+impl FromStr for Theme {
+    type Err = DifferentError;
+
+    /// A built-in theme by name, or else the path of a theme file
+    fn from_str(s: &str) -> Result<Self> {
+        if let Some(theme) = Self::preset(s) {
+            return Ok(theme);
+        }
+        #[cfg(feature = "structured")]
+        if Path::new(s).is_file() {
+            return Self::load(Path::new(s));
+        }
+        Err(DifferentError::InvalidTheme(format!(
+            "Unknown theme '{s}'. Valid values are {}, or the path of a theme file",
+            THEMES.join(", ")
+        )))
+    }
+}
+
+#[cfg(feature = "structured")]
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ThemeFile {
+    base: Option<String>,
+    left: Option<Style>,
+    right: Option<Style>,
+    unchanged: Option<Style>,
+    header: Option<Style>,
+    line_number: Option<Style>,
+    marker: Option<Style>,
+    left_changed: Option<Style>,
+    right_changed: Option<Style>,
+}
@@ -0,0 +1,440 @@
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
+use std::ops::RangeInclusive;
+
+/// A file of checks, as run by `diff check`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ChecksFile {
+    /// Other checks files to merge into this one, as glob patterns relative to this file
+    pub include: Vec<String>,
+    /// Variables available to templates, conditions and commands
+    pub variables: HashMap<String, String>,
+    /// Directory templates are loaded from, relative to the checks file.
+    /// Left unset, templates are found next to the checks file
+    pub templates: Option<String>,
+    /// How to show mismatched contents
+    pub diff: DiffOptions,
+    pub defaults: Defaults,
+    /// Commands run before any check. If one fails, no checks are run
+    pub setup: Vec<String>,
+    /// Commands run after all checks, whether or not setup and the checks succeeded
+    pub teardown: Vec<String>,
+    pub checks: Vec<Check>,
+}
+
+/// Settings for every check that doesn't set its own
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Defaults {
+    /// Seconds a check may run before it fails
+    pub timeout: Option<f64>,
+    /// Times a failing check is run again
+    pub retries: Option<u32>,
+    /// Seconds before the first retry
+    pub retry_delay: Option<f64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Check {
+    pub name: String,
+
+    /// Variables for this check only, overriding the file's.
+    /// Checks files can also give a `matrix` of values, which is expanded into one check
+    /// per combination with these variables set
+    #[serde(default)]
+    pub variables: HashMap<String, String>,
+
+    /// Only run the check if this expression is true, otherwise report it as skipped.
+    /// Besides the variables, `platform`, `arch` and `family` describe the OS
+    #[serde(alias = "only_if")]
+    pub when: Option<String>,
+
+    /// Skip the check if this expression is true, see `when`
+    pub skip_if: Option<String>,
+
+    /// Extra text added to the failure reason, rendered as a template with the variables
+    /// plus `name` and `reason`
+    pub message: Option<String>,
+
+    /// Seconds the check may run before it fails. Commands are killed, requests aborted
+    pub timeout: Option<f64>,
+
+    /// Times to run the check again if it fails, e.g. while a service is still starting
+    pub retries: Option<u32>,
+
+    /// Seconds to wait before the first retry, doubling after each one. Defaults to 1
+    pub retry_delay: Option<f64>,
+
+    /// Names of checks that have to pass before this one runs.
+    /// If any of them doesn't, this check is skipped
+    #[serde(default)]
+    pub needs: Vec<String>,
+
+    /// Commands run before the check. If one fails, the check errors
+    #[serde(default)]
+    pub before: Vec<String>,
+
+    /// Commands run after the check, even if it failed
+    #[serde(default)]
+    pub after: Vec<String>,
+
+    #[serde(flatten)]
+    pub check: CheckType,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CheckType {
+    File {
+        path: String,
+        /// The file must not exist. Nothing else is checked
+        #[serde(default)]
+        absent: bool,
+        #[serde(default)]
+        contains: Vec<String>,
+        /// Fragments the file must not contain
+        #[serde(default)]
+        not_contains: Vec<String>,
+        /// Regexes the file contents must match
+        #[serde(default)]
+        matches: Vec<String>,
+        template: Option<String>,
+        contents: Option<String>,
+        /// Unix permission bits in octal, like "755"
+        mode: Option<String>,
+        /// Smallest allowed size in bytes
+        min_size: Option<u64>,
+        /// Largest allowed size in bytes
+        max_size: Option<u64>,
+        /// Path of a file this one must have been modified after
+        newer_than: Option<String>,
+        /// Hex encoded SHA-256 digest of the contents. Binary files can only be checked this way
+        sha256: Option<String>,
+    },
+
+    Directory {
+        path: String,
+        /// The directory must not exist. Nothing else is checked
+        #[serde(default)]
+        absent: bool,
+        #[serde(default)]
+        children: Vec<String>,
+        /// Children that must not exist
+        #[serde(default)]
+        forbidden_children: Vec<String>,
+    },
+
+    /// Strict comparison of a directory tree against a manifest of expected files.
+    /// Unlike `Directory`, any file not listed in the manifest is an error
+    Tree {
+        path: String,
+        manifest: Vec<ManifestEntry>,
+    },
+
+    Command {
+        cmd: String,
+        /// Acceptable exit codes, 0 if not given
+        #[serde(default)]
+        code: ExpectedCode,
+        expected_stdout: Option<String>,
+        expected_stderr: Option<String>,
+        #[serde(default)]
+        stdout_contains: Vec<String>,
+        #[serde(default)]
+        stderr_contains: Vec<String>,
+        /// Fragments stdout must not contain
+        #[serde(default)]
+        stdout_not_contains: Vec<String>,
+        /// Fragments stderr must not contain
+        #[serde(default)]
+        stderr_not_contains: Vec<String>,
+        /// Regexes stdout must match
+        #[serde(default)]
+        stdout_matches: Vec<String>,
+        /// Regexes stderr must match
+        #[serde(default)]
+        stderr_matches: Vec<String>,
+        /// Variables to set from stdout for later checks, with the regex to take each from.
+        /// The value is the first capture group, or the whole match without one
+        #[serde(default)]
+        capture: BTreeMap<String, String>,
+        /// Run the command with the system shell, allowing pipelines and redirection
+        #[serde(default)]
+        shell: bool,
+        /// Input for the command
+        stdin: Option<Stdin>,
+    },
+
+    Http {
+        #[serde(default = "default_method")]
+        method: String,
+        #[serde(default = "default_http_code")]
+        code: u16,
+        url: String,
+        /// Request body, sent as is
+        body: Option<String>,
+        /// Template rendered with the check's variables as the request body
+        body_template: Option<String>,
+        /// Request headers. Values are rendered with the check's variables
+        #[serde(default)]
+        headers: BTreeMap<String, String>,
+        auth: Option<HttpAuth>,
+        /// Follow redirects instead of checking the redirect response itself
+        #[serde(default = "default_true")]
+        follow_redirects: bool,
+        /// Reject invalid or self-signed TLS certificates
+        #[serde(default = "default_true")]
+        verify_tls: bool,
+        #[serde(default)]
+        body_contains: Vec<String>,
+        expected_body: Option<String>,
+        /// Headers the response must have, with their exact values. Names are case insensitive
+        #[serde(default)]
+        expected_headers: BTreeMap<String, String>,
+        /// Assertions on the JSON response body, like `$.data.id == 42`
+        #[serde(default)]
+        json: Vec<JsonAssertion>,
+        /// Longest the whole request may take, in milliseconds
+        max_latency_ms: Option<u64>,
+    },
+
+    /// Connect to a TCP port. The check's timeout limits how long connecting may take
+    Tcp { host: String, port: u16 },
+
+    VarSet {
+        key: String,
+        value: Option<String>,
+        /// Regex the variable's value must match
+        matches: Option<String>,
+    },
+}
+
+impl CheckType {
+    /// The name of the check type, as written in checks files
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::File { .. } => "file",
+            Self::Directory { .. } => "directory",
+            Self::Tree { .. } => "tree",
+            Self::Command { .. } => "command",
+            Self::Http { .. } => "http",
+            Self::Tcp { .. } => "tcp",
+            Self::VarSet { .. } => "var_set",
+        }
+    }
+}
+
+/// Exit codes a command may return: a code, a list of codes,
+/// an inclusive range like "1-5", "nonzero" or "any"
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "RawCode")]
+pub enum ExpectedCode {
+    Exact(i32),
+    OneOf(Vec<i32>),
+    Range(RangeInclusive<i32>),
+    NonZero,
+    Any,
+}
+
+impl Default for ExpectedCode {
+    fn default() -> Self {
+        Self::Exact(0)
+    }
+}
+
+impl ExpectedCode {
+    /// Whether a command exiting with 'code' passes. Commands killed by a signal have no code
+    /// and only pass with `Any`
+    pub fn matches(&self, code: Option<i32>) -> bool {
+        let Some(code) = code else {
+            return *self == Self::Any;
+        };
+        match self {
+            Self::Exact(expected) => code == *expected,
+            Self::OneOf(expected) => expected.contains(&code),
+            Self::Range(expected) => expected.contains(&code),
+            Self::NonZero => code != 0,
+            Self::Any => true,
+        }
+    }
+}
+
+impl Display for ExpectedCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Exact(code) => write!(f, "{code}"),
+            Self::OneOf(codes) => {
+                let codes: Vec<String> = codes.iter().map(|c| c.to_string()).collect();
+                write!(f, "one of {}", codes.join(", "))
+            }
+            Self::Range(range) => write!(f, "{}-{}", range.start(), range.end()),
+            Self::NonZero => write!(f, "nonzero"),
+            Self::Any => write!(f, "any"),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawCode {
+    Exact(i32),
+    OneOf(Vec<i32>),
+    Text(String),
+}
+
+impl TryFrom<RawCode> for ExpectedCode {
+    type Error = String;
+
+    fn try_from(raw: RawCode) -> Result<Self, Self::Error> {
+        match raw {
+            RawCode::Exact(code) => Ok(Self::Exact(code)),
+            RawCode::OneOf(codes) => Ok(Self::OneOf(codes)),
+            RawCode::Text(text) => match text.as_str() {
+                "any" => Ok(Self::Any),
+                "nonzero" => Ok(Self::NonZero),
+                range => {
+                    let invalid = || {
+                        format!(
+                            "invalid exit code '{range}', expected a code, a list, a range like 1-5, 'nonzero' or 'any'"
+                        )
+                    };
+                    let (start, end) = range.split_once('-').ok_or_else(invalid)?;
+                    let start = start.trim().parse().map_err(|_| invalid())?;
+                    let end = end.trim().parse().map_err(|_| invalid())?;
+                    Ok(Self::Range(start..=end))
+                }
+            },
+        }
+    }
+}
+
+/// Credentials for an HTTP check. Values are rendered with the check's variables,
+/// so secrets can be passed with `--var` instead of written in the checks file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HttpAuth {
+    Basic { username: String, password: String },
+    Bearer(String),
+}
+
+/// An assertion on a JSON document, like `$.data.id == 42` or `/data/id == 42`.
+/// Paths are JSON pointers or simple JSONPaths of `.key`, `[index]` and `['key']` steps.
+/// Without `== value`, the path only has to exist. Values are JSON, or else plain strings
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct JsonAssertion {
+    /// The path as written
+    pub path: String,
+    /// The path as a JSON pointer
+    pub pointer: String,
+    pub expected: Option<serde_json::Value>,
+}
+
+impl TryFrom<String> for JsonAssertion {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        let (path, expected) = match text.split_once("==") {
+            Some((path, expected)) => {
+                let expected = expected.trim();
+                let value = serde_json::from_str(expected)
+                    .unwrap_or_else(|_| serde_json::Value::String(expected.to_string()));
+                (path.trim(), Some(value))
+            }
+            None => (text.trim(), None),
+        };
+        let pointer = json_pointer(path).ok_or_else(|| {
+            format!("invalid JSON path '{path}', expected like $.data.id or /data/id")
+        })?;
+        Ok(Self {
+            path: path.to_string(),
+            pointer,
+            expected,
+        })
+    }
+}
+
+/// Convert a JSONPath like `$.items[0]['a b']` to a JSON pointer. Pointers are returned as is
+fn json_pointer(path: &str) -> Option<String> {
+    if path.is_empty() || path.starts_with('/') {
+        return Some(path.to_string());
+    }
+    let mut rest = path.strip_prefix('$')?;
+    let mut pointer = String::new();
+    while !rest.is_empty() {
+        let key = if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            rest = &after[end..];
+            &after[..end]
+        } else {
+            let after = rest.strip_prefix('[')?;
+            let end = after.find(']')?;
+            rest = &after[end + 1..];
+            let key = &after[..end];
+            key.strip_prefix(['\'', '"'])
+                .and_then(|key| key.strip_suffix(['\'', '"']))
+                .unwrap_or(key)
+        };
+        if key.is_empty() {
+            return None;
+        }
+        pointer.push('/');
+        pointer.push_str(&key.replace('~', "~0").replace('/', "~1"));
+    }
+    Some(pointer)
+}
+
+/// Input for a command, given inline or as a file relative to the base directory
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Stdin {
+    Text(String),
+    File { file: String },
+}
+
+fn default_method() -> String {
+    String::from("GET")
+}
+
+fn default_http_code() -> u16 {
+    200
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// How diffs are shown when a check's contents don't match
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DiffOptions {
+    /// Print the diff at all
+    pub print: bool,
+    /// Force color on or off. Left unset, it depends on the terminal
+    pub color: Option<bool>,
+    /// Unchanged lines to show around each change. Left unset, every line is shown
+    pub context: Option<usize>,
+    /// Truncate the printed diff after this many lines
+    pub max_lines: Option<usize>,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            print: true,
+            color: None,
+            context: None,
+            max_lines: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ManifestEntry {
+    /// Path relative to the tree root, using '/' as separator
+    pub path: String,
+    pub sha256: Option<String>,
+    pub size: Option<u64>,
+}
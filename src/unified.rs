@@ -0,0 +1,186 @@
+use crate::ops::{Hunk, OpTag, group_ops, ops_from_results};
+use crate::{Diff, Side};
+use std::fmt::Write;
+use std::ops::Range;
+
+const NO_NEWLINE_MARKER: &str = "\\ No newline at end of file";
+
+/// Split 'text' into lines, flagging the final line if it lacks a trailing newline
+/// so that a missing newline at end of file counts as a change
+fn keyed_lines(text: &str) -> Vec<(&str, bool)> {
+    // Split on '\n' alone so CRLF lines keep their '\r', like GNU diff
+    let mut lines: Vec<(&str, bool)> = text
+        .split_inclusive('\n')
+        .map(|line| (line.strip_suffix('\n').unwrap_or(line), false))
+        .collect();
+    if !text.ends_with('\n')
+        && let Some(last) = lines.last_mut()
+    {
+        last.1 = true;
+    }
+    lines
+}
+
+/// Format a hunk range the way GNU diff does: 1-based start, length omitted when it is 1
+fn format_range(range: &Range<usize>) -> String {
+    match range.len() {
+        0 => format!("{},0", range.start),
+        1 => format!("{}", range.start + 1),
+        len => format!("{},{len}", range.start + 1),
+    }
+}
+
+fn push_line(out: &mut String, prefix: char, (line, no_newline): (&str, bool)) {
+    out.push(prefix);
+    out.push_str(line);
+    out.push('\n');
+    if no_newline {
+        out.push_str(NO_NEWLINE_MARKER);
+        out.push('\n');
+    }
+}
+
+/// Render a standard unified diff (as produced by `diff -u`) of 'left' and 'right'
+/// with 'context' lines of context around each change.
+/// Returns an empty string if the inputs are identical
+pub fn unified_diff(
+    left: &str,
+    right: &str,
+    left_label: &str,
+    right_label: &str,
+    context: usize,
+) -> String {
+    let left_lines = keyed_lines(left);
+    let right_lines = keyed_lines(right);
+    let results = diff::slice(&left_lines, &right_lines);
+    let hunks = group_ops(&ops_from_results(&results), context);
+    render(&left_lines, &right_lines, &hunks, left_label, right_label)
+}
+
+impl<T: AsRef<str>> Diff<'_, T> {
+    /// The diff as a standard unified diff with 'context' lines of context around each change,
+    /// headed by the input names. Unlike [`unified_diff`], the lines are compared as the
+    /// settings say, and hunks whose changes all match an ignore pattern are left out.
+    /// Empty if the inputs are the same
+    pub fn to_unified(&self, context: usize) -> String {
+        let Self::Diff { settings, diff } = self else {
+            return String::new();
+        };
+
+        // Lines are split on '\n', so each side ends with the text after its last newline:
+        // nothing if the input ends with a newline, otherwise a line missing its newline
+        let left_count = diff
+            .iter()
+            .filter(|line| !matches!(line, diff::Result::Right(_)))
+            .count();
+        let right_count = diff
+            .iter()
+            .filter(|line| !matches!(line, diff::Result::Left(_)))
+            .count();
+        fn last_line<T: AsRef<str>>(line: &T, count: usize, index: usize) -> Option<(&str, bool)> {
+            let line = line.as_ref();
+            match (index + 1 == count, line.is_empty()) {
+                (true, true) => None,
+                (last, _) => Some((line, last)),
+            }
+        }
+
+        let mut left_lines = Vec::new();
+        let mut right_lines = Vec::new();
+        let mut results = Vec::new();
+        for line in diff {
+            let (left, right) = match line {
+                diff::Result::Left(l) => (last_line(l, left_count, left_lines.len()), None),
+                diff::Result::Right(r) => (None, last_line(r, right_count, right_lines.len())),
+                diff::Result::Both(l, r) => (
+                    last_line(l, left_count, left_lines.len()),
+                    last_line(r, right_count, right_lines.len()),
+                ),
+            };
+            match (left, right) {
+                // A missing newline at end of file changes the line
+                (Some(left), Some(right)) if left.1 == right.1 => {
+                    left_lines.push(left);
+                    right_lines.push(right);
+                    results.push(diff::Result::Both((), ()));
+                }
+                (left, right) => {
+                    if let Some(left) = left {
+                        left_lines.push(left);
+                        results.push(diff::Result::Left(()));
+                    }
+                    if let Some(right) = right {
+                        right_lines.push(right);
+                        results.push(diff::Result::Right(()));
+                    }
+                }
+            }
+        }
+
+        let ignored = |(line, _): &(&str, bool)| settings.ignores(line);
+        let hunks: Vec<Hunk> = group_ops(&ops_from_results(&results), context)
+            .into_iter()
+            .filter(|hunk| {
+                !hunk
+                    .ops
+                    .iter()
+                    .filter(|op| op.tag != OpTag::Equal)
+                    .all(|op| {
+                        left_lines[op.left.clone()].iter().all(ignored)
+                            && right_lines[op.right.clone()].iter().all(ignored)
+                    })
+            })
+            .collect();
+
+        let left_label = settings
+            .left_name()
+            .map_or(Side::Left.to_string(), str::to_string);
+        let right_label = settings
+            .right_name()
+            .map_or(Side::Right.to_string(), str::to_string);
+        render(&left_lines, &right_lines, &hunks, &left_label, &right_label)
+    }
+}
+
+/// Write 'hunks' of the lines in 'left_lines' and 'right_lines' below a `---`/`+++` header.
+/// Returns an empty string if there are no hunks
+fn render(
+    left_lines: &[(&str, bool)],
+    right_lines: &[(&str, bool)],
+    hunks: &[Hunk],
+    left_label: &str,
+    right_label: &str,
+) -> String {
+    let mut out = String::new();
+    if hunks.is_empty() {
+        return out;
+    }
+
+    writeln!(out, "--- {left_label}").unwrap();
+    writeln!(out, "+++ {right_label}").unwrap();
+    for hunk in hunks {
+        writeln!(
+            out,
+            "@@ -{} +{} @@",
+            format_range(&hunk.left_range()),
+            format_range(&hunk.right_range())
+        )
+        .unwrap();
+        for op in &hunk.ops {
+            if op.tag == OpTag::Equal {
+                for i in op.left.clone() {
+                    push_line(&mut out, ' ', left_lines[i]);
+                }
+                continue;
+            }
+            for i in op.left.clone() {
+                push_line(&mut out, '-', left_lines[i]);
+            }
+            for j in op.right.clone() {
+                push_line(&mut out, '+', right_lines[j]);
+            }
+        }
+    }
+
+    out
+}
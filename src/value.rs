@@ -0,0 +1,103 @@
+use crate::JsonOp;
+use crate::json::{diff_values, escape_token};
+use serde_json::Value;
+use std::fmt::Display;
+
+/// A change between two value trees, with a readable path like `spec.containers[0].image`
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValueChange {
+    Added {
+        path: String,
+        new: Value,
+    },
+    Removed {
+        path: String,
+        old: Value,
+    },
+    Changed {
+        path: String,
+        old: Value,
+        new: Value,
+    },
+}
+
+impl ValueChange {
+    pub fn path(&self) -> &str {
+        match self {
+            Self::Added { path, .. } => path,
+            Self::Removed { path, .. } => path,
+            Self::Changed { path, .. } => path,
+        }
+    }
+}
+
+impl Display for ValueChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Added { path, new } => write!(f, "+ {path}: {new}"),
+            Self::Removed { path, old } => write!(f, "- {path}: {old}"),
+            Self::Changed { path, old, new } => write!(f, "~ {path}: {old} -> {new}"),
+        }
+    }
+}
+
+fn is_identifier(key: &str) -> bool {
+    !key.is_empty()
+        && !key.starts_with(|c: char| c.is_ascii_digit())
+        && key
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+}
+
+/// The readable path of the 'key' member of the object at 'parent'
+pub(crate) fn key_path(parent: &str, key: &str) -> String {
+    match parent {
+        "." if is_identifier(key) => key.to_string(),
+        "." => format!("[{}]", Value::String(key.to_string())),
+        _ if is_identifier(key) => format!("{parent}.{key}"),
+        _ => format!("{parent}[{}]", Value::String(key.to_string())),
+    }
+}
+
+/// The readable path of item 'index' of the array at 'parent'
+pub(crate) fn index_path(parent: &str, index: impl Display) -> String {
+    let parent = if parent == "." { "" } else { parent };
+    format!("{parent}[{index}]")
+}
+
+/// Turn a JSON Pointer into a dotted path. Every container along the pointer exists in
+/// at least one of the documents, which tells whether a token is an array index or a key
+pub(crate) fn readable_path(pointer: &str, left: &Value, right: &Value) -> String {
+    let mut path = String::from(".");
+    let mut current = (Some(left), Some(right));
+    for token in pointer.split('/').skip(1) {
+        let token = token.replace("~1", "/").replace("~0", "~");
+        path = match current.0.or(current.1) {
+            Some(Value::Array(_)) => index_path(&path, &token),
+            _ => key_path(&path, &token),
+        };
+        let child = format!("/{}", escape_token(&token));
+        current = (
+            current.0.and_then(|v| v.pointer(&child)),
+            current.1.and_then(|v| v.pointer(&child)),
+        );
+    }
+    path
+}
+
+/// Compare two value trees, listing what was added, removed or changed at each path.
+/// Returns no changes if they are equal
+pub fn value_diff(left: &Value, right: &Value) -> Vec<ValueChange> {
+    let mut ops = Vec::new();
+    diff_values("", ".", left, right, &[], &mut ops);
+    ops.into_iter()
+        .map(|op| {
+            let path = readable_path(op.path(), left, right);
+            match op {
+                JsonOp::Add { value, .. } => ValueChange::Added { path, new: value },
+                JsonOp::Remove { value, .. } => ValueChange::Removed { path, old: value },
+                JsonOp::Replace { old, new, .. } => ValueChange::Changed { path, old, new },
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,63 @@
+use anyhow::{Context, Result};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// How long to wait for more changes before running again, since saving a file often takes several writes
+const SETTLE: Duration = Duration::from_millis(100);
+
+/// Call 'run' now and again whenever one of 'paths' changes, until interrupted.
+/// Directories are watched recursively. Files are watched through their directory,
+/// so editors that save by replacing the file are noticed too.
+/// Errors from 'run' are printed and watching goes on
+pub fn watch(paths: &[&Path], mut run: impl FnMut() -> Result<()>) -> Result<()> {
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+        if let Ok(event) = event {
+            let _ = sender.send(event);
+        }
+    })
+    .context("Unable to watch for changes")?;
+
+    let mut targets: Vec<PathBuf> = Vec::new();
+    for path in paths {
+        let path = path
+            .canonicalize()
+            .with_context(|| format!("Unable to watch {}", path.display()))?;
+        let (watched, mode) = if path.is_dir() {
+            (path.as_path(), RecursiveMode::Recursive)
+        } else {
+            let parent = path.parent().unwrap_or(Path::new("/"));
+            (parent, RecursiveMode::NonRecursive)
+        };
+        watcher
+            .watch(watched, mode)
+            .with_context(|| format!("Unable to watch {}", watched.display()))?;
+        targets.push(path);
+    }
+    // Reading a file isn't a change
+    let relevant = |event: &Event| {
+        !matches!(event.kind, EventKind::Access(_))
+            && event
+                .paths
+                .iter()
+                .any(|path| targets.iter().any(|target| path.starts_with(target)))
+    };
+
+    loop {
+        if let Err(e) = run() {
+            eprintln!("Error: {e:?}");
+        }
+        // Changes made while running, like files written by checks, don't count
+        while receiver.recv_timeout(SETTLE).is_ok() {}
+
+        loop {
+            let event = receiver.recv().context("Stopped watching for changes")?;
+            if relevant(&event) {
+                break;
+            }
+        }
+        while receiver.recv_timeout(SETTLE).is_ok() {}
+    }
+}
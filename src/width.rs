@@ -0,0 +1,55 @@
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Columns between tab stops
+const TAB_WIDTH: usize = 8;
+
+/// Columns 'grapheme' takes up when it starts at column 'column'
+fn grapheme_width(grapheme: &str, column: usize) -> usize {
+    if grapheme == "\t" {
+        TAB_WIDTH - column % TAB_WIDTH
+    } else {
+        grapheme.width()
+    }
+}
+
+/// Columns 'text' takes up in a terminal. Wide characters like CJK take two,
+/// combining characters none, and tabs reach the next tab stop
+pub(crate) fn display_width(text: &str) -> usize {
+    text.graphemes(true).fold(0, |column, grapheme| {
+        column + grapheme_width(grapheme, column)
+    })
+}
+
+/// Pad or truncate 'text' to exactly 'width' columns, ending truncated text with '…'.
+/// Tabs are expanded to spaces, so the columns hold wherever the text is drawn
+pub(crate) fn fit(text: &str, width: usize) -> String {
+    let truncate = display_width(text) > width;
+    let room = if truncate {
+        width.saturating_sub(1)
+    } else {
+        width
+    };
+
+    let mut fitted = String::with_capacity(text.len());
+    let mut column = 0;
+    for grapheme in text.graphemes(true) {
+        let grapheme_width = grapheme_width(grapheme, column);
+        if column + grapheme_width > room {
+            break;
+        }
+        if grapheme == "\t" {
+            fitted.extend(std::iter::repeat_n(' ', grapheme_width));
+        } else {
+            fitted.push_str(grapheme);
+        }
+        column += grapheme_width;
+    }
+    if truncate && width > 0 {
+        fitted.push('…');
+        column += 1;
+    }
+    // A wide character that didn't fit leaves a gap
+    fitted.extend(std::iter::repeat_n(' ', width.saturating_sub(column)));
+    fitted
+}
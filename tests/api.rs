@@ -0,0 +1,1059 @@
+use different::{
+    Algorithm, ColorMode, Diff, DiffSettings, DiffSettingsBuilder, DifferentError, JsonOp,
+    LineKind, Op, OpTag, Patch, Segment, WordDiff, group_ops, hex_rows, inline_diff, is_binary,
+    json_diff, line_diff, line_diff_iter, slice_diff, unified_diff,
+};
+#[cfg(feature = "structured")]
+use different::{StructuredFormat, Theme, structured_diff};
+use serde_json::json;
+
+#[test]
+fn json_diff_reports_pointer_ops() {
+    let settings = DiffSettings::new();
+    let left = json!({"name": "a", "tags": ["x", "y"], "a/b": 1});
+    let right = json!({"name": "b", "tags": ["x"], "extra": true});
+    let diff = json_diff(&left, &right, &settings);
+    assert_eq!(
+        diff.ops(),
+        &[
+            JsonOp::Remove {
+                path: "/a~1b".to_string(),
+                value: json!(1)
+            },
+            JsonOp::Replace {
+                path: "/name".to_string(),
+                old: json!("a"),
+                new: json!("b")
+            },
+            JsonOp::Remove {
+                path: "/tags/1".to_string(),
+                value: json!("y")
+            },
+            JsonOp::Add {
+                path: "/extra".to_string(),
+                value: json!(true)
+            },
+        ]
+    );
+
+    let patch: serde_json::Value = serde_json::from_str(&diff.to_json_patch().unwrap()).unwrap();
+    assert_eq!(
+        patch,
+        json!([
+            {"op": "remove", "path": "/a~1b"},
+            {"op": "replace", "path": "/name", "value": "b"},
+            {"op": "remove", "path": "/tags/1"},
+            {"op": "add", "path": "/extra", "value": true},
+        ])
+    );
+}
+
+#[test]
+fn group_ops_limits_context() {
+    let left = "a\nb\nc\nd\ne\nf\ng\nh\ni\n";
+    let right = "a\nB\nc\nd\ne\nf\ng\nh\nI\n";
+    let settings = DiffSettings::new();
+    let diff = line_diff(left, right, &settings);
+    let hunks = group_ops(&diff.ops(), 1);
+    assert_eq!(hunks.len(), 2);
+    assert_eq!(hunks[0].left_range(), 0..3);
+    assert_eq!(
+        hunks[1].ops,
+        vec![
+            Op::new(OpTag::Equal, 7..8, 7..8),
+            Op::new(OpTag::Replace, 8..9, 8..9),
+            Op::new(OpTag::Equal, 9..10, 9..10),
+        ]
+    );
+}
+
+#[test]
+fn unified_diff_matches_gnu_format() {
+    let left = "a\nb\nc\nd\n";
+    let right = "a\nB\nc\nd";
+    let expected = "\
+--- old
++++ new
+@@ -1,4 +1,4 @@
+ a
+-b
++B
+ c
+-d
++d
+\\ No newline at end of file
+";
+    assert_eq!(unified_diff(left, right, "old", "new", 3), expected);
+    assert_eq!(unified_diff(left, left, "old", "new", 3), "");
+}
+
+#[test]
+fn apply_round_trips() {
+    let left = "one\ntwo\nthree\n";
+    let right = "one\n2\nthree\nfour\n";
+    let settings = DiffSettings::new();
+    let diff = line_diff(left, right, &settings);
+    assert_eq!(diff.apply(left).unwrap(), right);
+    assert!(diff.apply("one\nTWO\nthree\n").is_err());
+}
+
+#[test]
+fn invert_applies_backwards() {
+    let left = "one\ntwo\nthree\n";
+    let right = "one\n2\nthree\nfour\n";
+    let settings = DiffSettings::new();
+    let diff = line_diff(left, right, &settings).invert();
+    assert_eq!(diff.apply(right).unwrap(), left);
+}
+
+#[test]
+fn compose_chains_generations() {
+    let a = "one\ntwo\nthree\n";
+    let b = "one\n2\nthree\nfour\n";
+    let c = "zero\none\n2\nfour\n";
+    let settings = DiffSettings::new();
+    let a_to_b = line_diff(a, b, &settings);
+    let b_to_c = line_diff(b, c, &settings);
+    let a_to_c = Diff::compose(a_to_b, b_to_c).unwrap();
+    assert_eq!(a_to_c.apply(a).unwrap(), c);
+
+    let back = line_diff(b, a, &settings);
+    let round_trip = Diff::compose(line_diff(a, b, &settings), back).unwrap();
+    assert!(matches!(round_trip, Diff::Same));
+}
+
+#[test]
+fn context_lines_collapse_unchanged_runs() {
+    let left = "a\nb\nc\nd\ne\nf\ng\n";
+    let right = "a\nb\nc\nD\ne\nf\ng\n";
+    let settings = DiffSettings::builder()
+        .color(false)
+        .context_lines(1)
+        .build();
+    let diff = line_diff(left, right, &settings);
+    let expected = "\
+---- left
+++++ right
+  ...
+  3  3 | c
+  4    - d
+     4 + D
+  5  5 | e
+  ...
+";
+    assert_eq!(diff.to_string(), expected);
+}
+
+#[test]
+fn alternate_format_is_plain() {
+    let settings = DiffSettings::builder()
+        .names("old".to_string(), "new".to_string())
+        .build();
+    let diff = line_diff("a\nb", "a\nc", &settings);
+    assert_eq!(
+        format!("{diff:#}"),
+        "---- left:  old\n++++ right: new\n  a\n- b\n+ c\n"
+    );
+}
+
+#[test]
+fn inline_diff_marks_changed_words() {
+    let (left, right) = inline_diff("let x = 1;", "let y = 1;", WordDiff::Word);
+    let changed = |segments: &[Segment]| {
+        segments
+            .iter()
+            .filter(|s| s.changed)
+            .map(|s| s.text.clone())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(changed(&left), vec!["x"]);
+    assert_eq!(changed(&right), vec!["y"]);
+
+    let (left, _) = inline_diff("color", "colour", WordDiff::Char);
+    assert!(left.iter().all(|s| !s.changed));
+}
+
+#[test]
+fn lines_and_hunks_expose_structure() {
+    let left = "a\nb\nc\nd\ne\nf\ng\n";
+    let right = "a\nB\nc\nd\ne\nf\nG\n";
+    let settings = DiffSettings::new();
+    let diff = line_diff(left, right, &settings);
+
+    let changed: Vec<_> = diff
+        .lines()
+        .filter(|line| line.kind != LineKind::Unchanged)
+        .map(|line| {
+            (
+                line.left_lineno,
+                line.right_lineno,
+                line.kind,
+                *line.content,
+            )
+        })
+        .collect();
+    assert_eq!(
+        changed,
+        vec![
+            (Some(2), None, LineKind::Removed, "b"),
+            (None, Some(2), LineKind::Added, "B"),
+            (Some(7), None, LineKind::Removed, "g"),
+            (None, Some(7), LineKind::Added, "G"),
+        ]
+    );
+
+    let hunks: Vec<_> = diff.hunks(1).collect();
+    assert_eq!(hunks.len(), 2);
+    assert_eq!(hunks[0].left_start(), Some(1));
+    assert_eq!(hunks[1].lines.len(), 4);
+}
+
+#[test]
+fn row_count_matches_the_printed_diff() {
+    let left = "a\nb\nc\nd\ne\nf\ng\nh\n";
+    let right = "a\nB\nc\nd\ne\nf\ng\nH\n";
+    let all = [
+        DiffSettings::new(),
+        DiffSettings::builder().context_lines(1).build(),
+        DiffSettings::builder()
+            .context_lines(1)
+            .only_changes(true)
+            .show_header(false)
+            .build(),
+    ];
+    for settings in &all {
+        let diff = line_diff(left, right, settings);
+        assert_eq!(
+            diff.row_count(usize::MAX),
+            diff.to_string().lines().count(),
+            "{settings:?}"
+        );
+        assert_eq!(diff.row_count(3), 3);
+    }
+    assert_eq!(line_diff(left, left, &all[0]).row_count(10), 0);
+}
+
+#[test]
+fn whitespace_options_ignore_spacing() {
+    let left = "fn main() {\n    let x = 1;\n}\n";
+    let right = "fn main()  {\n\tlet x = 1;   \n\n}\n";
+
+    let settings = DiffSettings::builder().ignore_space_change(true).build();
+    assert!(matches!(
+        line_diff(left, right, &settings),
+        Diff::Diff { .. }
+    ));
+
+    let settings = DiffSettingsBuilder::from(settings)
+        .ignore_blank_lines(true)
+        .build();
+    assert!(matches!(line_diff(left, right, &settings), Diff::Same));
+
+    let settings = DiffSettings::builder().ignore_all_space(true).build();
+    let diff = line_diff("a b\n", "ab\n", &settings);
+    assert!(matches!(diff, Diff::Same));
+    let diff = line_diff("a b\n", "ac\n", &settings);
+    assert!(matches!(diff, Diff::Diff { .. }));
+}
+
+#[test]
+fn html_escapes_lines_and_follows_layout() {
+    let settings = DiffSettings::new();
+    let diff = line_diff("<a>\nb\n", "<a>\nb & c\n", &settings);
+    let html = diff.to_html();
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains("<td>&lt;a&gt;</td>"));
+    assert!(html.contains("<td class=\"marker\">+</td><td>b &amp; c</td>"));
+
+    let settings = DiffSettings::builder().side_by_side(true).build();
+    let html = line_diff("a\nb\n", "a\nc\n", &settings).to_html();
+    assert!(html.contains(
+        "<tr><td class=\"num\">2</td><td class=\"removed\">b</td><td class=\"num\">2</td><td class=\"added\">c</td></tr>"
+    ));
+}
+
+#[test]
+fn write_to_matches_display() {
+    let settings = DiffSettings::new();
+    let diff = line_diff("a\nb\n", "a\nc\n", &settings);
+    let mut out = Vec::new();
+    diff.write_to(&mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), diff.to_string());
+}
+
+#[test]
+fn markdown_fences_hunks_and_outlasts_backticks() {
+    let settings = DiffSettings::builder().context_lines(0).build();
+    let diff = line_diff("a\n```\n", "a\n```rust\n", &settings);
+    assert_eq!(
+        diff.to_markdown(false),
+        "````diff\n--- left\n+++ right\n@@ -2 +2 @@\n-```\n+```rust\n````\n"
+    );
+
+    let collapsible = diff.to_markdown(true);
+    assert!(collapsible.starts_with("<details>\n<summary>left → right</summary>\n\n"));
+    assert!(collapsible.ends_with("\n</details>\n"));
+    assert_eq!(line_diff("a\n", "a\n", &settings).to_markdown(true), "");
+}
+
+#[test]
+fn color_mode_is_per_diff() {
+    let colored = DiffSettings::builder()
+        .color_mode(ColorMode::Always)
+        .build();
+    let plain = DiffSettings::builder().color_mode(ColorMode::Never).build();
+
+    let colored_diff = line_diff("a\n", "b\n", &colored);
+    let plain_diff = line_diff("a\n", "b\n", &plain);
+    assert!(colored_diff.to_string().contains("\x1B[32m"));
+    assert!(!plain_diff.to_string().contains('\x1B'));
+    // Formatting one doesn't change how the other renders
+    assert!(colored_diff.to_string().contains("\x1B[32m"));
+}
+
+#[test]
+#[cfg(feature = "structured")]
+fn structured_diff_reports_readable_paths() {
+    let settings = DiffSettings::new();
+    let left = "[server]\nhosts = [\"a\", \"b\"]\n\"dotted.key\" = 1\n";
+    let right = "[server]\nhosts = [\"a\", \"c\"]\n\"dotted.key\" = 2\n";
+    let diff = structured_diff(left, right, StructuredFormat::Toml, &settings).unwrap();
+    let paths: Vec<_> = diff.changes().iter().map(|c| c.path.as_str()).collect();
+    assert_eq!(paths, vec![r#"server["dotted.key"]"#, "server.hosts[1]"]);
+    assert_eq!(diff.changes()[1].op.path(), "/server/hosts/1");
+
+    let same = structured_diff("{\"a\": 1}", "a: 1\n", StructuredFormat::Yaml, &settings).unwrap();
+    assert!(same.changes().is_empty());
+}
+
+#[test]
+fn patch_round_trips_unified_diff() {
+    let left = "one\ntwo\nthree\nfour\n";
+    let right = "zero\none\n2\nthree\nfour";
+    let patch = Patch::parse(&unified_diff(left, right, "a", "b", 1)).unwrap();
+    assert_eq!(patch.files.len(), 1);
+
+    let file = &patch.files[0];
+    assert_eq!(file.apply(left).unwrap(), right);
+    assert_eq!(file.reverse().apply(right).unwrap(), left);
+    // Lines added above the hunks only shift them
+    let shifted = format!("header\n{left}");
+    assert_eq!(file.apply(&shifted).unwrap(), format!("header\n{right}"));
+    assert!(file.apply("unrelated\n").is_err());
+}
+
+#[test]
+fn patch_round_trips_crlf_lines() {
+    let left = "a\r\nb\r\nc\r\nd\r\ne\r\n";
+    let right = "a\r\nB\r\nc\r\nd\r\ne";
+    let patch = Patch::parse(&unified_diff(left, right, "a", "b", 1)).unwrap();
+    let file = &patch.files[0];
+    // Untouched lines keep their CRLF endings too
+    assert_eq!(file.apply(left).unwrap(), right);
+    assert_eq!(file.reverse().apply(right).unwrap(), left);
+    // The hunk's lines have CRLF endings, so it doesn't match LF lines
+    assert!(file.apply("a\nb\nc\nd\ne\n").is_err());
+}
+
+#[test]
+fn unified_output_follows_the_settings() {
+    let settings = DiffSettings::builder()
+        .names("a".into(), "b".into())
+        .build();
+    let diff = line_diff("one\r\ntwo\r\n", "one\ntwo\n", &settings);
+    assert_eq!(
+        diff.to_unified(3),
+        "--- a\n+++ b\n@@ -1,2 +1,2 @@\n-one\r\n-two\r\n+one\n+two\n"
+    );
+
+    let left = "one\ntwo\n";
+    let right = "one\nTWO\nthree";
+    assert_eq!(
+        line_diff(left, right, &settings).to_unified(3),
+        unified_diff(left, right, "a", "b", 3)
+    );
+
+    let settings = DiffSettings::builder()
+        .ignore_all_space(true)
+        .ignore_matching_lines(regex::Regex::new("^#").unwrap())
+        .build();
+    let left = "# 1\na\nb\nc\nd\ne\nf\ng\n";
+    let right = "# 2\na\nb\nc\nd\ne\nf\n g \nh\n";
+    assert_eq!(
+        line_diff(left, right, &settings).to_unified(1),
+        "--- left\n+++ right\n@@ -8 +8,2 @@\n g\n+h\n"
+    );
+}
+
+#[test]
+fn zero_context_patches_round_trip() {
+    let left = "one\ntwo\nthree\nfour\n";
+    let right = "one\nX\ntwo\nfour\nfive\n";
+    let text = unified_diff(left, right, "a", "b", 0);
+    assert!(text.contains("@@ -1,0 +2 @@"), "{text}");
+
+    let file = &Patch::parse(&text).unwrap().files[0];
+    assert_eq!(file.apply(left).unwrap(), right);
+    assert_eq!(file.reverse().apply(right).unwrap(), left);
+}
+
+#[test]
+fn algorithms_produce_valid_diffs() {
+    let cases = [
+        ("a\nb\nc\n", "a\nc\nd\n"),
+        ("", "x\ny\n"),
+        ("x\ny\n", ""),
+        ("a\nb\na\nb\na\n", "b\na\nb\na\nb\n"),
+        (
+            "fn a() {\n    1\n}\n\nfn b() {\n    2\n}\n",
+            "fn b() {\n    2\n}\n\nfn a() {\n    1\n}\n",
+        ),
+    ];
+    let changes = |diff: &Diff<&str>| {
+        diff.lines()
+            .filter(|line| line.kind != LineKind::Unchanged)
+            .count()
+    };
+
+    for algorithm in [Algorithm::Myers, Algorithm::Patience, Algorithm::Histogram] {
+        let settings = DiffSettings::builder().algorithm(algorithm).build();
+        for (left, right) in cases {
+            let diff = line_diff(left, right, &settings);
+            assert_eq!(diff.apply(left).unwrap(), right, "{algorithm:?}");
+            if algorithm == Algorithm::Myers {
+                // Myers finds a shortest edit script, never longer than the diff crate's
+                let baseline = diff::lines(left, right)
+                    .iter()
+                    .filter(|line| !matches!(line, diff::Result::Both(..)))
+                    .count();
+                assert!(changes(&diff) <= baseline);
+            }
+        }
+    }
+}
+
+#[test]
+fn equal_inputs_short_circuit() {
+    let text = "line\n".repeat(10_000);
+    assert!(matches!(
+        line_diff(&text, &text, &DiffSettings::new()),
+        Diff::Same
+    ));
+
+    let settings = DiffSettings::builder().ignore_case(true).build();
+    assert!(matches!(
+        line_diff("A\nb\n", "a\nB\n", &settings),
+        Diff::Same
+    ));
+    assert!(matches!(
+        line_diff("a\nb\n", "a\nb", &settings),
+        Diff::Diff { .. }
+    ));
+}
+
+#[test]
+fn streaming_diff_matches_line_diff() {
+    let left = "a\nb\nc\nd\ne\n";
+    let right = "a\nB\nc\ne\nf";
+    let settings = DiffSettings::new();
+    let streamed: Vec<_> = line_diff_iter(left.as_bytes(), right.as_bytes(), &settings)
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let Diff::Diff { diff, .. } = line_diff(left, right, &settings) else {
+        panic!("inputs differ");
+    };
+    let expected: Vec<_> = diff
+        .into_iter()
+        .map(|line| match line {
+            diff::Result::Left(l) => diff::Result::Left(l.to_string()),
+            diff::Result::Both(l, r) => diff::Result::Both(l.to_string(), r.to_string()),
+            diff::Result::Right(r) => diff::Result::Right(r.to_string()),
+        })
+        .collect();
+    assert_eq!(streamed, expected);
+
+    // Far more lines than are ever buffered
+    let left: String = (0..100_000).map(|i| format!("{i}\n")).collect();
+    let right = left.replace("\n5000\n", "\nfive thousand\n");
+    let changes: Vec<_> = line_diff_iter(left.as_bytes(), right.as_bytes(), &settings)
+        .map(Result::unwrap)
+        .filter(|line| !matches!(line, diff::Result::Both(..)))
+        .collect();
+    assert_eq!(
+        changes,
+        vec![
+            diff::Result::Left("5000".to_string()),
+            diff::Result::Right("five thousand".to_string())
+        ]
+    );
+}
+
+#[test]
+fn slice_diff_renders_any_display_type() {
+    let settings = DiffSettings::builder().color(false).build();
+    let left = [1.5, 2.0, 3.25];
+    let right = [1.5, 2.5, 3.25];
+    let diff = slice_diff(&left, &right, &settings);
+    assert_eq!(
+        format!("{diff:#}"),
+        "---- left\n++++ right\n  1.5\n- 2\n+ 2.5\n  3.25\n"
+    );
+    assert!(matches!(slice_diff(&left, &left, &settings), Diff::Same));
+}
+
+#[test]
+fn hex_rows_of_binary_input() {
+    assert!(is_binary(b"a\0b"));
+    assert!(is_binary(&[0xff, 0xfe]));
+    assert!(!is_binary("caf\u{e9}\n".as_bytes()));
+
+    let rows = hex_rows(b"0123456789abcdef\x00\x7f!");
+    assert_eq!(rows.len(), 2);
+    assert_eq!(
+        rows[1].to_string(),
+        format!("00000010  00 7f 21{}  |..!|", " ".repeat(39))
+    );
+}
+
+#[test]
+fn stats_count_lines() {
+    let settings = DiffSettings::new();
+    let stats = line_diff("a\nb\nc\n", "a\nB\nc\nd\n", &settings).stats();
+    assert_eq!(
+        (stats.insertions, stats.deletions, stats.unchanged),
+        (2, 1, 3)
+    );
+    assert_eq!(stats.to_string(), "2 insertions(+), 1 deletion(-)");
+    assert!((stats.similarity - 2.0 / 3.0).abs() < 1e-9);
+    assert_eq!(line_diff("a", "a", &settings).stats().similarity, 1.0);
+}
+
+#[test]
+#[cfg(feature = "structured")]
+fn themes_style_each_part() {
+    let theme =
+        Theme::from_toml("base = \"github\"\nline_number = \"none\"\nmarker = \"blue bold\"\n")
+            .unwrap();
+    assert_eq!(theme.left, Theme::github().left);
+    assert_eq!(theme.line_number, Some(Default::default()));
+    assert!(Theme::from_toml("lft = \"red\"").is_err());
+    assert!("nonexistent-theme".parse::<Theme>().is_err());
+
+    let settings = DiffSettings::builder()
+        .theme(theme)
+        .color_mode(ColorMode::Always)
+        .build();
+    let diff = line_diff("a\n", "b\n", &settings).to_string();
+    // Bold header, red removed line with a bold blue marker
+    assert!(diff.starts_with("\x1B[1m---- left\x1B[0m\n"));
+    assert!(diff.contains("  1    \x1B[1;34m-\x1B[0m \x1B[31ma\x1B[0m\n"));
+}
+
+#[test]
+fn errors_can_be_told_apart() {
+    assert!(matches!(
+        different::parse_color("256"),
+        Err(DifferentError::InvalidColor(_))
+    ));
+    assert!(matches!(
+        different::parse_encoding("klingon"),
+        Err(DifferentError::UnknownEncoding(label)) if label == "klingon"
+    ));
+    let settings = DiffSettings::new();
+    let diff = line_diff("a\n", "b\n", &settings);
+    assert!(matches!(
+        diff.apply("c\n"),
+        Err(DifferentError::Mismatch(_))
+    ));
+}
+
+#[test]
+#[cfg(feature = "structured")]
+fn context_errors_keep_their_source() {
+    let settings = DiffSettings::new();
+    let error = structured_diff("{", "{}", StructuredFormat::Json, &settings).unwrap_err();
+    let DifferentError::Context { context, error } = &error else {
+        panic!("expected context, got {error:?}");
+    };
+    assert_eq!(context, "Unable to parse left input");
+    assert!(matches!(**error, DifferentError::Json(_)));
+
+    // Context only names what was being done, the error beneath is its source
+    let error = structured_diff("a: [", "a: 1", StructuredFormat::Yaml, &settings).unwrap_err();
+    assert!(matches!(error.inner(), DifferentError::Yaml(_)));
+    let source = std::error::Error::source(&error).unwrap();
+    assert_eq!(source.to_string(), error.inner().to_string());
+    assert_eq!(
+        error.full_message(),
+        format!("Unable to parse left input: {source}")
+    );
+}
+
+#[test]
+fn builder_sets_and_validates_every_field() {
+    let settings = DiffSettings::builder()
+        .left_name("old")
+        .right_name("new")
+        .left_marker('<')
+        .right_marker('>')
+        .marker_count(2)
+        .indent_spaces(0)
+        .left_color(colored::Color::Magenta)
+        .context_lines(1)
+        .build();
+    assert_eq!(settings.left_name(), Some("old"));
+    assert_eq!(settings.right_marker(), '>');
+    assert_eq!(settings.marker_count(), 2);
+    assert_eq!(settings.context_lines(), Some(1));
+    assert_eq!(settings.theme().left.fg, Some(colored::Color::Magenta));
+    let diff = line_diff("a\n", "b\n", &settings);
+    assert!(
+        diff.to_string()
+            .starts_with("<< left:  old\n>> right: new\n")
+    );
+
+    let same_markers = DiffSettings::builder().left_marker('+').try_build();
+    assert!(matches!(
+        same_markers,
+        Err(DifferentError::ConflictingSettings(_))
+    ));
+    // Only try_build checks the settings
+    let unchecked = DiffSettings::builder().left_marker('+').build();
+    assert!(unchecked.validate().is_err());
+    let width_alone = DiffSettings::builder().column_width(40).try_build();
+    assert!(width_alone.is_err());
+    assert!(
+        DiffSettings::builder()
+            .side_by_side(true)
+            .column_width(40)
+            .try_build()
+            .is_ok()
+    );
+}
+
+#[test]
+#[allow(deprecated)]
+fn deprecated_setters_forward_to_the_builder() {
+    let settings = DiffSettings::new()
+        .names("old".to_string(), "new".to_string())
+        .max_line_number(120);
+    assert_eq!(settings.left_name(), Some("old"));
+    assert_eq!(settings.right_name(), Some("new"));
+}
+
+#[test]
+fn plain_settings_give_bare_prefixed_lines() {
+    let settings = DiffSettings::plain();
+    let diff = line_diff("a\nb\n", "a\nc\n", &settings);
+    assert_eq!(
+        diff.to_string(),
+        "---- left\n++++ right\n  a\n- b\n+ c\n  \n"
+    );
+
+    let settings = DiffSettings::builder()
+        .plain()
+        .show_header(false)
+        .color(true)
+        .build();
+    assert!(!settings.show_line_numbers());
+    let diff = line_diff("a\n", "b\n", &settings).to_string();
+    assert!(diff.starts_with("\x1B[32m- a\x1B[0m\n"));
+}
+
+#[test]
+fn only_changes_drops_unchanged_lines() {
+    let settings = DiffSettings::builder().plain().only_changes(true).build();
+    let diff = line_diff("a\nb\nc\nd\n", "a\nB\nc\nd\ne\n", &settings);
+    assert_eq!(diff.to_string(), "---- left\n++++ right\n- b\n+ B\n+ e\n");
+
+    let with_context = DiffSettings::builder()
+        .only_changes(true)
+        .context_lines(1)
+        .try_build();
+    assert!(matches!(
+        with_context,
+        Err(DifferentError::ConflictingSettings(_))
+    ));
+}
+
+#[test]
+#[cfg(feature = "structured")]
+fn changed_words_can_have_a_background() {
+    let theme =
+        Theme::from_toml("left_changed = \"white on bright-red\"\nright_changed = \"on 46\"\n")
+            .unwrap();
+    assert!("red on".parse::<different::Style>().is_err());
+    assert!(different::parse_color("256").is_err());
+    assert_eq!(
+        different::parse_color("196").unwrap(),
+        colored::Color::TrueColor { r: 255, g: 0, b: 0 }
+    );
+
+    let settings = DiffSettings::builder()
+        .theme(theme)
+        .word_diff(WordDiff::Word)
+        .color_mode(ColorMode::Always)
+        .build();
+    let diff = line_diff("let x = 1;\n", "let y = 1;\n", &settings).to_string();
+    // Only the changed word gets the background
+    assert!(
+        diff.contains("\x1B[32mlet \x1B[0m\x1B[101;37mx\x1B[0m"),
+        "{diff:?}"
+    );
+}
+
+#[test]
+fn encodings_are_detected() {
+    use different::{decode, detect_encoding};
+    let utf16: Vec<u8> = "hi\n".encode_utf16().flat_map(u16::to_be_bytes).collect();
+    assert_eq!(detect_encoding(&utf16).map(|e| e.name()), Some("UTF-16BE"));
+    assert_eq!(
+        detect_encoding(b"caf\xe9").map(|e| e.name()),
+        Some("windows-1252")
+    );
+    assert_eq!(
+        detect_encoding("café".as_bytes()).map(|e| e.name()),
+        Some("UTF-8")
+    );
+    assert_eq!(detect_encoding(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), None);
+    assert_eq!(decode(b"\xef\xbb\xbfhi", None).as_deref(), Some("hi"));
+
+    // Binary data without NULs isn't mistaken for Windows-1252
+    assert_eq!(detect_encoding(b"\x89\x01\x02\xff\x03\x04\xfe"), None);
+    assert_eq!(
+        detect_encoding(b"r\xe9sum\xe9\r\n\tna\xefve\n").map(|e| e.name()),
+        Some("windows-1252")
+    );
+    // A given encoding isn't overridden by a byte order mark
+    let latin1 = different::parse_encoding("latin1").ok();
+    assert_eq!(
+        decode(b"\xff\xfeh", latin1).as_deref(),
+        Some("\u{ff}\u{fe}h")
+    );
+    let utf8 = different::parse_encoding("utf-8").ok();
+    assert_eq!(decode(b"\xef\xbb\xbfhi", utf8).as_deref(), Some("hi"));
+}
+
+#[test]
+fn carriage_returns_count_unless_stripped() {
+    let settings = DiffSettings::new();
+    assert!(!matches!(
+        line_diff("a\r\nb\r\n", "a\nb\n", &settings),
+        Diff::Same
+    ));
+    let settings = DiffSettings::builder().strip_trailing_cr(true).build();
+    assert!(matches!(
+        line_diff("a\r\nb\r\n", "a\nb\n", &settings),
+        Diff::Same
+    ));
+}
+
+#[test]
+fn similarity_ranks_near_matches() {
+    use different::{char_edit_distance, char_similarity, edit_distance, similarity};
+    assert_eq!(similarity("a\nb\n", "a\nb\n"), 1.0);
+    assert_eq!(similarity("", ""), 1.0);
+    assert_eq!(char_similarity("abc", "xyz"), 0.0);
+    assert_eq!(edit_distance("a\nb\nc\n", "a\nx\nc\n"), 2);
+    assert_eq!(char_edit_distance("kitten", "sitting"), 5);
+
+    let candidates = ["checkout", "cherry-pick", "commit"];
+    let best = candidates
+        .iter()
+        .max_by(|a, b| char_similarity("chekout", a).total_cmp(&char_similarity("chekout", b)))
+        .unwrap();
+    assert_eq!(*best, "checkout");
+}
+
+#[test]
+fn opcodes_match_difflib() {
+    let left: Vec<char> = "qabxcd".chars().collect();
+    let right: Vec<char> = "abycdf".chars().collect();
+    let ops: Vec<String> = different::opcodes(&left, &right)
+        .iter()
+        .map(|op| format!("{} {:?} {:?}", op.tag, op.left, op.right))
+        .collect();
+    // From difflib.SequenceMatcher(None, "qabxcd", "abycdf").get_opcodes()
+    assert_eq!(
+        ops,
+        [
+            "delete 0..1 0..0",
+            "equal 1..3 0..2",
+            "replace 3..4 2..3",
+            "equal 4..6 3..5",
+            "insert 6..6 5..6",
+        ]
+    );
+    assert_eq!(
+        different::opcodes(&[1, 2], &[1, 2]),
+        [Op::new(OpTag::Equal, 0..2, 0..2)]
+    );
+}
+
+#[test]
+fn restore_round_trips_both_inputs() {
+    use different::Side;
+    let settings = DiffSettings::builder().ignore_case(true).build();
+    let pairs = [
+        ("a\nb\nc\n", "a\nx\nc\n"),
+        ("a\nb", "a\nb\n"),
+        ("", "only right\n"),
+        ("only left\n", ""),
+        ("Same But Case\r\nend\n", "same but case\nend"),
+    ];
+    for (left, right) in pairs {
+        let diff = line_diff(left, right, &settings);
+        assert_eq!(diff.restore(Side::Left).as_deref(), Some(left));
+        assert_eq!(diff.restore(Side::Right).as_deref(), Some(right));
+    }
+    assert_eq!(line_diff("a\n", "a\n", &settings).restore(Side::Left), None);
+}
+
+#[cfg(feature = "assert")]
+#[test]
+fn assert_diff_eq_passes_for_equal_text() {
+    different::assert_diff_eq!("a\nb\n", String::from("a\nb\n"));
+    different::assert_debug_diff_eq!(vec![1, 2], vec![1, 2], "lists should match");
+}
+
+#[cfg(feature = "assert")]
+#[test]
+#[should_panic(expected = "assertion `left == right` failed: for case 3")]
+fn assert_diff_eq_panics_with_a_diff() {
+    different::assert_diff_eq!("a\nb\nc", "a\nx\nc", "for case {}", 3);
+}
+
+#[cfg(feature = "assert")]
+#[test]
+#[should_panic(expected = "3    -     2,\n     3 +     4,")]
+fn assert_debug_diff_eq_diffs_pretty_printed_values() {
+    different::assert_debug_diff_eq!(vec![1, 2, 3], vec![1, 4, 3]);
+}
+
+#[test]
+fn value_diff_reports_typed_changes() {
+    use different::{ValueChange, value_diff};
+    let left = json!({"name": "a", "tags": ["x"], "old": true});
+    let right = json!({"name": "b", "tags": ["x", "y"]});
+    let changes = value_diff(&left, &right);
+    assert_eq!(
+        changes[0],
+        ValueChange::Changed {
+            path: "name".to_string(),
+            old: "a".into(),
+            new: "b".into()
+        }
+    );
+    let lines: Vec<String> = changes.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        lines,
+        vec!["~ name: \"a\" -> \"b\"", "- old: true", "+ tags[1]: \"y\""]
+    );
+    assert!(value_diff(&left, &left).is_empty());
+}
+
+#[test]
+fn unordered_arrays_compare_as_multisets() {
+    let left = json!([{"b": 1, "a": [1, 2]}, 3, 3]);
+    let right = json!([3, {"a": [2, 1], "b": 1}, 4]);
+
+    let ordered = DiffSettings::new();
+    assert!(!json_diff(&left, &right, &ordered).ops().is_empty());
+
+    let settings = DiffSettings::builder()
+        .unordered_array(".")
+        .unordered_array("[*].a")
+        .build();
+    let diff = json_diff(&left, &right, &settings);
+    assert_eq!(
+        diff.ops(),
+        &[
+            JsonOp::Remove {
+                path: "/2".to_string(),
+                value: json!(3)
+            },
+            JsonOp::Add {
+                path: "/2".to_string(),
+                value: json!(4)
+            },
+        ]
+    );
+
+    // Key order never matters
+    let reordered = json!({"b": 1, "a": 2});
+    assert!(
+        json_diff(&json!({"a": 2, "b": 1}), &reordered, &ordered)
+            .ops()
+            .is_empty()
+    );
+}
+
+#[test]
+#[cfg(feature = "table")]
+fn table_diff_matches_rows_by_key() {
+    use different::{CellChange, TableChange, table_diff};
+    let left = "sku,price,stock\na,1,5\nb,2,0\n";
+    let right = "stock,sku,price\n0,b,2\n5,a,3\n";
+    let settings = DiffSettings::builder().key_column("sku").build();
+    let diff = table_diff(left, right, b',', &settings).unwrap();
+    assert_eq!(
+        diff.changes(),
+        &[TableChange::ChangedRow {
+            key: "a".to_string(),
+            cells: vec![CellChange {
+                column: "price".to_string(),
+                old: "1".to_string(),
+                new: "3".to_string()
+            }]
+        }]
+    );
+
+    let reordered = "stock,sku,price\n0,b,2\n5,a,1\n";
+    assert!(
+        table_diff(left, reordered, b',', &settings)
+            .unwrap()
+            .changes()
+            .is_empty()
+    );
+    assert!(table_diff(left, "id\n1\n", b',', &settings).is_err());
+
+    // Without a key column, the right input is keyed by the left input's first column name
+    let settings = DiffSettings::default();
+    assert!(
+        table_diff(left, reordered, b',', &settings)
+            .unwrap()
+            .changes()
+            .is_empty()
+    );
+    assert!(table_diff(left, "id\n1\n", b',', &settings).is_err());
+}
+
+#[test]
+fn ignore_matching_lines_only_skips_matching_changes() {
+    let settings = DiffSettings::builder()
+        .ignore_matching_lines(regex::Regex::new(r"^id: \d+$").unwrap())
+        .build();
+    assert!(matches!(
+        line_diff("id: 1\nx\n", "id: 2\nx\n", &settings),
+        Diff::Same
+    ));
+    assert!(matches!(
+        line_diff("id: 1\nx\n", "id: 2\ny\n", &settings),
+        Diff::Diff { .. }
+    ));
+}
+
+#[test]
+fn normalizers_apply_before_comparing_only() {
+    use std::borrow::Cow;
+    let settings = DiffSettings::builder()
+        .preprocess("s/ID-[0-9]+/ID/gi".parse().unwrap())
+        .normalizer(|line| Cow::Borrowed(line.trim_end_matches('!')))
+        .build();
+    assert!(matches!(
+        line_diff("id-1 and ID-2!\n", "ID-3 and id-4\n", &settings),
+        Diff::Same
+    ));
+
+    let diff = line_diff("ID-1 a\nb\n", "ID-2 a\nc\n", &settings);
+    // Lines are shown as they are, not normalized
+    assert_eq!(
+        format!("{diff:#}"),
+        "---- left\n++++ right\n  ID-1 a\n- b\n+ c\n  \n"
+    );
+
+    assert!("s,a,b".parse::<different::Substitution>().is_err());
+    assert!("s/a/b/x".parse::<different::Substitution>().is_err());
+}
+
+#[test]
+fn tokenizers_split_on_their_own_boundaries() {
+    use different::Tokenizer;
+    assert_eq!(
+        WordDiff::Code.tokenize("a->b == 1.5;"),
+        vec!["a", "->", "b", " ", "==", " ", "1.5", ";"]
+    );
+    assert_eq!(
+        WordDiff::Prose.tokenize("don't 日本語"),
+        vec!["don't", " ", "日", "本", "語"]
+    );
+    assert_eq!(
+        WordDiff::Grapheme.tokenize("e\u{301}x"),
+        vec!["e\u{301}", "x"]
+    );
+
+    struct Cells;
+    impl Tokenizer for Cells {
+        fn tokenize<'a>(&self, line: &'a str) -> Vec<&'a str> {
+            line.split_inclusive(',').collect()
+        }
+    }
+    let settings = DiffSettings::builder()
+        .tokenizer(Cells)
+        .color_mode(ColorMode::Never)
+        .build();
+    let (left, right) = different::inline_diff_with("1,old value,3", "1,new value,3", &Cells);
+    assert_eq!(left[1].text, "old value,");
+    assert!(left[1].changed && right[1].changed);
+    // The custom tokenizer turns on highlighting within lines
+    assert!(settings.tokenizer().is_some());
+}
+
+#[cfg(feature = "highlight")]
+#[test]
+fn highlighting_colors_tokens_but_keeps_the_text() {
+    let left = "fn main() {\n    let x = 1;\n}\n";
+    let right = "fn main() {\n    let x = 2;\n}\n";
+    let build = |highlight: bool| {
+        DiffSettings::builder()
+            .names("a.rs".to_string(), "b.rs".to_string())
+            .color_mode(ColorMode::Always)
+            .highlight(highlight)
+            .build()
+    };
+    let (plain, highlighted) = (build(false), build(true));
+    let plain = line_diff(left, right, &plain).to_string();
+    let highlighted = line_diff(left, right, &highlighted).to_string();
+    assert_ne!(plain, highlighted);
+    assert!(highlighted.matches('\x1B').count() > plain.matches('\x1B').count());
+
+    let strip = |s: &str| {
+        regex::Regex::new("\x1B\\[[0-9;]*m")
+            .unwrap()
+            .replace_all(s, "")
+            .into_owned()
+    };
+    assert_eq!(strip(&plain), strip(&highlighted));
+}
+
+#[test]
+fn line_numbers_link_to_the_files() {
+    let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/cmd/simple.in");
+    let (left, right) = (dir.join("left"), dir.join("right"));
+    let left_text = std::fs::read_to_string(&left).unwrap();
+    let right_text = std::fs::read_to_string(&right).unwrap();
+    let settings = |links: bool| {
+        DiffSettings::builder()
+            .names(left.display().to_string(), right.display().to_string())
+            .color_mode(ColorMode::Always)
+            .hyperlinks(links)
+    };
+
+    let linked = settings(true).build();
+    let output = line_diff(&left_text, &right_text, &linked).to_string();
+    let url = format!("\x1B]8;;file://{}#L1\x1B\\1\x1B]8;;\x1B\\", left.display());
+    assert!(output.contains(&url), "{output:?}");
+
+    let custom = settings(false)
+        .hyperlink_format("edit:{path}:{line}")
+        .build();
+    let output = line_diff(&left_text, &right_text, &custom).to_string();
+    assert!(output.contains(&format!("edit:{}:2", right.display())));
+
+    let plain = settings(true).color_mode(ColorMode::Never).build();
+    assert!(
+        !line_diff(&left_text, &right_text, &plain)
+            .to_string()
+            .contains('\x1B')
+    );
+}
@@ -1,6 +1,173 @@
+#![cfg(feature = "cli")]
+
 #[test]
 fn cli_tests() {
     trycmd::TestCases::new()
         .case("tests/cmd/*.toml")
         .case("README.md");
 }
+
+/// Serve 'body' to a single request, returning the URL and the request it got
+fn serve_once(body: &'static [u8]) -> (String, std::sync::mpsc::Receiver<String>) {
+    use std::io::{Read, Write};
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!(
+        "http://{}/config.toml?version=2",
+        listener.local_addr().unwrap()
+    );
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0; 4096];
+        while !request.ends_with(b"\r\n\r\n") {
+            let n = stream.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            request.extend_from_slice(&buf[..n]);
+        }
+        let head = format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            body.len()
+        );
+        stream.write_all(head.as_bytes()).unwrap();
+        stream.write_all(body).unwrap();
+        let _ = sender.send(String::from_utf8_lossy(&request).to_lowercase());
+    });
+    (url, receiver)
+}
+
+#[test]
+fn url_inputs_are_fetched() {
+    let (url, request) = serve_once(b"name = \"prod\"\nport = 80\n");
+    let dir = std::env::temp_dir().join(format!("different-url-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("config.toml");
+    std::fs::write(&file, "name = \"prod\"\nport = 8080\n").unwrap();
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_diff"))
+        .args([
+            "--header",
+            "X-Env: prod",
+            "--user",
+            "me:secret",
+            "--color",
+            "never",
+        ])
+        .arg(&url)
+        .arg(&file)
+        .output()
+        .unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(output.status.code(), Some(1), "{stdout}");
+    // Compared as TOML, going by the extension of the URL's path
+    assert!(stdout.contains("port"), "{stdout}");
+    assert!(stdout.contains(&url), "{stdout}");
+    let request = request.recv().unwrap();
+    assert!(request.contains("x-env: prod"), "{request}");
+    assert!(
+        request.contains("authorization: basic bwu6c2vjcmv0"),
+        "{request}"
+    );
+}
+
+#[test]
+fn url_bodies_are_decoded_like_files() {
+    // Latin-1, which isn't valid UTF-8
+    let (url, _) = serve_once(b"caf\xe9\n");
+    let dir = std::env::temp_dir().join(format!("different-url-bytes-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("menu.txt");
+    std::fs::write(&file, "cafe\n").unwrap();
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_diff"))
+        .args(["--color", "never"])
+        .arg(&url)
+        .arg(&file)
+        .output()
+        .unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(output.status.code(), Some(1), "{stdout}");
+    assert!(stdout.contains("caf\u{e9}"), "{stdout}");
+}
+
+#[test]
+fn check_output_to_a_closed_pipe_exits_quietly() {
+    use std::fmt::Write as _;
+    use std::io::{BufRead, BufReader, Read};
+    let dir = std::env::temp_dir().join(format!("different-pipe-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    // Far more output than a pipe buffers, so the runner is still writing when the reader goes
+    let mut checks = String::from("[variables]\nversion = \"1.0\"\n");
+    for i in 0..5000 {
+        write!(
+            checks,
+            "[[checks]]\nname = \"version is set, check number {i:>40}\"\ntype = \"var_set\"\nkey = \"version\"\n"
+        )
+        .unwrap();
+    }
+    let file = dir.join("many.toml");
+    std::fs::write(&file, checks).unwrap();
+
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_diff"))
+        .arg("check")
+        .arg(&file)
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut first = String::new();
+    BufReader::new(child.stdout.take().unwrap())
+        .read_line(&mut first)
+        .unwrap();
+    let mut stderr = String::new();
+    child
+        .stderr
+        .take()
+        .unwrap()
+        .read_to_string(&mut stderr)
+        .unwrap();
+    let status = child.wait().unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(first.ends_with(": ok\n"), "{first}");
+    assert_eq!(status.code(), Some(2), "{stderr}");
+    assert_eq!(stderr, "");
+}
+
+#[cfg(unix)]
+#[test]
+fn recursive_compares_symlinks_without_following_them() {
+    use std::os::unix::fs::symlink;
+    let dir = std::env::temp_dir().join(format!("different-links-{}", std::process::id()));
+    for side in ["left", "right"] {
+        std::fs::create_dir_all(dir.join(side)).unwrap();
+        std::fs::write(dir.join(side).join("a.txt"), "same\n").unwrap();
+        // A link back up the tree, which would never end if it were followed
+        symlink("..", dir.join(side).join("loop")).unwrap();
+    }
+    symlink("a.txt", dir.join("left/current")).unwrap();
+    symlink("b.txt", dir.join("right/current")).unwrap();
+    symlink("a.txt", dir.join("left/kind")).unwrap();
+    std::fs::write(dir.join("right/kind"), "same\n").unwrap();
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_diff"))
+        .current_dir(&dir)
+        .args(["-r", "left", "right"])
+        .output()
+        .unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(output.status.code(), Some(1), "{stdout}");
+    assert_eq!(
+        stdout,
+        "Symbolic links left/current -> a.txt and right/current -> b.txt differ\n\
+         File left/kind is a symbolic link while file right/kind is a regular file\n"
+    );
+}